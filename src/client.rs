@@ -1,19 +1,56 @@
+use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::time::Duration;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use url::Url;
 
-use crate::connection::ConnectionManager;
+#[cfg(feature = "tls")]
+use crate::connection::TlsOptions;
+use crate::connection::{self, ConnectionCallbacks, ConnectionManager, ProtocolFactory, ShardedPool};
+#[cfg(feature = "srv-discovery")]
+use crate::discovery::{self, SrvDiscovery};
 use crate::error::{ClientError, MemcacheError};
-use crate::protocol::{Protocol, ProtocolTrait};
+use crate::fallback::FallbackClient;
+#[cfg(feature = "ketama")]
+use crate::hash::{self, KetamaRing};
+#[cfg(feature = "metrics")]
+use crate::instrument;
+use crate::instrument::{Custom, OperationStatus};
+use crate::key::{check_key_chars, KeyBuilder};
+#[cfg(feature = "test-mode")]
+use crate::mock::MockBackend;
+use crate::options::ClientOptions;
+use crate::pipeline::Pipeline;
+use crate::protocol::{AddResult, PipelineResult, Protocol, ProtocolTrait, RawValue, ValueWithMetadata};
+use crate::shadow::ShadowWriter;
+use crate::stats::{
+    self, CachedItem, ClusterInfo, ConnectionInfo, HealthStatus, ServerInfo, ServerLatency, SlabItemStats, Version,
+};
 use crate::stream::Stream;
-use crate::value::{FromMemcacheValueExt, ToMemcacheValue};
+#[cfg(feature = "compression")]
+use crate::value::CompressedValue;
+use crate::value::{FromMemcacheValueExt, MaybeCompressed, ToMemcacheValue, WithFlags};
+#[cfg(feature = "sticky-keys")]
+use glob::Pattern;
 use r2d2::Pool;
+#[cfg(feature = "key-hashing")]
+use sha2::{Digest, Sha256};
 
 pub type Stats = HashMap<String, String>;
 
+type MissObserver = Arc<dyn Fn(&str, bool) + Send + Sync>;
+
+/// The values found on each server by a multi-get, by the url of the server.
+type ServerValues<V> = Vec<(String, HashMap<String, V>)>;
+
+/// The entries to set on a server, with their keys prepared and their values compressed if needed.
+type ServerEntries<K, V> = Vec<(PreparedKey<K>, MaybeCompressed<V>, u32)>;
+
 pub trait Connectable {
     fn get_urls(self) -> Vec<String>;
 }
@@ -24,6 +61,18 @@ impl Connectable for (&str, u16) {
     }
 }
 
+impl Connectable for (String, u16) {
+    fn get_urls(self) -> Vec<String> {
+        return vec![format!("{}:{}", self.0, self.1)];
+    }
+}
+
+impl Connectable for &(String, u16) {
+    fn get_urls(self) -> Vec<String> {
+        return vec![format!("{}:{}", self.0, self.1)];
+    }
+}
+
 impl Connectable for &[(&str, u16)] {
     fn get_urls(self) -> Vec<String> {
         self.iter().map(|(host, port)| format!("{}:{}", host, port)).collect()
@@ -36,6 +85,18 @@ impl Connectable for Url {
     }
 }
 
+impl Connectable for &[Url] {
+    fn get_urls(self) -> Vec<String> {
+        self.iter().map(Url::to_string).collect()
+    }
+}
+
+impl Connectable for Vec<Url> {
+    fn get_urls(self) -> Vec<String> {
+        self.iter().map(Url::to_string).collect()
+    }
+}
+
 impl Connectable for String {
     fn get_urls(self) -> Vec<String> {
         return vec![self];
@@ -66,11 +127,108 @@ impl Connectable for Vec<&str> {
 
 #[derive(Clone)]
 pub struct Client {
-    connections: Vec<Pool<ConnectionManager>>,
+    servers: Arc<RwLock<Arc<Servers>>>,
     pub hash_function: fn(&str) -> u64,
+    hash_function_name: String,
+    key_prefix: Option<String>,
+    #[cfg(feature = "key-hashing")]
+    key_hashing: bool,
+    max_key_length: usize,
+    read_failover: u32,
+    #[cfg(feature = "sticky-keys")]
+    sticky_keys: Vec<Pattern>,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    miss_observer: Option<MissObserver>,
+    shadow_writer: Arc<ShadowWriter>,
+}
+
+/// The servers of a client, with the ketama ring distributing the keys on them if any. With SRV discovery, they're
+/// replaced at once from a background thread when the discovered servers change, so each operation takes a snapshot
+/// with `Client::servers` to both hash its keys and pick their pools.
+pub(crate) struct Servers {
+    pub(crate) pools: Vec<ShardedPool>,
+    #[cfg(feature = "ketama")]
+    pub(crate) ketama_ring: Option<KetamaRing>,
+}
+
+impl Servers {
+    /// Servers distributing the keys by their hash modulo the number of servers.
+    pub(crate) fn new(pools: Vec<ShardedPool>) -> Self {
+        Servers {
+            pools,
+            #[cfg(feature = "ketama")]
+            ketama_ring: None,
+        }
+    }
+
+    /// Servers distributing the keys on a ketama ring, placed on it by their `host:port`.
+    #[cfg(feature = "ketama")]
+    pub(crate) fn with_ketama_ring(pools: Vec<ShardedPool>) -> Self {
+        let servers: Vec<String> = pools.iter().map(ShardedPool::server_name).collect();
+        let ketama_ring = Some(KetamaRing::new(&servers));
+        Servers { pools, ketama_ring }
+    }
+
+    /// Servers with `pools` instead of the current ones, distributing the keys the same way.
+    #[cfg(feature = "srv-discovery")]
+    pub(crate) fn replace_pools(&self, pools: Vec<ShardedPool>) -> Self {
+        #[cfg(feature = "ketama")]
+        if self.ketama_ring.is_some() {
+            return Servers::with_ketama_ring(pools);
+        }
+        Servers::new(pools)
+    }
+
+    /// The index of the pool of a key hashed to `hash`.
+    fn index(&self, hash: u64) -> usize {
+        #[cfg(feature = "ketama")]
+        if let Some(ref ring) = self.ketama_ring {
+            return ring.index(hash);
+        }
+        hash as usize % self.pools.len()
+    }
+
+    /// The indexes of up to `count` distinct pools to try in turn for a key hashed to `hash`, starting with
+    /// `index(hash)`.
+    fn successors(&self, hash: u64, count: usize) -> Vec<usize> {
+        #[cfg(feature = "ketama")]
+        if let Some(ref ring) = self.ketama_ring {
+            return ring.successors(hash, count);
+        }
+        let index = hash as usize % self.pools.len();
+        (0..count).map(|offset| (index + offset) % self.pools.len()).collect()
+    }
 }
 
-unsafe impl Send for Client {}
+/// A summary of the client's configuration for logs, like
+/// `MemcacheClient { servers: ["localhost:11211"], pool_size: 1, protocol: binary, hash_function: default }`.
+///
+/// The protocol is `mixed` if the servers use different protocols.
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pools = &self.servers().pools;
+        let servers: Vec<String> = pools.iter().map(ShardedPool::server_name).collect();
+        let pool_size = pools.first().map_or(0, ShardedPool::max_size);
+        let ascii_count = pools
+            .iter()
+            .map(ShardedPool::url)
+            .filter(|url| url.query_pairs().any(|(k, v)| k == "protocol" && v == "ascii"))
+            .count();
+        let protocol = if ascii_count == 0 {
+            "binary"
+        } else if ascii_count == pools.len() {
+            "ascii"
+        } else {
+            "mixed"
+        };
+        write!(
+            f,
+            "MemcacheClient {{ servers: {:?}, pool_size: {}, protocol: {}, hash_function: {} }}",
+            servers, pool_size, protocol, self.hash_function_name
+        )
+    }
+}
 
 fn default_hash_function(key: &str) -> u64 {
     let mut hasher = DefaultHasher::new();
@@ -78,8 +236,59 @@ fn default_hash_function(key: &str) -> u64 {
     return hasher.finish();
 }
 
-pub(crate) fn check_key_len(key: &str) -> Result<(), MemcacheError> {
-    if key.len() > 250 {
+fn parse_server_url(url: &str) -> Result<Url, MemcacheError> {
+    let url = Url::parse(url).map_err(|e| MemcacheError::BadURL(e.to_string()))?;
+    match url.scheme() {
+        "memcache" | "memcache+tcp" | "memcache+tls" | "memcache+udp" | "memcache+unix" | "memcache+unix-abstract" => {
+            Ok(url)
+        }
+        _ => Err(MemcacheError::BadURL(format!("Unsupported protocol: {}", url.scheme()))),
+    }
+}
+
+/// The `min_idle` URL parameter, which overrides the builder's minimum number of idle connections for this server.
+fn min_idle_from_url(url: &Url, default: Option<u32>, max_size: u32) -> Result<Option<u32>, MemcacheError> {
+    let min_idle = match url.query_pairs().find(|(k, _)| k == "min_idle") {
+        Some((_, v)) => Some(
+            v.parse::<u32>()
+                .map_err(|_| MemcacheError::BadURL("invalid min_idle, expected a number".into()))?,
+        ),
+        None => return Ok(default),
+    };
+    if min_idle > Some(max_size) {
+        return Err(MemcacheError::BadURL(format!(
+            "min_idle can't be larger than the maximum pool size {}",
+            max_size
+        )));
+    }
+    Ok(min_idle)
+}
+
+/// A key as given by the caller, or its hash if it's too long and key hashing is enabled.
+enum PreparedKey<K> {
+    Original(K),
+    Hashed(String),
+}
+
+impl<K: AsRef<str>> AsRef<str> for PreparedKey<K> {
+    fn as_ref(&self) -> &str {
+        match self {
+            PreparedKey::Original(key) => key.as_ref(),
+            PreparedKey::Hashed(key) => key,
+        }
+    }
+}
+
+/// The longest key accepted by memcached, in bytes.
+pub(crate) const MAX_KEY_LENGTH: usize = 250;
+
+/// Check that `key` isn't empty and is at most `max_length` bytes long. The length is the number of bytes of the UTF-8
+/// encoded key, not its number of characters.
+pub(crate) fn check_key_len(key: &str, max_length: usize) -> Result<(), MemcacheError> {
+    if key.is_empty() {
+        Err(ClientError::Error(Cow::Borrowed("key cannot be empty")))?
+    }
+    if key.len() > max_length {
         Err(ClientError::KeyTooLong)?
     }
     Ok(())
@@ -111,36 +320,304 @@ impl Client {
             } else {
                 builder
             };
-            let pool = builder.build(ConnectionManager::new(parsed))?;
-            connections.push(pool);
+            let pool = builder.build(ConnectionManager::new(parsed.clone()))?;
+            connections.push(ShardedPool::new(parsed, vec![pool]));
         }
-        Ok(Client {
-            connections,
-            hash_function: default_hash_function,
-        })
+        Ok(Self::with_sharded_pools(connections))
     }
 
     pub fn with_pool(pool: Pool<ConnectionManager>) -> Result<Self, MemcacheError> {
-        Ok(Client {
-            connections: vec![pool],
-            hash_function: default_hash_function,
-        })
+        Self::with_pools(vec![pool])
     }
 
+    /// Create a client from connection pools built by the caller. r2d2 doesn't expose the manager of a pool, so a
+    /// connection is taken from each pool to read its server's URL.
     pub fn with_pools(pools: Vec<Pool<ConnectionManager>>) -> Result<Self, MemcacheError> {
-        Ok(Client {
-            connections: pools,
+        let mut connections = Vec::with_capacity(pools.len());
+        for pool in pools {
+            let url = pool.get()?.get_url();
+            connections.push(ShardedPool::new(url, vec![pool]));
+        }
+        Ok(Self::with_sharded_pools(connections))
+    }
+
+    fn with_sharded_pools(pools: Vec<ShardedPool>) -> Self {
+        Client {
+            servers: Arc::new(RwLock::new(Arc::new(Servers::new(pools)))),
             hash_function: default_hash_function,
-        })
+            hash_function_name: "default".to_string(),
+            key_prefix: None,
+            #[cfg(feature = "key-hashing")]
+            key_hashing: false,
+            max_key_length: MAX_KEY_LENGTH,
+            read_failover: 0,
+            #[cfg(feature = "sticky-keys")]
+            sticky_keys: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            miss_observer: None,
+            shadow_writer: Arc::default(),
+        }
     }
 
     pub fn connect<C: Connectable>(target: C) -> Result<Self, MemcacheError> {
         Self::builder().add_server(target)?.build()
     }
 
-    fn get_connection(&self, key: &str) -> Pool<ConnectionManager> {
-        let connections_count = self.connections.len();
-        return self.connections[(self.hash_function)(key) as usize % connections_count].clone();
+    /// Return the key which is actually sent to the servers for `key`. This is `key` itself, unless the client was
+    /// built with `ClientBuilder::with_key_hashing` and `key` is longer than 250 bytes (or the length set with
+    /// `ClientBuilder::with_max_key_length`), in which case it's the hex
+    /// encoded SHA-256 digest of `key`. Requires the `key-hashing` feature.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_key_hashing(true)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(client.hash_key("foo"), "foo");
+    /// assert_eq!(client.hash_key(&"a".repeat(300)).len(), 64);
+    /// ```
+    #[cfg(feature = "key-hashing")]
+    pub fn hash_key(&self, key: &str) -> String {
+        if self.key_hashing && key.len() > self.max_key_length {
+            return format!("{:x}", Sha256::digest(key.as_bytes()));
+        }
+        return key.to_string();
+    }
+
+    fn observe(&self, key: &str, hit: bool) {
+        if let Some(ref observer) = self.miss_observer {
+            observer(key, hit);
+        }
+    }
+
+    /// Validate `key`, or hash it if it's too long and key hashing is enabled. This is done before taking a
+    /// connection from a pool, so that an invalid key doesn't hold one.
+    pub(crate) fn prepare_key<'a>(&self, key: &'a str) -> Result<Cow<'a, str>, MemcacheError> {
+        #[cfg(feature = "key-hashing")]
+        if self.key_hashing && key.len() > self.max_key_length {
+            return Ok(Cow::Owned(self.hash_key(key)));
+        }
+        check_key_len(key, self.max_key_length)?;
+        check_key_chars(key)?;
+        Ok(Cow::Borrowed(key))
+    }
+
+    /// Run `command` on a connection to the server of `key`, see `run_on`.
+    fn run<T, F>(&self, operation: &'static str, key: &str, command: F) -> Result<T, MemcacheError>
+    where
+        T: OperationStatus,
+        F: FnOnce(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        let servers = self.servers();
+        self.run_on(&servers.pools[self.connection_index(&servers, key)], operation, command)
+    }
+
+    /// Run a read `command` like `run`, but if the server of `key` can't be reached, retry it on the next servers, up
+    /// to the number set with `ClientBuilder::with_read_failover`. With ketama hashing, the next servers are the
+    /// successors on the ring, where the key would go if its server was removed; otherwise they're the following
+    /// servers in the order they were added. The keys set with `ClientBuilder::with_sticky_keys` are never retried.
+    fn run_read<T, F>(&self, operation: &'static str, key: &str, command: F) -> Result<T, MemcacheError>
+    where
+        T: OperationStatus,
+        F: Fn(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        let servers = self.servers();
+        let connections_count = servers.pools.len();
+        #[cfg(feature = "sticky-keys")]
+        let sticky = self.sticky_keys.iter().any(|pattern| pattern.matches(key));
+        #[cfg(not(feature = "sticky-keys"))]
+        let sticky = false;
+        let count = if sticky {
+            1
+        } else {
+            (self.read_failover as usize).min(connections_count - 1) + 1
+        };
+        let hash = (self.hash_function)(key);
+        let indexes = servers.successors(hash, count);
+        let mut result = self.run_on(&servers.pools[indexes[0]], operation, &command);
+        for &index in &indexes[1..] {
+            match result {
+                Err(MemcacheError::IOError(_)) | Err(MemcacheError::PoolError(_)) => {
+                    result = self.run_on(&servers.pools[index], operation, &command);
+                }
+                _ => break,
+            }
+        }
+        result
+    }
+
+    /// Run `command` on a connection from `pool`, recording the operation's outcome and duration with the `metrics`
+    /// feature.
+    fn run_on<T, F>(&self, pool: &ShardedPool, operation: &'static str, command: F) -> Result<T, MemcacheError>
+    where
+        T: OperationStatus,
+        F: FnOnce(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        self.run_on_pool(pool, operation, true, command)
+    }
+
+    /// See `run_on`. Without `wait`, fails with `MemcacheError::PoolExhausted` instead of waiting for a connection
+    /// when the pool has no idle one.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn run_on_pool<T, F>(
+        &self,
+        pool: &ShardedPool,
+        operation: &'static str,
+        wait: bool,
+        command: F,
+    ) -> Result<T, MemcacheError>
+    where
+        T: OperationStatus,
+        F: FnOnce(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+        let connection = if wait {
+            pool.get().map_err(MemcacheError::from)
+        } else {
+            pool.try_get().ok_or(MemcacheError::PoolExhausted)
+        };
+        let result = match connection {
+            Ok(mut connection) => {
+                let result = command(&mut connection);
+                if let Err(MemcacheError::IOError(_)) = result {
+                    connection.broken = true;
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+        #[cfg(feature = "metrics")]
+        {
+            let status = match result {
+                Ok(ref value) => value.status(),
+                Err(_) => "error",
+            };
+            instrument::record(operation, pool.server_name(), status, started.elapsed());
+        }
+        result
+    }
+
+    /// A snapshot of the servers, see `Servers`.
+    fn servers(&self) -> Arc<Servers> {
+        Arc::clone(&self.servers.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn connection_index(&self, servers: &Servers, key: &str) -> usize {
+        servers.index((self.hash_function)(key))
+    }
+
+    /// Group `keys` by the index of their server, with their indexes in `keys`, to send a pipelined command per server
+    /// and put the results back in the order of `keys`.
+    fn group_by_server<'a, K: AsRef<str>>(
+        &self,
+        servers: &Servers,
+        keys: &'a [K],
+    ) -> HashMap<usize, (Vec<usize>, Vec<&'a str>)> {
+        let mut con_keys: HashMap<usize, (Vec<usize>, Vec<&str>)> = HashMap::new();
+        for (index, key) in keys.iter().enumerate() {
+            let (indexes, keys) = con_keys
+                .entry(self.connection_index(servers, key.as_ref()))
+                .or_default();
+            indexes.push(index);
+            keys.push(key.as_ref());
+        }
+        con_keys
+    }
+
+    /// Compress `value` if it's larger than the threshold set with `ClientBuilder::with_compression_threshold`.
+    fn maybe_compress<V>(&self, value: V) -> Result<MaybeCompressed<V>, MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        #[cfg(feature = "compression")]
+        {
+            if let Some(threshold) = self.compression_threshold {
+                if ToMemcacheValue::<Vec<u8>>::get_length(&value) > threshold {
+                    return Ok(MaybeCompressed::Compressed(CompressedValue::new(value)?));
+                }
+            }
+        }
+        Ok(MaybeCompressed::Plain(value))
+    }
+
+    fn get_connection(&self, key: &str) -> ShardedPool {
+        let servers = self.servers();
+        return servers.pools[self.connection_index(&servers, key)].clone();
+    }
+
+    /// Run `f` with the connection of the server which `key` is hashed to, e.g. to send a command which isn't
+    /// supported by this crate. It's recorded as the `with_connection` operation with the `metrics` feature, and the
+    /// connection is discarded if `f` fails with an IO error.
+    ///
+    /// The connection goes back to the pool afterwards, so `f` must read the whole response of each command it sends,
+    /// including the responses of quiet binary commands, which are only sent for failures. Anything left unread would
+    /// be taken as the response of the next command.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::ProtocolTrait;
+    /// use std::io::{Read, Write};
+    ///
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// let touched = client
+    ///     .with_connection_for_key("foo", |protocol| {
+    ///         let stream = protocol.stream();
+    ///         stream.write_all(b"touch foo 10\r\n")?;
+    ///         stream.flush()?;
+    ///         let mut response = [0; 9];
+    ///         stream.read_exact(&mut response)?;
+    ///         Ok(&response == b"TOUCHED\r\n")
+    ///     })
+    ///     .unwrap();
+    /// assert!(touched);
+    /// let value: Option<String> = client.with_connection_for_key("foo", |protocol| protocol.get("foo")).unwrap();
+    /// assert_eq!(value, Some(String::from("bar")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn with_connection_for_key<F, T>(&self, key: &str, f: F) -> Result<T, MemcacheError>
+    where
+        F: FnOnce(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        let key = self.prepare_key(key)?;
+        let pool = self.get_connection(&key);
+        let Custom(value) = self.run_on(&pool, "with_connection", |connection| f(connection).map(Custom))?;
+        Ok(value)
+    }
+
+    /// Start building a key in `namespace`. If the client was built with `ClientBuilder::with_key_builder_prefix`, the
+    /// key starts with that prefix.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_key_builder_prefix("myapp")
+    ///     .build()
+    ///     .unwrap();
+    /// let key = client.key_builder("users").id(42).build().unwrap();
+    /// assert_eq!(key, "myapp:users:42");
+    /// ```
+    pub fn key_builder(&self, namespace: &str) -> KeyBuilder {
+        let builder = match self.key_prefix {
+            Some(ref prefix) => KeyBuilder::new(prefix).segment(namespace),
+            None => KeyBuilder::new(namespace),
+        };
+        builder.with_max_length(self.max_key_length)
+    }
+
+    /// Wrap this client into a `FallbackClient`, which gets the keys this client misses from `fallback`.
+    pub fn with_fallback(self, fallback: Arc<Client>) -> FallbackClient {
+        FallbackClient::new(self, fallback)
     }
 
     /// Set the socket read timeout for TCP connections.
@@ -152,12 +629,8 @@ impl Client {
     /// client.set_read_timeout(Some(::std::time::Duration::from_secs(3))).unwrap();
     /// ```
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), MemcacheError> {
-        for conn in self.connections.iter() {
-            let mut conn = conn.get()?;
-            match **conn {
-                Protocol::Ascii(ref mut protocol) => protocol.stream().set_read_timeout(timeout)?,
-                Protocol::Binary(ref mut protocol) => protocol.stream.set_read_timeout(timeout)?,
-            }
+        for pool in self.servers().pools.iter() {
+            pool.set_read_timeout(timeout)?;
         }
         Ok(())
     }
@@ -171,12 +644,8 @@ impl Client {
     /// client.set_write_timeout(Some(::std::time::Duration::from_secs(3))).unwrap();
     /// ```
     pub fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), MemcacheError> {
-        for conn in self.connections.iter() {
-            let mut conn = conn.get()?;
-            match **conn {
-                Protocol::Ascii(ref mut protocol) => protocol.stream().set_write_timeout(timeout)?,
-                Protocol::Binary(ref mut protocol) => protocol.stream.set_write_timeout(timeout)?,
-            }
+        for pool in self.servers().pools.iter() {
+            pool.set_write_timeout(timeout)?;
         }
         Ok(())
     }
@@ -190,257 +659,1563 @@ impl Client {
     /// client.version().unwrap();
     /// ```
     pub fn version(&self) -> Result<Vec<(String, String)>, MemcacheError> {
-        let mut result = Vec::with_capacity(self.connections.len());
-        for connection in self.connections.iter() {
-            let mut connection = connection.get()?;
-            let url = connection.get_url();
-            result.push((url, connection.version()?));
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let version = self.run_on(pool, "version", |connection| connection.version())?;
+            result.push((pool.url().to_string(), version));
         }
         Ok(result)
     }
 
-    /// Flush all cache on memcached server immediately.
+    /// Get the parsed version of all servers, e.g. to check that a feature is supported before using it.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.flush().unwrap();
+    /// for info in client.server_info().unwrap() {
+    ///     assert!(info.version >= memcache::Version(1, 4, 0));
+    /// }
     /// ```
-    pub fn flush(&self) -> Result<(), MemcacheError> {
-        for connection in self.connections.iter() {
-            connection.get()?.flush()?;
+    pub fn server_info(&self) -> Result<Vec<ServerInfo>, MemcacheError> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for (url, version) in self.version()? {
+            let (version, platform) = stats::parse_version(&version)?;
+            result.push(ServerInfo { url, version, platform });
         }
-        return Ok(());
+        Ok(result)
     }
 
-    /// Flush all cache on memcached server with a delay seconds.
+    /// Get an overview of all servers, combining their version and their main statistics, e.g. to print it while
+    /// debugging. This takes two round trips per server.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.flush_with_delay(10).unwrap();
+    /// let info = client.info().unwrap();
+    /// assert_eq!(info.servers.len(), 1);
+    /// println!("{}", info);
     /// ```
-    pub fn flush_with_delay(&self, delay: u32) -> Result<(), MemcacheError> {
-        for connection in self.connections.iter() {
-            connection.get()?.flush_with_delay(delay)?;
-        }
-        return Ok(());
+    pub fn info(&self) -> Result<ClusterInfo, MemcacheError> {
+        let servers = self
+            .version()?
+            .into_iter()
+            .zip(self.stats()?)
+            .map(|((url, version), (_, stats))| stats::summarize_server(url, version, &stats))
+            .collect();
+        Ok(ClusterInfo { servers })
     }
 
-    /// Get a key from memcached server.
+    /// Get the version of all servers as a comparable `Version`. Anything following the version number, like a
+    /// distribution's build suffix in `1.6.12-ubuntu`, is ignored.
     ///
     /// Example:
     ///
     /// ```rust
+    /// use memcache::Version;
+    ///
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// let _: Option<String> = client.get("foo").unwrap();
+    /// for (_url, version) in client.parsed_versions().unwrap() {
+    ///     assert!(version >= Version(1, 4, 0));
+    /// }
     /// ```
-    pub fn get<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<V>, MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.get(key);
+    pub fn parsed_versions(&self) -> Result<Vec<(String, Version)>, MemcacheError> {
+        Ok(self
+            .server_info()?
+            .into_iter()
+            .map(|info| (info.url, info.version))
+            .collect())
     }
 
-    /// Get multiple keys from memcached server. Using this function instead of calling `get` multiple times can reduce network workloads.
+    /// Measure the round trip latency to every server by sending `samples` `version` commands on a connection
+    /// borrowed from its pool, e.g. as a sanity check at startup.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.set("foo", "42", 0).unwrap();
-    /// let result: std::collections::HashMap<String, String> = client.gets(&["foo", "bar", "baz"]).unwrap();
-    /// assert_eq!(result.len(), 1);
-    /// assert_eq!(result["foo"], "42");
+    /// for latency in client.benchmark_servers(10).unwrap() {
+    ///     println!("{}: avg {:?}, p99 {:?}", latency.url, latency.avg, latency.p99);
+    /// }
     /// ```
-    pub fn gets<V: FromMemcacheValueExt>(&self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
-        for key in keys {
-            check_key_len(key)?;
-        }
-        let mut con_keys: HashMap<usize, Vec<&str>> = HashMap::new();
-        let mut result: HashMap<String, V> = HashMap::new();
-        let connections_count = self.connections.len();
-
-        for key in keys {
-            let connection_index = (self.hash_function)(key) as usize % connections_count;
-            let array = con_keys.entry(connection_index).or_insert_with(Vec::new);
-            array.push(key);
+    pub fn benchmark_servers(&self, samples: u32) -> Result<Vec<ServerLatency>, MemcacheError> {
+        if samples == 0 {
+            Err(ClientError::Error(Cow::Borrowed("samples should be greater than 0")))?
         }
-        for (&connection_index, keys) in con_keys.iter() {
-            let connection = self.connections[connection_index].clone();
-            result.extend(connection.get()?.gets(keys)?);
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let durations = self.run_on(pool, "benchmark_servers", |connection| {
+                let mut durations = Vec::with_capacity(samples as usize);
+                for _ in 0..samples {
+                    let start = Instant::now();
+                    connection.version()?;
+                    durations.push(start.elapsed());
+                }
+                Ok(durations)
+            })?;
+            result.push(stats::summarize_latency(pool.url().to_string(), durations));
         }
-        return Ok(result);
+        Ok(result)
     }
 
-    /// Set a key with associate value into memcached server with expiration seconds.
+    /// Check that every server responds to a `version` command, e.g. for the health check endpoint of a service
+    /// behind a load balancer. A server which can't be connected to within the connection timeout set with
+    /// `ClientBuilder::with_connection_timeout`, or which doesn't respond within the read timeout, is unreachable.
+    /// The servers are checked concurrently, each from its own thread, so that the unreachable servers don't add up
+    /// their timeouts.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.set("foo", "bar", 10).unwrap();
-    /// # client.flush().unwrap();
+    /// let health = client.healthcheck();
+    /// assert!(health.is_ok(), "memcached is {}", health);
     /// ```
-    pub fn set<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.set(key, value, expiration);
+    pub fn healthcheck(&self) -> HealthStatus {
+        let servers = self.servers();
+        let unreachable: Vec<String> = thread::scope(|scope| {
+            let handles: Vec<_> = servers
+                .pools
+                .iter()
+                .map(|pool| {
+                    scope.spawn(move || {
+                        let result = self.run_on(pool, "healthcheck", |connection| connection.version());
+                        result.err().map(|_| pool.url().to_string())
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        });
+        if unreachable.is_empty() {
+            HealthStatus::Healthy
+        } else if unreachable.len() < servers.pools.len() {
+            HealthStatus::Degraded(unreachable)
+        } else {
+            HealthStatus::Unhealthy(unreachable)
+        }
     }
 
-    /// Compare and swap a key with the associate value into memcached server with expiration seconds.
-    /// `cas_id` should be obtained from a previous `gets` call.
+    /// Check the idle connections of all pools, and discard those which went stale, e.g. after a server restart or a
+    /// firewall dropping idle connections, instead of finding out when using them. Returns the number of connections
+    /// which failed the check and were discarded. This can be called periodically from a background thread.
+    ///
+    /// The connections are checked out like for a command, which validates them with a `noop` or `version` command,
+    /// so they aren't available to other threads meanwhile. The connections in use aren't checked.
     ///
     /// Example:
     ///
     /// ```rust
-    /// use std::collections::HashMap;
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.set("foo", "bar", 10).unwrap();
-    /// let (_, _, cas) = client.get("foo").unwrap().unwrap();
-    /// let cas = cas.unwrap();
-    /// assert_eq!(true, client.cas("foo", "bar2", 10, cas).unwrap());
-    /// # client.flush().unwrap();
+    /// assert_eq!(client.prune_stale_connections(), 0);
     /// ```
-    pub fn cas<V: ToMemcacheValue<Stream>>(
-        &self,
-        key: &str,
-        value: V,
-        expiration: u32,
-        cas_id: u64,
-    ) -> Result<bool, MemcacheError> {
-        check_key_len(key)?;
-        self.get_connection(key).get()?.cas(key, value, expiration, cas_id)
+    pub fn prune_stale_connections(&self) -> usize {
+        let invalid = connection::invalid_connections();
+        for pool in self.servers().pools.iter() {
+            for shard in pool.shards() {
+                let idle = shard.state().idle_connections as usize;
+                // the pool discards the connections failing the check on checkout and tries the next idle one, the
+                // valid ones are held until all the idle ones were checked
+                let _valid: Vec<_> = (0..idle).map_while(|_| shard.try_get()).collect();
+            }
+        }
+        connection::invalid_connections() - invalid
     }
 
-    /// Add a key with associate value into memcached server with expiration seconds.
+    /// The number of servers of the client, without any network I/O.
     ///
     /// Example:
     ///
     /// ```rust
-    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// let key = "add_test";
-    /// client.delete(key).unwrap();
-    /// client.add(key, "bar", 100000000).unwrap();
-    /// # client.flush().unwrap();
+    /// let client = memcache::Client::connect(vec!["memcache://localhost:12345", "memcache://localhost:12346"]).unwrap();
+    /// assert_eq!(client.server_count(), 2);
+    /// assert!(!client.is_single_server());
     /// ```
-    pub fn add<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.add(key, value, expiration);
+    pub fn server_count(&self) -> usize {
+        self.servers().pools.len()
     }
 
-    /// Replace a key with associate value into memcached server with expiration seconds.
+    /// Whether the client has a single server, so that all the keys are on it, e.g. to skip grouping keys by server.
+    pub fn is_single_server(&self) -> bool {
+        self.server_count() == 1
+    }
+
+    /// Get the state of the connection pools of all servers, without any network I/O, e.g. to chart the pools'
+    /// utilization. A server has an entry per pool shard with `ClientBuilder::with_pool_shards`.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// let key = "replace_test";
-    /// client.set(key, "bar", 0).unwrap();
-    /// client.replace(key, "baz", 100000000).unwrap();
-    /// # client.flush().unwrap();
+    /// for (url, state) in client.pool_metrics() {
+    ///     println!("{}: {} idle of {}", url, state.idle_connections, state.connections);
+    /// }
     /// ```
-    pub fn replace<V: ToMemcacheValue<Stream>>(
-        &self,
-        key: &str,
-        value: V,
-        expiration: u32,
-    ) -> Result<(), MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.replace(key, value, expiration);
+    pub fn pool_metrics(&self) -> Vec<(String, r2d2::State)> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let url = pool.url().to_string();
+            for shard in pool.shards() {
+                result.push((url.clone(), shard.state()));
+            }
+        }
+        result
     }
 
-    /// Append value to the key.
+    /// The share of the connections of all pools which are in use, from 0 when they are all idle to 1 when none is.
+    /// This is 0 when the pools have no connection yet.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// let key = "key_to_append";
-    /// client.set(key, "hello", 0).unwrap();
-    /// client.append(key, ", world!").unwrap();
-    /// let result: String = client.get(key).unwrap().unwrap();
-    /// assert_eq!(result, "hello, world!");
-    /// # client.flush().unwrap();
+    /// assert!(client.total_pool_utilization() < 1.0);
     /// ```
-    pub fn append<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.append(key, value);
+    pub fn total_pool_utilization(&self) -> f64 {
+        let (connections, idle_connections) =
+            self.pool_metrics()
+                .iter()
+                .fold((0u64, 0u64), |(connections, idle), (_, state)| {
+                    (
+                        connections + u64::from(state.connections),
+                        idle + u64::from(state.idle_connections),
+                    )
+                });
+        if connections == 0 {
+            return 0.0;
+        }
+        (connections - idle_connections) as f64 / connections as f64
     }
 
-    /// Prepend value to the key.
+    /// Flush all cache on memcached server immediately.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// let key = "key_to_append";
-    /// client.set(key, "world!", 0).unwrap();
-    /// client.prepend(key, "hello, ").unwrap();
-    /// let result: String = client.get(key).unwrap().unwrap();
-    /// assert_eq!(result, "hello, world!");
-    /// # client.flush().unwrap();
+    /// client.flush().unwrap();
     /// ```
-    pub fn prepend<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.prepend(key, value);
+    pub fn flush(&self) -> Result<(), MemcacheError> {
+        for pool in self.servers().pools.iter() {
+            self.run_on(pool, "flush", |connection| connection.flush())?;
+        }
+        return Ok(());
     }
 
-    /// Delete a key from memcached server.
+    /// Flush only the servers whose URL is one of `server_urls`, e.g. the servers of a single tenant. The URLs are
+    /// compared once normalized, so they must be written like when the client was built, parameters included.
+    /// Returns every server's URL with whether it was flushed.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.delete("foo").unwrap();
-    /// # client.flush().unwrap();
+    /// let flushed = client.flush_many(&["memcache://localhost:12345"]).unwrap();
+    /// assert_eq!(flushed, vec![("memcache://localhost:12345".to_string(), true)]);
     /// ```
-    pub fn delete(&self, key: &str) -> Result<bool, MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.delete(key);
+    pub fn flush_many(&self, server_urls: &[&str]) -> Result<Vec<(String, bool)>, MemcacheError> {
+        let mut urls = Vec::with_capacity(server_urls.len());
+        for url in server_urls {
+            urls.push(Url::parse(url)?);
+        }
+        self.flush_matching(|url| urls.contains(url))
     }
 
-    /// Increment the value with amount.
+    /// Like `flush_many`, but flush the servers whose host is `host`, whatever their port and parameters.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.increment("counter", 42).unwrap();
-    /// # client.flush().unwrap();
+    /// let flushed = client.flush_by_host("example.com").unwrap();
+    /// assert_eq!(flushed, vec![("memcache://localhost:12345".to_string(), false)]);
     /// ```
-    pub fn increment(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.increment(key, amount);
+    pub fn flush_by_host(&self, host: &str) -> Result<Vec<(String, bool)>, MemcacheError> {
+        self.flush_matching(|url| url.host_str() == Some(host))
     }
 
-    /// Decrement the value with amount.
+    fn flush_matching<F: Fn(&Url) -> bool>(&self, matches: F) -> Result<Vec<(String, bool)>, MemcacheError> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let url = pool.url();
+            let flush = matches(url);
+            let url = url.to_string();
+            if flush {
+                self.run_on(pool, "flush", |connection| connection.flush())?;
+            }
+            result.push((url, flush));
+        }
+        Ok(result)
+    }
+
+    /// Flush all cache on memcached server with a delay seconds.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// client.decrement("counter", 42).unwrap();
-    /// # client.flush().unwrap();
+    /// client.flush_with_delay(10).unwrap();
     /// ```
-    pub fn decrement(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.decrement(key, amount);
+    pub fn flush_with_delay(&self, delay: u32) -> Result<(), MemcacheError> {
+        for pool in self.servers().pools.iter() {
+            self.run_on(pool, "flush_with_delay", |connection| {
+                connection.flush_with_delay(delay)
+            })?;
+        }
+        return Ok(());
     }
 
-    /// Set a new expiration time for a exist key.
+    /// Get a key from memcached server.
     ///
     /// Example:
     ///
     /// ```rust
     /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
-    /// assert_eq!(client.touch("not_exists_key", 12345).unwrap(), false);
-    /// client.set("foo", "bar", 123).unwrap();
-    /// assert_eq!(client.touch("foo", 12345).unwrap(), true);
-    /// # client.flush().unwrap();
+    /// let _: Option<String> = client.get("foo").unwrap();
     /// ```
-    pub fn touch(&self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
-        check_key_len(key)?;
-        return self.get_connection(key).get()?.touch(key, expiration);
+    pub fn get<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<V>, MemcacheError> {
+        let effective_key = self.prepare_key(key)?;
+        let value = self.run_read("get", &effective_key, |connection| connection.get(&effective_key))?;
+        self.observe(key, value.is_some());
+        return Ok(value);
+    }
+
+    /// Get a key from memcached server like `get`, but without waiting for a connection when all the connections to
+    /// the key's server are in use: `MemcacheError::PoolExhausted` is returned right away instead. This lets callers
+    /// shed load, e.g. by responding with a 503 status, rather than piling up requests waiting for the pool.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::MemcacheError;
+    ///
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// match client.try_get::<String>("foo") {
+    ///     Ok(value) => assert_eq!(value, Some(String::from("bar"))),
+    ///     Err(MemcacheError::PoolExhausted) => println!("memcached is busy"),
+    ///     Err(e) => panic!("{}", e),
+    /// }
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn try_get<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<V>, MemcacheError> {
+        let effective_key = self.prepare_key(key)?;
+        let pool = self.get_connection(&effective_key);
+        let value = self.run_on_pool(&pool, "get", false, |connection| connection.get(&effective_key))?;
+        self.observe(key, value.is_some());
+        return Ok(value);
+    }
+
+    /// Run `f` like `with_connection_for_key`, but without waiting for a connection when all the connections to the
+    /// server of `key` are in use: `MemcacheError::PoolExhausted` is returned right away instead.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::{MemcacheError, ProtocolTrait};
+    ///
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// match client.try_with_connection_for_key("foo", |connection| connection.version()) {
+    ///     Ok(version) => println!("memcached {}", version),
+    ///     Err(MemcacheError::PoolExhausted) => println!("memcached is busy"),
+    ///     Err(e) => panic!("{}", e),
+    /// }
+    /// ```
+    pub fn try_with_connection_for_key<F, T>(&self, key: &str, f: F) -> Result<T, MemcacheError>
+    where
+        F: FnOnce(&mut Protocol) -> Result<T, MemcacheError>,
+    {
+        let key = self.prepare_key(key)?;
+        let pool = self.get_connection(&key);
+        let Custom(value) =
+            self.run_on_pool(&pool, "with_connection", false, |connection| f(connection).map(Custom))?;
+        Ok(value)
+    }
+
+    /// Queue commands with `f`, then send them to the server of `key` in a single write and return their results in
+    /// order. All commands go to this server regardless of their own keys, so they should be keys which are read
+    /// together with `key`, e.g. built with the same `KeyBuilder` namespace.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::PipelineReply;
+    ///
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let results = client
+    ///     .pipeline_for_key("pipeline", |p| {
+    ///         p.set("pipeline_a", "1", 0)?;
+    ///         p.delete("pipeline_b")?;
+    ///         p.get("pipeline_a")
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(results[0].as_ref().unwrap(), &PipelineReply::Stored);
+    /// assert_eq!(results[1].as_ref().unwrap(), &PipelineReply::Deleted(false));
+    /// match &results[2] {
+    ///     Ok(PipelineReply::Value(Some((value, _, _)))) => assert_eq!(value, b"1"),
+    ///     result => panic!("unexpected result {:?}", result),
+    /// }
+    /// ```
+    pub fn pipeline_for_key<F>(&self, key: &str, f: F) -> Result<Vec<PipelineResult>, MemcacheError>
+    where
+        F: FnOnce(&mut Pipeline<'_>) -> Result<(), MemcacheError>,
+    {
+        let key = self.prepare_key(key)?;
+        let mut pipeline = Pipeline::new(self);
+        f(&mut pipeline)?;
+        if pipeline.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.run("pipeline", &key, |connection| connection.pipeline(&pipeline.commands))
+    }
+
+    /// Get a key from memcached server and update its expiration time in a single round trip, with the `gat`
+    /// command. Older servers which don't support it get a `get` and a `touch` command instead.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// assert_eq!(client.get_and_touch::<String>("not_exists_key", 100).unwrap(), None);
+    /// client.set("foo", "bar", 10).unwrap();
+    /// let value: Option<String> = client.get_and_touch("foo", 100).unwrap();
+    /// assert_eq!(value, Some(String::from("bar")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_and_touch<V: FromMemcacheValueExt>(
+        &self,
+        key: &str,
+        expiration: u32,
+    ) -> Result<Option<V>, MemcacheError> {
+        let effective_key = self.prepare_key(key)?;
+        let value = self.run("get_and_touch", &effective_key, |connection| {
+            connection.get_and_touch(&effective_key, expiration)
+        })?;
+        self.observe(key, value.is_some());
+        return Ok(value);
+    }
+
+    /// Get a key from memcached server with its flags, cas id, and remaining time to live in seconds, e.g. to refresh
+    /// a value before it expires. The time to live is `Some(-1)` for a value which doesn't expire, and `None` if the
+    /// server can't report it, which is the case with the binary protocol, and with the ASCII protocol for servers
+    /// older than 1.6, which don't support the meta commands.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.set("foo", "bar", 100).unwrap();
+    /// let (value, _flags, _cas, ttl): (String, _, _, _) = client.get_with_metadata("foo").unwrap().unwrap();
+    /// assert_eq!(value, "bar");
+    /// assert!(ttl.unwrap() <= 100);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_with_metadata<V: FromMemcacheValueExt>(
+        &self,
+        key: &str,
+    ) -> Result<Option<ValueWithMetadata<V>>, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        self.run_read("get_with_metadata", &key, |connection| {
+            connection.get_with_metadata(&key)
+        })
+    }
+
+    /// Change the flags of a stored value without changing the value, e.g. after changing how the flags of a type are
+    /// computed. memcached can't update flags alone, so this takes two round trips: the value is read with its cas id
+    /// and remaining time to live, then stored again with a `cas` command. Returns `false` if the key doesn't exist,
+    /// or if it was modified in between, in which case it's left alone.
+    ///
+    /// This requires the time to live reported by the meta commands of the ASCII protocol, so it fails with the
+    /// binary protocol or with servers older than 1.6, instead of storing the value again without expiration.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.set("flagged", "value", 100).unwrap();
+    /// assert!(client.set_flags("flagged", 42).unwrap());
+    /// let (_, flags, _) = client.get_raw("flagged").unwrap().unwrap();
+    /// assert_eq!(flags, 42);
+    /// assert!(!client.set_flags("not_exists_key", 42).unwrap());
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn set_flags(&self, key: &str, new_flags: u32) -> Result<bool, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        self.run("set_flags", &key, |connection| {
+            let (value, _, cas, ttl) = match connection.get_with_metadata::<Vec<u8>>(&key)? {
+                Some(metadata) => metadata,
+                None => return Ok(false),
+            };
+            let expiration = match ttl {
+                // an item expiring within a second reports 0, which would mean that the new one doesn't expire
+                Some(ttl) if ttl >= 0 => ttl.max(1) as u32,
+                Some(_) => 0,
+                None => Err(ClientError::Error(Cow::Borrowed(
+                    "the time to live of a value can't be kept without the meta commands of the ASCII protocol",
+                )))?,
+            };
+            let value: &[u8] = &value;
+            let value = WithFlags {
+                value: &value,
+                flags: new_flags,
+            };
+            connection.cas(&key, value, expiration, cas)
+        })
+    }
+
+    /// Get a key from memcached server as its raw bytes, flags and cas id, without having to annotate the value's
+    /// type. The cas id is only returned by the binary protocol, use `gets` with the ASCII protocol to get it.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// let (value, flags, _cas) = client.get_raw("foo").unwrap().unwrap();
+    /// assert_eq!((value.as_slice(), flags), (&b"bar"[..], 0));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_raw(&self, key: &str) -> Result<Option<RawValue>, MemcacheError> {
+        self.get(key)
+    }
+
+    /// Get a key from memcached server with its cas id, e.g. for `cas` or `compare_and_delete`. The binary protocol
+    /// returns the cas id of a plain get, and the ASCII protocol sends a `gets` command with this single key. Unlike
+    /// `gets`, the value isn't collected into a map.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// let (value, cas): (String, u64) = client.get_with_cas("foo").unwrap().unwrap();
+    /// assert_eq!(value, "bar");
+    /// assert!(client.cas("foo", "baz", 0, cas).unwrap());
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_with_cas<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<(V, u64)>, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        self.run("get_with_cas", &key, |connection| {
+            match connection.get_with_cas(&key)? {
+                Some((value, flags, cas)) => Ok(Some((V::from_memcache_value(value, flags, Some(cas))?, cas))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Get multiple keys from memcached server. Using this function instead of calling `get` multiple times can reduce network workloads.
+    ///
+    /// The values carry their cas id, e.g. for `cas` or `compare_and_delete`: with the ASCII protocol this is the
+    /// `gets` command. Use `get_many` when the cas ids aren't needed.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "42", 0).unwrap();
+    /// let result: std::collections::HashMap<String, String> = client.gets(&["foo", "bar", "baz"]).unwrap();
+    /// assert_eq!(result.len(), 1);
+    /// assert_eq!(result["foo"], "42");
+    /// ```
+    pub fn gets<V: FromMemcacheValueExt>(&self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
+        self.get_multi(keys, true)
+    }
+
+    /// Get multiple keys from memcached server without their cas id, which is the plain `get` command with the ASCII
+    /// protocol and saves the server looking them up. The binary protocol always returns the cas ids, so this is the
+    /// same as `gets` with it.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.set("foo", "42", 0).unwrap();
+    /// let result: std::collections::HashMap<String, (Vec<u8>, u32, Option<u64>)> =
+    ///     client.get_many(&["foo", "bar"]).unwrap();
+    /// assert_eq!(result["foo"], (b"42".to_vec(), 0, None));
+    /// ```
+    pub fn get_many<V: FromMemcacheValueExt>(&self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
+        self.get_multi(keys, false)
+    }
+
+    /// Get multiple keys like `get_many`, grouped by the URL of the server they were fetched from, e.g. to see how the
+    /// keys are distributed while debugging. Missing keys are absent from the inner maps, and the servers which none
+    /// of the keys are hashed to are absent from the result.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// let result = client.get_batch_by_server::<String>(&["foo", "baz"]).unwrap();
+    /// assert_eq!(result["memcache://localhost:12345"]["foo"], "bar");
+    /// assert!(!result["memcache://localhost:12345"].contains_key("baz"));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_batch_by_server<V: FromMemcacheValueExt>(
+        &self,
+        keys: &[&str],
+    ) -> Result<HashMap<String, HashMap<String, V>>, MemcacheError> {
+        let mut result = HashMap::new();
+        for (url, values) in self.get_multi_by_server(keys, false)? {
+            result.insert(url, values);
+        }
+        Ok(result)
+    }
+
+    fn get_multi<V: FromMemcacheValueExt>(
+        &self,
+        keys: &[&str],
+        with_cas: bool,
+    ) -> Result<HashMap<String, V>, MemcacheError> {
+        let mut result: HashMap<String, V> = HashMap::new();
+        for (_, values) in self.get_multi_by_server(keys, with_cas)? {
+            result.extend(values);
+        }
+        for key in keys {
+            self.observe(key, result.contains_key(*key));
+        }
+        return Ok(result);
+    }
+
+    /// Get multiple keys with a single command per server, returning the values found on each server by their
+    /// original keys.
+    fn get_multi_by_server<V: FromMemcacheValueExt>(
+        &self,
+        keys: &[&str],
+        with_cas: bool,
+    ) -> Result<ServerValues<V>, MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            effective_keys.push(self.prepare_key(key)?);
+        }
+        // the original keys of the hashed ones, to return the values by the keys the caller asked for
+        let original_keys: HashMap<&str, &str> = effective_keys
+            .iter()
+            .zip(keys)
+            .filter_map(|(effective_key, key)| match effective_key {
+                Cow::Owned(hashed_key) => Some((hashed_key.as_str(), *key)),
+                Cow::Borrowed(_) => None,
+            })
+            .collect();
+        let mut result = Vec::with_capacity(self.server_count());
+
+        let servers = self.servers();
+
+        for (connection_index, (_, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let pool = &servers.pools[connection_index];
+            let values = if with_cas {
+                self.run_on(pool, "gets", |connection| connection.gets::<V>(&keys))?
+            } else {
+                self.run_on(pool, "get_many", |connection| connection.get_many::<V>(&keys))?
+            };
+            let values = values
+                .into_iter()
+                .map(|(key, value)| match original_keys.get(key.as_str()) {
+                    Some(original_key) => (original_key.to_string(), value),
+                    None => (key, value),
+                })
+                .collect();
+            result.push((pool.url().to_string(), values));
+        }
+        Ok(result)
+    }
+
+    /// Get multiple keys from memcached server as raw bytes and flags, whatever the type of each value. The flags
+    /// set by `ToMemcacheValue` can tell the callers how to deserialize each value, which is useful when the keys
+    /// hold values of different types. Missing keys are absent from the result, like with `gets`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// client.set("counter", 42, 0).unwrap();
+    /// let values = client.get_bulk_raw(&["foo", "counter", "not_exists_key"]).unwrap();
+    /// assert_eq!(values.len(), 2);
+    /// assert_eq!(values["foo"].0, b"bar");
+    /// assert_eq!(values["counter"].0, b"42");
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_bulk_raw(&self, keys: &[&str]) -> Result<HashMap<String, (Vec<u8>, u32)>, MemcacheError> {
+        return self.gets(keys);
+    }
+
+    /// Get multiple keys from memcached server, and load the missing ones with `loader`, which is called with the
+    /// missing keys only. The loaded values are set into memcached server with expiration seconds, and returned
+    /// together with the cached ones. `loader` isn't called when all the keys are cached.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "42", 0).unwrap();
+    /// # client.delete("bar").unwrap();
+    /// let result: std::collections::HashMap<String, String> = client
+    ///     .get_or_set_many(&["foo", "bar"], 10, |missing| {
+    ///         assert_eq!(missing, ["bar"]);
+    ///         Ok(missing.iter().map(|key| (key.to_string(), key.to_uppercase())).collect())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(result["foo"], "42");
+    /// assert_eq!(result["bar"], "BAR");
+    /// let value: Option<String> = client.get("bar").unwrap();
+    /// assert_eq!(value, Some(String::from("BAR")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_or_set_many<V, F>(
+        &self,
+        keys: &[&str],
+        expiration: u32,
+        loader: F,
+    ) -> Result<HashMap<String, V>, MemcacheError>
+    where
+        V: FromMemcacheValueExt + ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+        F: FnOnce(&[&str]) -> Result<HashMap<String, V>, MemcacheError>,
+    {
+        let mut result: HashMap<String, V> = self.gets(keys)?;
+        let missing_keys: Vec<&str> = keys.iter().filter(|key| !result.contains_key(**key)).copied().collect();
+        if missing_keys.is_empty() {
+            return Ok(result);
+        }
+        let loaded = loader(&missing_keys)?;
+        self.set_entries(
+            loaded.iter().map(|(key, value)| {
+                let flags = ToMemcacheValue::<Stream>::get_flags(value);
+                (key, WithFlags { value, flags }, expiration)
+            }),
+            false,
+        )?;
+        result.extend(loaded);
+        Ok(result)
+    }
+
+    /// Check which of the keys exist in memcached server, the result is in the same order as `keys`. With the binary
+    /// protocol, the servers only respond for the keys which exist.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "42", 0).unwrap();
+    /// let result = client.exists_batch(&["foo", "bar"]).unwrap();
+    /// assert_eq!(result, vec![true, false]);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn exists_batch(&self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            effective_keys.push(self.prepare_key(key)?);
+        }
+        let mut result = vec![false; keys.len()];
+
+        let servers = self.servers();
+
+        for (connection_index, (indexes, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let pool = &servers.pools[connection_index];
+            let exists = self.run_on(pool, "exists_batch", |connection| connection.exists_batch(&keys))?;
+            for (index, exists) in indexes.into_iter().zip(exists) {
+                result[index] = exists;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Set a key with associate value into memcached server with expiration seconds.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 10).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn set<V>(&self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        let key = self.prepare_key(key)?;
+        let value = self.maybe_compress(value)?;
+        return self.run("set", &key, |connection| connection.set(&key, value, expiration));
+    }
+
+    /// Set multiple keys with associate values into memcached server with expiration seconds.
+    /// The commands sent to each server are pipelined, so using this function instead of calling `set` multiple times can reduce network round trips.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set_many(vec![("foo", "bar"), ("baz", "qux")], 10).unwrap();
+    /// let value: Option<String> = client.get("baz").unwrap();
+    /// assert_eq!(value, Some(String::from("qux")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn set_many<K, V, I>(&self, entries: I, expiration: u32) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.set_entries(entries.into_iter().map(|(key, value)| (key, value, expiration)), false)
+    }
+
+    /// Set the entries of `entries` into memcached servers with expiration seconds, e.g. to pre-load the cache from
+    /// a database at startup. The entries are sent with `set_many` in batches of 100, so the iterator is consumed
+    /// lazily. Returns the number of entries set, see `warm_from_iterator_with_progress` for how errors are handled.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let entries = (0..250).map(|i| (format!("warm_{}", i), i));
+    /// assert_eq!(client.warm_from_iterator(entries, 10).unwrap(), 250);
+    /// let value: Option<u32> = client.get("warm_42").unwrap();
+    /// assert_eq!(value, Some(42));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn warm_from_iterator<K, V, I>(&self, entries: I, expiration: u32) -> Result<usize, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.warm_from_iterator_with_progress(entries, expiration, 100, |_, _| {})
+    }
+
+    /// Like `warm_from_iterator`, with `batch_size` entries per batch, and calling `on_progress` after each batch
+    /// with the number of entries set so far and the number of entries consumed from the iterator so far.
+    ///
+    /// A batch failing with an I/O or connection pool error stops the loading and returns the error, as the next
+    /// batches would likely fail too. A batch failing with any other error, like a value too large for the server,
+    /// isn't counted as set, even though some of its entries may be, and the loading goes on with the next batch.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let entries = (0..250).map(|i| (format!("warm_{}", i), i));
+    /// let mut batches = 0;
+    /// let set = client
+    ///     .warm_from_iterator_with_progress(entries, 10, 50, |set, consumed| {
+    ///         assert_eq!(set, consumed);
+    ///         batches += 1;
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!((set, batches), (250, 5));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn warm_from_iterator_with_progress<K, V, I, F>(
+        &self,
+        entries: I,
+        expiration: u32,
+        batch_size: usize,
+        mut on_progress: F,
+    ) -> Result<usize, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(usize, usize),
+    {
+        let mut entries = entries.into_iter();
+        let (mut set, mut consumed) = (0, 0);
+        loop {
+            let batch: Vec<(K, V)> = entries.by_ref().take(batch_size.max(1)).collect();
+            if batch.is_empty() {
+                return Ok(set);
+            }
+            let count = batch.len();
+            consumed += count;
+            match self.set_many(batch, expiration) {
+                Ok(()) => set += count,
+                Err(e @ MemcacheError::IOError(_)) | Err(e @ MemcacheError::PoolError(_)) => return Err(e),
+                Err(_) => {}
+            }
+            on_progress(set, consumed);
+        }
+    }
+
+    /// Set multiple keys with associated values, expiration seconds and flags into memcached servers. Each entry is
+    /// a `(key, value, expiration, flags)` tuple, the flags replace the default ones of the value's type, which is
+    /// useful when sharing the cache with other clients which use the flags to mark the serialization format.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set_many_with_flags(&[("foo", "bar", 10, 1), ("baz", "qux", 0, 2)]).unwrap();
+    /// let value: Option<(Vec<u8>, u32)> = client.get("baz").unwrap();
+    /// assert_eq!(value, Some((b"qux".to_vec(), 2)));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn set_many_with_flags<V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>>(
+        &self,
+        entries: &[(&str, V, u32, u32)],
+    ) -> Result<(), MemcacheError> {
+        self.set_entries(
+            entries
+                .iter()
+                .map(|&(key, ref value, expiration, flags)| (key, WithFlags { value, flags }, expiration)),
+            false,
+        )
+    }
+
+    /// Set multiple keys like `set_many`, without waiting for a reply per key with the ASCII protocol: the sets are sent
+    /// with `noreply`, followed by a `version` command whose reply confirms that each server processed them. This
+    /// saves parsing a reply per key, but memcached doesn't report every failed `noreply` command, e.g. a value too
+    /// large may be silently dropped. The binary protocol already only replies to the failed sets, so this is the
+    /// same as `set_many` with it.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.pipe_set(&[("foo", "bar", 10), ("baz", "qux", 0)]).unwrap();
+    /// let value: Option<String> = client.get("baz").unwrap();
+    /// assert_eq!(value, Some(String::from("qux")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn pipe_set<V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>>(
+        &self,
+        entries: &[(&str, V, u32)],
+    ) -> Result<(), MemcacheError> {
+        self.set_entries(
+            entries.iter().map(|&(key, ref value, expiration)| {
+                let flags = ToMemcacheValue::<Stream>::get_flags(value);
+                (key, WithFlags { value, flags }, expiration)
+            }),
+            true,
+        )
+    }
+
+    /// Set `entries` with a pipelined command per server, with `noreply` for the ASCII protocol, see `pipe_set`.
+    fn set_entries<K, V, I>(&self, entries: I, noreply: bool) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let operation = if noreply { "pipe_set" } else { "set_many" };
+        let servers = self.servers();
+        let mut con_entries: HashMap<usize, ServerEntries<K, V>> = HashMap::new();
+
+        for (key, value, expiration) in entries {
+            let hashed_key = match self.prepare_key(key.as_ref())? {
+                Cow::Owned(hashed_key) => Some(hashed_key),
+                Cow::Borrowed(_) => None,
+            };
+            let key = match hashed_key {
+                Some(hashed_key) => PreparedKey::Hashed(hashed_key),
+                None => PreparedKey::Original(key),
+            };
+            let connection_index = self.connection_index(&servers, key.as_ref());
+            let array = con_entries.entry(connection_index).or_insert_with(Vec::new);
+            array.push((key, self.maybe_compress(value)?, expiration));
+        }
+        for (connection_index, entries) in con_entries {
+            let pool = &servers.pools[connection_index];
+            self.run_on(pool, operation, |connection| match connection {
+                Protocol::Ascii(protocol) if noreply => protocol.sets_noreply(entries),
+                _ => connection.sets(entries),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Set multiple keys with associated values and expiration seconds into memcached servers, like `set_many`, but
+    /// sending the pipelined commands to each server from its own thread, so that the round trips to the servers
+    /// overlap instead of adding up. The values are shared with the threads, so their type must be `Sync`.
+    ///
+    /// All the servers are written to even when some of them fail. If a single server failed, its error is
+    /// returned, otherwise the error lists the failure of each server.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect(vec!["memcache://localhost:12345", "memcache://localhost:12346"]).unwrap();
+    /// let entries: Vec<(String, String, u32)> = (0..10).map(|i| (format!("key{}", i), i.to_string(), 10)).collect();
+    /// client.set_many_concurrent(&entries).unwrap();
+    /// let value: Option<String> = client.get("key7").unwrap();
+    /// assert_eq!(value, Some(String::from("7")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn set_many_concurrent<V>(&self, entries: &[(String, V, u32)]) -> Result<(), MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>> + Sync,
+    {
+        let mut effective_keys = Vec::with_capacity(entries.len());
+        let mut values = Vec::with_capacity(entries.len());
+        for (key, value, _) in entries {
+            effective_keys.push(self.prepare_key(key)?);
+            let flags = ToMemcacheValue::<Stream>::get_flags(value);
+            values.push(self.maybe_compress(WithFlags { value, flags })?);
+        }
+        let values = &values;
+        let servers = self.servers();
+        let servers = &servers;
+        let mut errors: Vec<(String, MemcacheError)> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .group_by_server(servers, &effective_keys)
+                .into_iter()
+                .map(|(connection_index, (indexes, keys))| {
+                    scope.spawn(move || {
+                        let sets = indexes.iter().zip(keys).map(|(&index, key)| {
+                            let value = &values[index];
+                            let flags = ToMemcacheValue::<Stream>::get_flags(value);
+                            (key, WithFlags { value, flags }, entries[index].2)
+                        });
+                        let pool = &servers.pools[connection_index];
+                        self.run_on(pool, "set_many", |connection| connection.sets(sets))
+                            .err()
+                            .map(|e| (pool.server_name(), e))
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        });
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0).1),
+            count => {
+                let errors: Vec<String> = errors
+                    .iter()
+                    .map(|(server, error)| format!("{}: {}", server, error))
+                    .collect();
+                Err(ClientError::Error(Cow::Owned(format!(
+                    "failed to set keys on {} servers: {}",
+                    count,
+                    errors.join(", ")
+                ))))?
+            }
+        }
+    }
+
+    /// Set a key with associate value into this client's servers, and queue the same write to a `shadow` client's
+    /// servers, which is useful when migrating to a new memcached cluster. Only the result of this client's write is
+    /// returned.
+    ///
+    /// The shadow writes are run by a background thread shared by the client and its clones, so they don't add
+    /// latency. They are best effort: errors from the shadow client are ignored, and the writes are dropped when more
+    /// than 1024 of them are waiting for the shadow servers.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let shadow = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    /// client.shadow_write(&shadow, "foo", "bar", 10).unwrap();
+    /// let value: Option<String> = client.get("foo").unwrap();
+    /// assert_eq!(value, Some(String::from("bar")));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn shadow_write<V>(&self, shadow: &Client, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        self.prepare_key(key)?;
+        let mut bytes = Vec::with_capacity(ToMemcacheValue::<Vec<u8>>::get_length(&value));
+        ToMemcacheValue::<Vec<u8>>::write_to(&value, &mut bytes)?;
+        let flags = ToMemcacheValue::<Vec<u8>>::get_flags(&value);
+        self.shadow_writer.send(shadow, key, bytes, flags, expiration);
+        self.set(key, value, expiration)
+    }
+
+    /// Compare and swap a key with the associate value into memcached server with expiration seconds.
+    /// `cas_id` should be obtained from a previous `gets` call.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 10).unwrap();
+    /// let (_, _, cas) = client.get("foo").unwrap().unwrap();
+    /// let cas = cas.unwrap();
+    /// assert_eq!(true, client.cas("foo", "bar2", 10, cas).unwrap());
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn cas<V>(&self, key: &str, value: V, expiration: u32, cas_id: u64) -> Result<bool, MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        let key = self.prepare_key(key)?;
+        let value = self.maybe_compress(value)?;
+        self.run("cas", &key, |connection| {
+            connection.cas(&key, value, expiration, cas_id)
+        })
+    }
+
+    /// Add a key with associate value into memcached server with expiration seconds.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let key = "add_test";
+    /// client.delete(key).unwrap();
+    /// client.add(key, "bar", 100000000).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn add<V>(&self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        let key = self.prepare_key(key)?;
+        let value = self.maybe_compress(value)?;
+        return self.run("add", &key, |connection| connection.add(&key, value, expiration));
+    }
+
+    /// Add multiple keys with associate values into memcached servers with expiration seconds, returning whether each
+    /// key was added, in the order of `entries`. The commands sent to each server are pipelined, so using this
+    /// function instead of calling `add` multiple times can reduce network round trips.
+    ///
+    /// A key which already exists is `Ok(false)`, other errors reported by the servers are returned for the key which
+    /// caused them, and don't prevent the other keys from being added.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// # client.delete("baz").unwrap();
+    /// let added = client.add_many(&[("foo", "qux", 10), ("baz", "qux", 10)]).unwrap();
+    /// assert!(matches!(added[..], [Ok(false), Ok(true)]));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn add_many<V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>>(
+        &self,
+        entries: &[(&str, V, u32)],
+    ) -> Result<Vec<AddResult>, MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(entries.len());
+        let mut values = Vec::with_capacity(entries.len());
+        for (key, value, _) in entries {
+            effective_keys.push(self.prepare_key(key)?);
+            let flags = ToMemcacheValue::<Stream>::get_flags(value);
+            values.push(self.maybe_compress(WithFlags { value, flags })?);
+        }
+        let mut result: Vec<Option<AddResult>> = (0..entries.len()).map(|_| None).collect();
+
+        let servers = self.servers();
+        for (connection_index, (indexes, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let adds = indexes.iter().zip(keys).map(|(&index, key)| {
+                let value = &values[index];
+                let flags = ToMemcacheValue::<Stream>::get_flags(value);
+                (key, WithFlags { value, flags }, entries[index].2)
+            });
+            let pool = &servers.pools[connection_index];
+            let added = self.run_on(pool, "add_many", |connection| connection.adds(adds))?;
+            for (index, added) in indexes.into_iter().zip(added) {
+                result[index] = Some(added);
+            }
+        }
+        Ok(result.into_iter().flatten().collect())
+    }
+
+    /// Replace a key with associate value into memcached server with expiration seconds.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let key = "replace_test";
+    /// client.set(key, "bar", 0).unwrap();
+    /// client.replace(key, "baz", 100000000).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn replace<V>(&self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>
+    where
+        V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>,
+    {
+        let key = self.prepare_key(key)?;
+        let value = self.maybe_compress(value)?;
+        return self.run("replace", &key, |connection| {
+            connection.replace(&key, value, expiration)
+        });
+    }
+
+    /// Replace a key only if its value was stored at least `max_age` ago, e.g. to avoid overwriting a value which was
+    /// just refreshed by another client. Returns `false` if the value is too recent, if the key doesn't exist, or if it
+    /// was modified while checking its age.
+    ///
+    /// memcached doesn't keep the time a value was stored, so its age is computed from its remaining time to live,
+    /// assuming it was stored with the same `expiration` as the new value. This requires the time to live reported by
+    /// the meta commands of the ASCII protocol, so it fails with the binary protocol, with servers older than 1.6, for
+    /// an `expiration` of 0, or if the stored value doesn't expire.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// client.set("fresh", "old value", 100).unwrap();
+    /// assert!(!client.replace_only_if_older_than("fresh", "new value", 100, Duration::from_secs(60)).unwrap());
+    /// assert!(client.replace_only_if_older_than("fresh", "new value", 100, Duration::from_secs(0)).unwrap());
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn replace_only_if_older_than<V: ToMemcacheValue<Stream> + ToMemcacheValue<Vec<u8>>>(
+        &self,
+        key: &str,
+        value: V,
+        expiration: u32,
+        max_age: Duration,
+    ) -> Result<bool, MemcacheError> {
+        if expiration == 0 {
+            Err(ClientError::Error(Cow::Borrowed(
+                "the age of a value can't be computed for values which don't expire",
+            )))?
+        }
+        let key = self.prepare_key(key)?;
+        let value = self.maybe_compress(value)?;
+        self.run("replace_only_if_older_than", &key, |connection| {
+            let (_, _, cas, ttl) = match connection.get_with_metadata::<Vec<u8>>(&key)? {
+                Some(metadata) => metadata,
+                None => return Ok(false),
+            };
+            let ttl = match ttl {
+                Some(ttl) if ttl >= 0 => ttl as u64,
+                Some(_) => Err(ClientError::Error(Cow::Borrowed(
+                    "the age of a value can't be computed for values which don't expire",
+                )))?,
+                None => Err(ClientError::Error(Cow::Borrowed(
+                    "the age of a value can't be computed without the meta commands of the ASCII protocol",
+                )))?,
+            };
+            let age = Duration::from_secs(u64::from(expiration).saturating_sub(ttl));
+            if age < max_age {
+                return Ok(false);
+            }
+            connection.cas(&key, value, expiration, cas)
+        })
+    }
+
+    /// Append value to the key.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let key = "key_to_append";
+    /// client.set(key, "hello", 0).unwrap();
+    /// client.append(key, ", world!").unwrap();
+    /// let result: String = client.get(key).unwrap().unwrap();
+    /// assert_eq!(result, "hello, world!");
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn append<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("append", &key, |connection| connection.append(&key, value));
+    }
+
+    /// Prepend value to the key.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let key = "key_to_append";
+    /// client.set(key, "world!", 0).unwrap();
+    /// client.prepend(key, "hello, ").unwrap();
+    /// let result: String = client.get(key).unwrap().unwrap();
+    /// assert_eq!(result, "hello, world!");
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn prepend<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("prepend", &key, |connection| connection.prepend(&key, value));
+    }
+
+    /// Append value to the key without waiting for the server's confirmation, which saves a round trip when
+    /// appending frequently, e.g. to a log. Errors, like a missing key, aren't reported.
+    ///
+    /// The binary protocol only replies to a failed append, so it's followed by a `noop` command whose reply marks
+    /// the end of the replies, which are skipped.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("log", "a", 0).unwrap();
+    /// client.append_noreply("log", "b").unwrap();
+    /// let result: String = client.get("log").unwrap().unwrap();
+    /// assert_eq!(result, "ab");
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn append_noreply<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("append_noreply", &key, |connection| {
+            connection.append_noreply(&key, value)
+        });
+    }
+
+    /// Prepend value to the key without waiting for the server's confirmation. Errors, like a missing key, aren't
+    /// reported. With the binary protocol, it's followed by a `noop` command like `append_noreply`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("log", "b", 0).unwrap();
+    /// client.prepend_noreply("log", "a").unwrap();
+    /// let result: String = client.get("log").unwrap().unwrap();
+    /// assert_eq!(result, "ab");
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn prepend_noreply<V: ToMemcacheValue<Stream>>(&self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("prepend_noreply", &key, |connection| {
+            connection.prepend_noreply(&key, value)
+        });
+    }
+
+    /// Delete a key from memcached server.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.delete("foo").unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn delete(&self, key: &str) -> Result<bool, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("delete", &key, |connection| connection.delete(&key));
+    }
+
+    /// Delete multiple keys from memcached servers. The commands sent to each server are pipelined. The result is in
+    /// the same order as `keys`, `false` means the key didn't exist.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// assert_eq!(client.delete_multi(&["foo", "baz"]).unwrap(), vec![true, false]);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn delete_multi(&self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            effective_keys.push(self.prepare_key(key)?);
+        }
+        let mut result = vec![false; keys.len()];
+
+        let servers = self.servers();
+
+        for (connection_index, (indexes, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let pool = &servers.pools[connection_index];
+            let deleted = self.run_on(pool, "delete_multi", |connection| connection.deletes(&keys))?;
+            for (index, deleted) in indexes.into_iter().zip(deleted) {
+                result[index] = deleted;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Delete multiple keys from memcached servers without waiting for the servers' confirmations, e.g. to
+    /// invalidate many keys at once. The commands sent to each server are pipelined. Errors, like missing keys,
+    /// aren't reported.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// client.delete_many_noreply(&["foo", "baz"]).unwrap();
+    /// let value: Option<String> = client.get("foo").unwrap();
+    /// assert_eq!(value, None);
+    /// ```
+    pub fn delete_many_noreply(&self, keys: &[&str]) -> Result<(), MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            effective_keys.push(self.prepare_key(key)?);
+        }
+        let servers = self.servers();
+        for (connection_index, (_, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let pool = &servers.pools[connection_index];
+            self.run_on(pool, "delete_many_noreply", |connection| {
+                connection.deletes_noreply(&keys)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Delete a key from memcached server only if it wasn't modified since its cas id was read, e.g. by `gets`.
+    /// Returns `false` if the key was modified or doesn't exist.
+    ///
+    /// The ASCII protocol has no delete by cas, so with it the item is replaced by an empty one which expires
+    /// immediately, using a `cas` command.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 10).unwrap();
+    /// let result: std::collections::HashMap<String, (Vec<u8>, u32, Option<u64>)> = client.gets(&["foo"]).unwrap();
+    /// let (_, _, cas) = result.get("foo").unwrap();
+    /// let cas = cas.unwrap();
+    /// client.set("foo", "baz", 10).unwrap();
+    /// assert_eq!(client.compare_and_delete("foo", cas).unwrap(), false);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn compare_and_delete(&self, key: &str, cas_id: u64) -> Result<bool, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        self.run("compare_and_delete", &key, |connection| {
+            connection.compare_and_delete(&key, cas_id)
+        })
+    }
+
+    /// Get a key and delete it, e.g. to consume a queued job exactly once. Returns `None` if the key doesn't exist, or
+    /// if it was modified or deleted by another client between the two steps: only one of the concurrent consumers
+    /// gets the value.
+    ///
+    /// memcached has no command to do this, so the value is read with its cas id, then deleted with
+    /// `compare_and_delete`. These are two round trips, and with the ASCII protocol the deleted item is replaced by
+    /// an empty one which expires immediately, see `compare_and_delete`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("job", "send_mail", 0).unwrap();
+    /// let job: Option<String> = client.get_and_delete("job").unwrap();
+    /// assert_eq!(job, Some(String::from("send_mail")));
+    /// assert_eq!(client.get_and_delete::<String>("job").unwrap(), None);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn get_and_delete<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<V>, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        self.run("get_and_delete", &key, |connection| {
+            let (value, flags, cas) = match connection.get_with_cas(&key)? {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+            if !connection.compare_and_delete(&key, cas)? {
+                return Ok(None);
+            }
+            Ok(Some(V::from_memcache_value(value, flags, Some(cas))?))
+        })
+    }
+
+    /// Increment the value with amount without waiting for the server's confirmation, e.g. for page view counters.
+    /// Errors aren't reported. With the binary protocol, a missing key is created with the value 0 like with
+    /// `increment`; with the ASCII protocol it stays missing.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("page_views", 10, 0).unwrap();
+    /// client.increment_quiet("page_views", 1).unwrap();
+    /// assert_eq!(client.get::<u64>("page_views").unwrap(), Some(11));
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn increment_quiet(&self, key: &str, amount: u64) -> Result<(), MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("increment_quiet", &key, |connection| {
+            connection.increment_noreply(&key, amount)
+        });
+    }
+
+    /// Increment the value with amount.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.increment("counter", 42).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn increment(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("increment", &key, |connection| connection.increment(&key, amount));
+    }
+
+    /// Decrement the value with amount.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.decrement("counter", 42).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn decrement(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("decrement", &key, |connection| connection.decrement(&key, amount));
+    }
+
+    /// Set a new expiration time for a exist key.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// assert_eq!(client.touch("not_exists_key", 12345).unwrap(), false);
+    /// client.set("foo", "bar", 123).unwrap();
+    /// assert_eq!(client.touch("foo", 12345).unwrap(), true);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn touch(&self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+        let key = self.prepare_key(key)?;
+        return self.run("touch", &key, |connection| connection.touch(&key, expiration));
+    }
+
+    /// Set new expiration seconds for multiple keys, each with its own expiration. The commands sent to each server
+    /// are pipelined. The result is in the same order as `entries`, `false` means the key doesn't exist.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// client.set("foo", "bar", 10).unwrap();
+    /// assert_eq!(client.multi_touch(&[("foo", 100), ("bar", 200)]).unwrap(), vec![true, false]);
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn multi_touch(&self, entries: &[(&str, u32)]) -> Result<Vec<bool>, MemcacheError> {
+        let mut effective_keys = Vec::with_capacity(entries.len());
+        for (key, _) in entries {
+            effective_keys.push(self.prepare_key(key)?);
+        }
+        let mut result = vec![false; entries.len()];
+
+        let servers = self.servers();
+        for (connection_index, (indexes, keys)) in self.group_by_server(&servers, &effective_keys) {
+            let touches: Vec<(&str, u32)> = indexes
+                .iter()
+                .zip(keys)
+                .map(|(&index, key)| (key, entries[index].1))
+                .collect();
+            let pool = &servers.pools[connection_index];
+            let touched = self.run_on(pool, "multi_touch", |connection| connection.touches(&touches))?;
+            for (index, touched) in indexes.into_iter().zip(touched) {
+                result[index] = touched;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Get the statistics of all servers merged together, for dashboards showing totals. Counters like
+    /// `curr_items`, `bytes` or `get_hits` are summed, while other stats like `version` or `pid` are the first
+    /// server's. `cache_hit_rate` is computed as `get_hits / (get_hits + get_misses)`.
+    ///
+    /// Example:
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// let stats = client.stats_aggregated().unwrap();
+    /// println!("{} items", stats["curr_items"]);
+    /// ```
+    pub fn stats_aggregated(&self) -> Result<Stats, MemcacheError> {
+        Ok(stats::aggregate_stats(
+            self.stats()?.into_iter().map(|(_, stats)| stats),
+        ))
     }
 
     /// Get all servers' statistics.
@@ -452,25 +2227,117 @@ impl Client {
     /// ```
     pub fn stats(&self) -> Result<Vec<(String, Stats)>, MemcacheError> {
         let mut result: Vec<(String, HashMap<String, String>)> = vec![];
-        for connection in self.connections.iter() {
-            let mut connection = connection.get()?;
-            let stats_info = connection.stats()?;
-            let url = connection.get_url();
-            result.push((url, stats_info));
+        for pool in self.servers().pools.iter() {
+            let stats_info = self.run_on(pool, "stats", |connection| connection.stats())?;
+            result.push((pool.url().to_string(), stats_info));
         }
         return Ok(result);
     }
+
+    /// Get the connections of all servers, as reported by `stats conns`.
+    ///
+    /// Example:
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// for (url, connections) in client.stats_connections().unwrap() {
+    ///     println!("{} has {} connections", url, connections.len());
+    /// }
+    /// ```
+    pub fn stats_connections(&self) -> Result<Vec<(String, Vec<ConnectionInfo>)>, MemcacheError> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let stats = self.run_on(pool, "stats_connections", |connection| {
+                connection.stats_with_arg("conns")
+            })?;
+            result.push((pool.url().to_string(), stats::parse_connection_stats(stats)?));
+        }
+        Ok(result)
+    }
+
+    /// List up to `limit` items of the slab class `slab_id` of all servers, 0 meaning all of them, with the
+    /// `stats cachedump` command. It is only supported by the ASCII protocol, and memcached caps the response to 2MB
+    /// and may remove the command in future versions, so it's only meant for debugging.
+    ///
+    /// Example:
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    /// for (url, items) in client.stats_cachedump(1, 100).unwrap() {
+    ///     for item in items {
+    ///         println!("{}: {} ({} bytes)", url, item.key, item.bytes);
+    ///     }
+    /// }
+    /// ```
+    pub fn stats_cachedump(&self, slab_id: u32, limit: u32) -> Result<Vec<(String, Vec<CachedItem>)>, MemcacheError> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let items = self.run_on(pool, "stats_cachedump", |connection| match connection {
+                Protocol::Ascii(protocol) => protocol.stats_cachedump(slab_id, limit),
+                _ => Err(ClientError::Error(Cow::Borrowed(
+                    "stats cachedump is only supported by the ASCII protocol",
+                )))?,
+            })?;
+            result.push((pool.url().to_string(), items));
+        }
+        Ok(result)
+    }
+
+    /// Get the item statistics of each slab class of all servers, as reported by `stats items`, ordered by slab id.
+    ///
+    /// Example:
+    /// ```rust
+    /// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    /// for (url, slabs) in client.stats_items().unwrap() {
+    ///     for (slab_id, items) in slabs {
+    ///         println!("{} slab {}: {} items, {} evicted", url, slab_id, items.number, items.evicted);
+    ///     }
+    /// }
+    /// ```
+    pub fn stats_items(&self) -> Result<Vec<(String, SlabItemStats)>, MemcacheError> {
+        let mut result = Vec::with_capacity(self.server_count());
+        for pool in self.servers().pools.iter() {
+            let stats = self.run_on(pool, "stats_items", |connection| connection.stats_with_arg("items"))?;
+            result.push((pool.url().to_string(), stats::parse_item_stats(stats)?));
+        }
+        Ok(result)
+    }
 }
 
+/// A builder of `Client`. Clone it to build several clients sharing the same configuration, like a client for reads
+/// and another for writes with different servers.
+#[derive(Clone)]
 pub struct ClientBuilder {
     targets: Vec<String>,
     max_size: u32,
     min_idle: Option<u32>,
     max_lifetime: Option<Duration>,
+    idle_timeout: Option<Duration>,
     read_timeout: Option<Duration>,
     write_timeout: Option<Duration>,
     connection_timeout: Option<Duration>,
     hash_function: fn(&str) -> u64,
+    hash_function_name: String,
+    #[cfg(feature = "ketama")]
+    ketama_hashing: bool,
+    pool_shards: usize,
+    key_prefix: Option<String>,
+    #[cfg(feature = "key-hashing")]
+    key_hashing: bool,
+    max_key_length: usize,
+    read_failover: u32,
+    #[cfg(feature = "sticky-keys")]
+    sticky_keys: Vec<Pattern>,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
+    tcp_nodelay: bool,
+    #[cfg(feature = "tls")]
+    tls_options: TlsOptions,
+    miss_observer: Option<MissObserver>,
+    #[cfg(feature = "test-mode")]
+    mock: Option<Arc<dyn MockBackend>>,
+    connection_callbacks: ConnectionCallbacks,
+    protocol_factory: Option<ProtocolFactory>,
+    #[cfg(feature = "srv-discovery")]
+    srv_discovery: Option<(String, Duration)>,
 }
 
 impl ClientBuilder {
@@ -481,10 +2348,34 @@ impl ClientBuilder {
             max_size: 1,
             min_idle: None,
             max_lifetime: None,
+            idle_timeout: None,
             read_timeout: None,
             write_timeout: None,
             connection_timeout: None,
             hash_function: default_hash_function,
+            hash_function_name: "default".to_string(),
+            #[cfg(feature = "ketama")]
+            ketama_hashing: false,
+            pool_shards: 1,
+            key_prefix: None,
+            #[cfg(feature = "key-hashing")]
+            key_hashing: false,
+            max_key_length: MAX_KEY_LENGTH,
+            read_failover: 0,
+            #[cfg(feature = "sticky-keys")]
+            sticky_keys: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            tcp_nodelay: true,
+            #[cfg(feature = "tls")]
+            tls_options: TlsOptions::default(),
+            miss_observer: None,
+            #[cfg(feature = "test-mode")]
+            mock: None,
+            connection_callbacks: ConnectionCallbacks::default(),
+            protocol_factory: None,
+            #[cfg(feature = "srv-discovery")]
+            srv_discovery: None,
         }
     }
 
@@ -495,56 +2386,451 @@ impl ClientBuilder {
         if targets.len() == 0 {
             return Err(MemcacheError::BadURL("No servers specified".to_string()));
         }
+        for target in targets.iter() {
+            parse_server_url(target)?;
+        }
 
         self.targets.extend(targets);
         Ok(self)
     }
 
+    /// Create a client builder with the servers from the environment variable `var`, which is a comma separated list
+    /// of server URLs, like `memcache://10.0.0.1:11211?timeout=1, memcache://10.0.0.2:11211?timeout=1`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// std::env::set_var("MY_MEMCACHE_SERVERS", "memcache://localhost:12345, memcache://localhost:12346");
+    /// let client = memcache::ClientBuilder::from_env("MY_MEMCACHE_SERVERS").unwrap().build().unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn from_env(var: &str) -> Result<Self, MemcacheError> {
+        let servers = std::env::var(var)
+            .map_err(|e| ClientError::Error(Cow::Owned(format!("can't read the servers from ${}: {}", var, e))))?;
+        let targets: Vec<&str> = servers
+            .split(',')
+            .map(str::trim)
+            .filter(|server| !server.is_empty())
+            .collect();
+        Self::new().add_server(targets)
+    }
+
+    /// Create a client builder with the servers from the `MEMCACHE_SERVERS` environment variable, see `from_env`.
+    pub fn from_default_env() -> Result<Self, MemcacheError> {
+        Self::from_env("MEMCACHE_SERVERS")
+    }
+
+    /// Create a client builder from `options`, e.g. loaded from a configuration file. See `ClientOptions`.
+    pub fn from_options(options: ClientOptions) -> Result<Self, MemcacheError> {
+        let mut builder = Self::new().add_server(options.servers)?;
+        builder.max_size = options.max_size;
+        builder.min_idle = options.min_idle;
+        builder.max_lifetime = options.max_lifetime;
+        builder.read_timeout = options.read_timeout;
+        builder.write_timeout = options.write_timeout;
+        builder.connection_timeout = options.connection_timeout;
+        Ok(builder)
+    }
+
     /// Set the maximum number of connections managed by the pool.
     pub fn with_max_pool_size(mut self, max_size: u32) -> Self {
         self.max_size = max_size;
         self
     }
 
-    /// Set the minimum number of idle connections to maintain in the pool.
-    pub fn with_min_idle_conns(mut self, min_idle: u32) -> Self {
-        self.min_idle = Some(min_idle);
+    /// Set the minimum number of idle connections to maintain in the pool. A server's `min_idle` URL parameter, like
+    /// `memcache://localhost:11211?min_idle=5`, overrides it for that server's pool.
+    pub fn with_min_idle_conns(mut self, min_idle: u32) -> Self {
+        self.min_idle = Some(min_idle);
+        self
+    }
+
+    /// Set the maximum lifetime of connections in the pool.
+    pub fn with_max_conn_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = Some(max_lifetime);
+        self
+    }
+
+    /// Set how long a connection may sit unused in the pool before it's closed, e.g. shorter than the idle timeout
+    /// of a NAT or firewall between the client and the servers, which would break the connection silently. 60
+    /// seconds suits most cloud environments, the default is 10 minutes.
+    ///
+    /// Only the connections above the minimum number of idle connections are closed, so this has no effect unless
+    /// `with_min_idle_conns` is set below the maximum pool size.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set the socket read timeout for TCP connections.
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Set the socket write timeout for TCP connections.
+    pub fn with_write_timeout(mut self, write_timeout: Duration) -> Self {
+        self.write_timeout = Some(write_timeout);
+        self
+    }
+
+    /// Set the connection timeout for TCP connections.
+    pub fn with_connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection_timeout = Some(connection_timeout);
+        self
+    }
+
+    /// When the server of a key can't be reached, retry `get` and `get_with_metadata` on up to `max_fallback` of the
+    /// next servers instead of failing right away: the successors on the ring with `with_ketama_hashing`, otherwise
+    /// the following servers in the order they were added. Disabled by default.
+    ///
+    /// Writes are never retried on another server, so the value is usually missing from the fallback servers and
+    /// these reads return `None`, like for a cold cache. This only avoids failing when a server is down.
+    pub fn with_read_failover(mut self, max_fallback: u32) -> Self {
+        self.read_failover = max_fallback;
+        self
+    }
+
+    /// Never fail over the reads of the keys matching one of the glob `patterns`, like `counter:*`, e.g. for the keys
+    /// which must only be read from their own server: an error is returned when it can't be reached, instead of
+    /// reading from another server with `with_read_failover`. The patterns are matched against the keys sent to the
+    /// servers, so against the hash of the keys hashed with `with_key_hashing`. Requires the `sticky-keys` feature.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_read_failover(1)
+    ///     .with_sticky_keys(&["counter:*"])
+    ///     .unwrap()
+    ///     .build()
+    ///     .unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    #[cfg(feature = "sticky-keys")]
+    pub fn with_sticky_keys(mut self, patterns: &[&str]) -> Result<Self, MemcacheError> {
+        for pattern in patterns {
+            let pattern = Pattern::new(pattern)
+                .map_err(|e| ClientError::from(format!("invalid sticky key pattern '{}': {}", pattern, e)))?;
+            self.sticky_keys.push(pattern);
+        }
+        Ok(self)
+    }
+
+    /// Compress the values larger than `threshold` bytes with zlib when storing them with `set`, `add`, `replace`,
+    /// `cas`, `set_many`, `set_many_concurrent`, `add_many` or `replace_only_if_older_than`, see `CompressedValue`.
+    /// The values are decompressed when read, whatever their type.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_compression_threshold(1024)
+    ///     .build()
+    ///     .unwrap();
+    /// let value = "a".repeat(10_000);
+    /// client.set("large", value.as_str(), 0).unwrap();
+    /// let (raw, _, _): (Vec<u8>, u32, Option<u64>) = client.get("large").unwrap().unwrap();
+    /// assert!(raw.len() < 1024);
+    /// assert_eq!(client.get::<String>("large").unwrap(), Some(value));
+    /// # client.flush().unwrap();
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn with_compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    /// Set whether `TCP_NODELAY` is enabled on TCP and TLS connections, which is the default. The `tcp_nodelay=true`
+    /// or `tcp_nodelay=false` parameter of a server URL takes precedence over this setting.
+    pub fn with_tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Set the TLS settings of the `memcache+tls` servers, on top of the parameters of their URLs, which take
+    /// precedence.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// use memcache::TlsOptions;
+    ///
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache+tls://localhost:12350")
+    ///     .unwrap()
+    ///     .with_tls_options(TlsOptions::insecure_skip_verify())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn with_tls_options(mut self, options: TlsOptions) -> Self {
+        self.tls_options = options;
+        self
+    }
+
+    /// Set the hash function for the client.
+    pub fn with_hash_function(mut self, hash_function: fn(&str) -> u64) -> Self {
+        self.hash_function = hash_function;
+        self.hash_function_name = "custom".to_string();
+        #[cfg(feature = "ketama")]
+        {
+            self.ketama_hashing = false;
+        }
+        self
+    }
+
+    /// Set the hash function for the client, with a name which is shown when the client is displayed instead of
+    /// `custom`.
+    pub fn with_named_hash_function(mut self, name: &str, hash_function: fn(&str) -> u64) -> Self {
+        self.hash_function = hash_function;
+        self.hash_function_name = name.to_string();
+        #[cfg(feature = "ketama")]
+        {
+            self.ketama_hashing = false;
+        }
+        self
+    }
+
+    /// Distribute the keys on the servers with ketama consistent hashing, instead of the hash of the key modulo the
+    /// number of servers. When a server is added or removed, only the keys of about one server move, instead of
+    /// almost all of them. The servers are placed on the ring by their `host:port`. Requires the `ketama` feature.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let client = memcache::Client::builder()
+    ///     .add_server(vec!["memcache://localhost:12346", "memcache://localhost:12347"])
+    ///     .unwrap()
+    ///     .with_ketama_hashing()
+    ///     .build()
+    ///     .unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// let value: Option<String> = client.get("foo").unwrap();
+    /// assert_eq!(value, Some(String::from("bar")));
+    /// ```
+    #[cfg(feature = "ketama")]
+    pub fn with_ketama_hashing(mut self) -> Self {
+        self.hash_function = hash::ketama_hash;
+        self.hash_function_name = "ketama".to_string();
+        self.ketama_hashing = true;
+        self
+    }
+
+    /// Replace keys longer than 250 bytes, which memcached doesn't accept, with the hex encoded SHA-256 digest of the
+    /// key instead of returning `ClientError::KeyTooLong`. The values are still returned by the original keys, see
+    /// `Client::hash_key` for the key which is stored on the servers.
+    ///
+    /// Two different long keys map to the same value only if their digests collide. For `n` distinct long keys the
+    /// probability is about `n² / 2²⁵⁷`:
+    ///
+    /// | long keys | collision probability |
+    /// |-----------|-----------------------|
+    /// | 10⁶       | 4 × 10⁻⁶⁶             |
+    /// | 10⁹       | 4 × 10⁻⁶⁰             |
+    /// | 10¹²      | 4 × 10⁻⁵⁴             |
+    ///
+    /// Requires the `key-hashing` feature.
+    #[cfg(feature = "key-hashing")]
+    pub fn with_key_hashing(mut self, enabled: bool) -> Self {
+        self.key_hashing = enabled;
+        self
+    }
+
+    /// Reject keys longer than `length` bytes with `ClientError::KeyTooLong`, for servers enforcing a stricter limit
+    /// than memcached's 250 bytes, which is the default. With `with_key_hashing`, keys longer than `length` are hashed
+    /// instead, so `length` must be at least 64 bytes.
+    ///
+    /// Keys created with `Client::key_builder` include the prefix set with `with_key_builder_prefix`, which must be
+    /// shorter than `length`, otherwise `build` fails.
+    pub fn with_max_key_length(mut self, length: usize) -> Self {
+        self.max_key_length = length;
+        self
+    }
+
+    /// Set the prefix of the keys created with `Client::key_builder`.
+    pub fn with_key_builder_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Create `shards` connection pools per server instead of a single one, each with the configured pool size. Every
+    /// thread picks one of the pools by its thread id, which reduces the contention on the pool's lock when many
+    /// threads share a client. A value of 0 is treated as 1.
+    pub fn with_sharded_pool(mut self, shards: usize) -> Self {
+        self.pool_shards = shards.max(1);
+        self
+    }
+
+    /// Call `observer` with each key and whether it was a hit after `Client::get` and `Client::gets`, e.g. to export the
+    /// cache hit rate. The key is the one passed by the caller, before it's hashed by `with_key_hashing`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let misses = Arc::new(AtomicUsize::new(0));
+    /// let counter = misses.clone();
+    /// let client = memcache::ClientBuilder::new()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_miss_observer(move |_key, hit| {
+    ///         if !hit {
+    ///             counter.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .build()
+    ///     .unwrap();
+    /// let _: Option<String> = client.get("missing_key").unwrap();
+    /// assert_eq!(misses.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn with_miss_observer(mut self, observer: impl Fn(&str, bool) + Send + Sync + 'static) -> Self {
+        self.miss_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Call `callback` with the server's name, as `host:port`, each time a connection to a server is established.
+    pub fn with_connect_callback(mut self, callback: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.connection_callbacks.on_connect = Some(Arc::new(callback));
         self
     }
 
-    /// Set the maximum lifetime of connections in the pool.
-    pub fn with_max_conn_lifetime(mut self, max_lifetime: Duration) -> Self {
-        self.max_lifetime = Some(max_lifetime);
+    /// Call `callback` with the server's name each time a connection is discarded from its pool because it's
+    /// broken: after an I/O error in a command, or when it fails the check done before being reused.
+    pub fn with_disconnect_callback(mut self, callback: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.connection_callbacks.on_disconnect = Some(Arc::new(callback));
         self
     }
 
-    /// Set the socket read timeout for TCP connections.
-    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
-        self.read_timeout = Some(read_timeout);
+    /// Call `callback` with the server's name and the error each time connecting to a server, or checking a
+    /// connection before reusing it, fails.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let errors = Arc::new(AtomicUsize::new(0));
+    /// let counter = errors.clone();
+    /// let result = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:1")
+    ///     .unwrap()
+    ///     .with_connection_timeout(Duration::from_millis(100))
+    ///     .with_error_callback(move |server, _error| {
+    ///         assert_eq!(server, "localhost:1");
+    ///         counter.fetch_add(1, Ordering::SeqCst);
+    ///     })
+    ///     .build();
+    /// assert!(result.is_err());
+    /// assert!(errors.load(Ordering::SeqCst) > 0);
+    /// ```
+    pub fn with_error_callback(mut self, callback: impl Fn(&str, &MemcacheError) + Send + Sync + 'static) -> Self {
+        self.connection_callbacks.on_error = Some(Arc::new(callback));
         self
     }
 
-    /// Set the socket write timeout for TCP connections.
-    pub fn with_write_timeout(mut self, write_timeout: Duration) -> Self {
-        self.write_timeout = Some(write_timeout);
+    /// Build the protocol of each new connection with `factory`, from the connected stream, instead of picking it with
+    /// the `protocol` URL parameter. The factory can use the stream before returning the protocol, e.g. to send a
+    /// preamble expected by a proxy, and pick the protocol per server with `Protocol::ascii` or `Protocol::binary`.
+    ///
+    /// Authentication with the URL's credentials happens after the factory returns.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::{Protocol, Stream};
+    ///
+    /// let client = memcache::Client::builder()
+    ///     .add_server("memcache://localhost:12345")
+    ///     .unwrap()
+    ///     .with_protocol_factory(|stream: Stream| Protocol::ascii(stream))
+    ///     .build()
+    ///     .unwrap();
+    /// client.set("foo", "bar", 0).unwrap();
+    /// # client.flush().unwrap();
+    /// ```
+    pub fn with_protocol_factory(mut self, factory: impl Fn(Stream) -> Protocol + Send + Sync + 'static) -> Self {
+        self.protocol_factory = Some(Arc::new(factory));
         self
     }
 
-    /// Set the connection timeout for TCP connections.
-    pub fn with_connection_timeout(mut self, connection_timeout: Duration) -> Self {
-        self.connection_timeout = Some(connection_timeout);
+    /// Run the client's commands on `mock` instead of memcached servers, e.g. an `InMemoryBackend` to test code using
+    /// the client without a running server. The servers added with `add_server` are ignored, and no connection is
+    /// made. See `InMemoryBackend` for an example.
+    #[cfg(feature = "test-mode")]
+    pub fn with_test_mode(mut self, mock: impl MockBackend + 'static) -> Self {
+        self.mock = Some(Arc::new(mock));
         self
     }
 
-    /// Set the hash function for the client.
-    pub fn with_hash_function(mut self, hash_function: fn(&str) -> u64) -> Self {
-        self.hash_function = hash_function;
+    /// Discover memcached servers from the DNS SRV record `srv_name` (like `_memcache._tcp.svc.cluster.local`).
+    ///
+    /// The record is resolved when the client is built, and a pool is created for each discovered host in addition
+    /// to the servers added with `add_server`. Then a background thread resolves it again every `refresh_interval`
+    /// to pick up the servers being added or removed: when they changed, the server list of the client and its clones
+    /// is replaced at once, keeping the pools of the servers still present. The thread stops at its first refresh
+    /// after the client and its clones are dropped.
+    #[cfg(feature = "srv-discovery")]
+    pub fn with_srv_discovery(mut self, srv_name: &str, refresh_interval: Duration) -> Self {
+        self.srv_discovery = Some((srv_name.to_string(), refresh_interval));
         self
     }
 
     /// Build the client. This will create a connection pool and return a client, or an error if the connection pool could not be created.
     pub fn build(self) -> Result<Client, MemcacheError> {
-        let urls = self.targets;
+        if let Some(ref prefix) = self.key_prefix {
+            if prefix.len() >= self.max_key_length {
+                Err(ClientError::Error(Cow::Owned(format!(
+                    "key prefix '{}' isn't shorter than the max key length of {} bytes",
+                    prefix, self.max_key_length
+                ))))?
+            }
+        }
+        #[cfg(feature = "key-hashing")]
+        if self.key_hashing && self.max_key_length < 64 {
+            Err(ClientError::Error(Cow::Borrowed(
+                "the max key length must be at least 64 bytes to fit hashed keys",
+            )))?
+        }
+
+        #[cfg(feature = "test-mode")]
+        if let Some(ref mock) = self.mock {
+            let url = Url::parse("memcache://mock")?;
+            let pool = r2d2::Pool::builder()
+                .max_size(self.max_size)
+                .build(ConnectionManager::mock(url.clone(), mock.clone()).with_callbacks(self.connection_callbacks))
+                .map_err(MemcacheError::PoolError)?;
+            let mut client = Client::with_sharded_pools(vec![ShardedPool::new(url, vec![pool])]);
+            client.key_prefix = self.key_prefix;
+            #[cfg(feature = "key-hashing")]
+            {
+                client.key_hashing = self.key_hashing;
+            }
+            client.max_key_length = self.max_key_length;
+            #[cfg(feature = "compression")]
+            {
+                client.compression_threshold = self.compression_threshold;
+            }
+            client.miss_observer = self.miss_observer;
+            return Ok(client);
+        }
+
+        #[cfg(feature = "srv-discovery")]
+        let discovered_urls = match self.srv_discovery {
+            Some((ref srv_name, _)) => discovery::resolve_srv(srv_name)?,
+            None => vec![],
+        };
+        #[cfg(not(feature = "srv-discovery"))]
+        let discovered_urls = vec![];
+        let urls: Vec<String> = self.targets.iter().cloned().chain(discovered_urls).collect();
 
         if urls.len() == 0 {
             return Err(MemcacheError::BadURL("No servers specified".to_string()));
@@ -553,51 +2839,139 @@ impl ClientBuilder {
         let max_size = self.max_size;
         let min_idle = self.min_idle;
         let max_lifetime = self.max_lifetime;
+        let idle_timeout = self.idle_timeout;
         let timeout = self.connection_timeout;
-
-        let mut connections = vec![];
-
-        for url in urls.iter() {
-            let url = Url::parse(url.as_str()).map_err(|e| MemcacheError::BadURL(e.to_string()))?;
-
-            match url.scheme() {
-                "memcache" | "memcache+tls" | "memcache+udp" => {}
-                _ => {
-                    return Err(MemcacheError::BadURL(format!("Unsupported protocol: {}", url.scheme())));
-                }
+        let pool_shards = self.pool_shards;
+        let tcp_nodelay = self.tcp_nodelay;
+        #[cfg(feature = "tls")]
+        let tls_options = self.tls_options;
+        let connection_callbacks = self.connection_callbacks;
+        let protocol_factory = self.protocol_factory;
+
+        let build_pool = move |url: &str| -> Result<ShardedPool, MemcacheError> {
+            let mut url = parse_server_url(url)?;
+            if !tcp_nodelay && !url.query_pairs().any(|(k, _)| k == "tcp_nodelay") {
+                url.query_pairs_mut().append_pair("tcp_nodelay", "false");
             }
+            #[cfg(feature = "tls")]
+            tls_options.apply(&mut url);
+            let min_idle = min_idle_from_url(&url, min_idle, max_size)?;
+
+            let mut shards = Vec::with_capacity(pool_shards);
+            for _ in 0..pool_shards {
+                let mut builder = r2d2::Pool::builder()
+                    .max_size(max_size)
+                    .min_idle(min_idle)
+                    .max_lifetime(max_lifetime);
+
+                if let Some(timeout) = timeout {
+                    builder = builder.connection_timeout(timeout);
+                }
+                if let Some(idle_timeout) = idle_timeout {
+                    builder = builder.idle_timeout(Some(idle_timeout));
+                }
 
-            let mut builder = r2d2::Pool::builder()
-                .max_size(max_size)
-                .min_idle(min_idle)
-                .max_lifetime(max_lifetime);
-
-            if let Some(timeout) = timeout {
-                builder = builder.connection_timeout(timeout);
+                shards.push(
+                    builder
+                        .build(
+                            ConnectionManager::new(url.clone())
+                                .with_callbacks(connection_callbacks.clone())
+                                .with_protocol_factory(protocol_factory.clone()),
+                        )
+                        .map_err(|e| MemcacheError::PoolError(e))?,
+                );
             }
+            Ok(ShardedPool::new(url, shards))
+        };
 
-            let connection = builder
-                .build(ConnectionManager::new(url))
-                .map_err(|e| MemcacheError::PoolError(e))?;
+        let mut connections = vec![];
 
-            connections.push(connection);
+        for url in urls.iter() {
+            connections.push(build_pool(url)?);
         }
 
+        #[cfg(feature = "ketama")]
+        let servers = if self.ketama_hashing {
+            Servers::with_ketama_ring(connections)
+        } else {
+            Servers::new(connections)
+        };
+        #[cfg(not(feature = "ketama"))]
+        let servers = Servers::new(connections);
         let client = Client {
-            connections,
+            servers: Arc::new(RwLock::new(Arc::new(servers))),
             hash_function: self.hash_function,
+            hash_function_name: self.hash_function_name,
+            key_prefix: self.key_prefix,
+            #[cfg(feature = "key-hashing")]
+            key_hashing: self.key_hashing,
+            max_key_length: self.max_key_length,
+            read_failover: self.read_failover,
+            #[cfg(feature = "sticky-keys")]
+            sticky_keys: self.sticky_keys,
+            #[cfg(feature = "compression")]
+            compression_threshold: self.compression_threshold,
+            miss_observer: self.miss_observer,
+            shadow_writer: Arc::default(),
         };
-
         client.set_read_timeout(self.read_timeout)?;
         client.set_write_timeout(self.write_timeout)?;
 
+        #[cfg(feature = "srv-discovery")]
+        {
+            if let Some((srv_name, refresh_interval)) = self.srv_discovery {
+                let discovery = SrvDiscovery::new(
+                    srv_name,
+                    refresh_interval,
+                    self.targets,
+                    &urls,
+                    self.read_timeout,
+                    self.write_timeout,
+                    build_pool,
+                )?;
+                discovery.spawn(Arc::downgrade(&client.servers));
+            }
+        }
+
         Ok(client)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::Connectable;
     use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn clone_builder() {
+        let read_builder = super::ClientBuilder::new()
+            .with_key_builder_prefix("app")
+            .add_server("memcache://localhost:12345")
+            .unwrap();
+        let write_builder = read_builder.clone().add_server("memcache://localhost:12346").unwrap();
+        assert_eq!(read_builder.targets, vec!["memcache://localhost:12345"]);
+        assert_eq!(
+            write_builder.targets,
+            vec!["memcache://localhost:12345", "memcache://localhost:12346"]
+        );
+        assert_eq!(write_builder.key_prefix.as_deref(), Some("app"));
+    }
+
+    #[test]
+    fn connectable_owned_tuple_and_urls() {
+        let host = String::from("memcache://localhost");
+        assert_eq!((host.clone(), 12345).get_urls(), vec!["memcache://localhost:12345"]);
+        assert_eq!((&(host, 12345)).get_urls(), vec!["memcache://localhost:12345"]);
+
+        let urls = vec![
+            Url::parse("memcache://localhost:12345").unwrap(),
+            Url::parse("memcache://localhost:12346").unwrap(),
+        ];
+        let expected = vec!["memcache://localhost:12345", "memcache://localhost:12346"];
+        assert_eq!(urls.as_slice().get_urls(), expected);
+        assert_eq!(urls.get_urls(), expected);
+    }
 
     #[test]
     fn build_client_happy_path() {
@@ -611,11 +2985,8 @@ mod tests {
 
     #[test]
     fn build_client_bad_url() {
-        let client = super::Client::builder()
-            .add_server("memcache://localhost:12345:")
-            .unwrap()
-            .build();
-        assert!(client.is_err());
+        let builder = super::Client::builder().add_server("memcache://localhost:12345:");
+        assert!(builder.is_err());
     }
 
     #[test]
@@ -665,6 +3036,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn invalid_key_doesnt_take_a_connection() {
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_connection_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        // the only connection of the pool is held, so taking another one would time out with a pool error
+        client
+            .with_connection_for_key("foo", |_| {
+                for result in [client.set("foo bar", 1, 0), client.delete("foo\r\n").map(|_| ())].iter() {
+                    match result {
+                        Err(super::MemcacheError::ClientError(super::ClientError::KeyInvalidCharacters)) => {}
+                        result => panic!("unexpected result {:?}", result),
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "test-mode")]
+    #[test]
+    fn max_key_length() {
+        use crate::mock::InMemoryBackend;
+
+        let client = super::Client::builder()
+            .with_max_key_length(10)
+            .with_key_builder_prefix("app")
+            .with_test_mode(InMemoryBackend::new())
+            .build()
+            .unwrap();
+        client.set("short_key", 1, 0).unwrap();
+        for result in [
+            client.set("longer_key!", 1, 0),
+            client.key_builder("users").id(42).build().map(|_| ()),
+        ]
+        .iter()
+        {
+            match result {
+                Err(super::MemcacheError::ClientError(super::ClientError::KeyTooLong)) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+        assert_eq!(client.key_builder("u").id(1).build().unwrap(), "app:u:1");
+
+        let builder = super::Client::builder().with_test_mode(InMemoryBackend::new());
+        assert!(builder
+            .clone()
+            .with_max_key_length(3)
+            .with_key_builder_prefix("app")
+            .build()
+            .is_err());
+        #[cfg(feature = "key-hashing")]
+        assert!(builder.with_max_key_length(32).with_key_hashing(true).build().is_err());
+    }
+
     #[test]
     fn build_client_zero_min_idle_conns() {
         let client = super::Client::builder()
@@ -675,6 +3104,27 @@ mod tests {
         assert!(client.is_ok(), "Should handle zero min idle conns");
     }
 
+    #[test]
+    fn build_client_with_idle_timeout() {
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_min_idle_conns(0)
+            .with_idle_timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.servers().pools[0].shards()[0].idle_timeout(),
+            Some(Duration::from_secs(60))
+        );
+
+        let client = super::Client::connect("memcache://localhost:12345").unwrap();
+        assert_eq!(
+            client.servers().pools[0].shards()[0].idle_timeout(),
+            Some(Duration::from_secs(600))
+        );
+    }
+
     #[test]
     fn build_client_invalid_hash_function() {
         let invalid_hash_function = |_: &str| -> u64 {
@@ -690,11 +3140,84 @@ mod tests {
 
     #[test]
     fn build_client_with_unsupported_protocol() {
+        let builder = super::Client::builder().add_server("unsupported://localhost:12345");
+        assert!(builder.is_err(), "Expected error when using an unsupported protocol");
+
+        let builder = super::Client::builder().add_server(vec!["memcache://localhost:12345", "memcachee://localhost"]);
+        assert!(
+            builder.is_err(),
+            "Expected error when any of the servers uses an unsupported protocol"
+        );
+    }
+
+    #[test]
+    fn min_idle_url_parameter() {
         let client = super::Client::builder()
-            .add_server("unsupported://localhost:12345")
+            .add_server(vec![
+                "memcache://localhost:12345?min_idle=2",
+                "memcache://localhost:12346",
+            ])
             .unwrap()
-            .build();
-        assert!(client.is_err(), "Expected error when using an unsupported protocol");
+            .with_max_pool_size(5)
+            .with_min_idle_conns(1)
+            .build()
+            .unwrap();
+        assert_eq!(client.servers().pools[0].shards()[0].min_idle(), Some(2));
+        assert_eq!(client.servers().pools[1].shards()[0].min_idle(), Some(1));
+
+        for url in [
+            "memcache://localhost:12345?min_idle=6",
+            "memcache://localhost:12345?min_idle=many",
+        ]
+        .iter()
+        {
+            let result = super::Client::builder()
+                .add_server(*url)
+                .unwrap()
+                .with_max_pool_size(5)
+                .build();
+            assert!(
+                matches!(result, Err(super::MemcacheError::BadURL(_))),
+                "{} should be rejected",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn parse_server_url_schemes() {
+        for url in [
+            "memcache://localhost:12345",
+            "memcache+tcp://localhost:12345",
+            "memcache+tls://localhost:12350",
+            "memcache+udp://localhost:22345",
+            "memcache+unix:///tmp/memcached.sock",
+            "memcache+unix-abstract:///@memcached",
+        ]
+        .iter()
+        {
+            assert!(super::parse_server_url(url).is_ok(), "{} should be accepted", url);
+        }
+        for url in [
+            "memcachee://localhost:12345",
+            "http://localhost:12345",
+            "memcache+foo://localhost",
+        ]
+        .iter()
+        {
+            assert!(super::parse_server_url(url).is_err(), "{} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn build_client_with_explicit_transport_schemes() {
+        let client = super::Client::connect("memcache+tcp://localhost:12345").unwrap();
+        client.version().unwrap();
+        #[cfg(unix)]
+        {
+            let client = super::Client::connect("memcache+unix:///tmp/memcached.sock").unwrap();
+            client.version().unwrap();
+        }
     }
 
     #[test]
@@ -712,6 +3235,309 @@ mod tests {
         assert!(client.is_ok(), "Should successfully build with all optional parameters");
     }
 
+    #[test]
+    fn build_client_with_tcp_nodelay() {
+        use crate::stream::Stream;
+
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .add_server("memcache://localhost:12345?tcp_nodelay=true")
+            .unwrap()
+            .add_server("memcache://localhost:12345?tcp_nodelay=false")
+            .unwrap()
+            .with_tcp_nodelay(false)
+            .build()
+            .unwrap();
+        let mut nodelay = vec![];
+        for pool in client.servers().pools.iter() {
+            match pool.get().unwrap().stream() {
+                Stream::Tcp(stream) => nodelay.push(stream.nodelay().unwrap()),
+                _ => panic!("not a tcp stream"),
+            }
+        }
+        assert_eq!(nodelay, vec![false, true, false]);
+    }
+
+    #[cfg(feature = "test-mode")]
+    #[test]
+    fn server_count() {
+        let client = super::Client::builder()
+            .with_test_mode(crate::InMemoryBackend::new())
+            .build()
+            .unwrap();
+        assert_eq!(client.server_count(), 1);
+        assert!(client.is_single_server());
+    }
+
+    #[test]
+    fn from_options() {
+        let options = crate::ClientOptions {
+            servers: vec!["memcache://localhost:12345".to_string()],
+            max_size: 3,
+            min_idle: Some(1),
+            connection_timeout: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        let client = super::ClientBuilder::from_options(options).unwrap().build().unwrap();
+        let servers = client.servers();
+        let pool = &servers.pools[0].shards()[0];
+        assert_eq!(pool.max_size(), 3);
+        assert_eq!(pool.min_idle(), Some(1));
+        assert_eq!(pool.connection_timeout(), Duration::from_secs(2));
+
+        assert!(super::ClientBuilder::from_options(crate::ClientOptions::default()).is_err());
+    }
+
+    #[test]
+    fn read_failover() {
+        use crate::connection::{ConnectionManager, ShardedPool};
+
+        let pool = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            let pool = r2d2::Pool::builder()
+                .min_idle(Some(0))
+                .connection_timeout(Duration::from_millis(100))
+                .build(ConnectionManager::new(url.clone()))
+                .unwrap();
+            ShardedPool::new(url, vec![pool])
+        };
+        let mut client =
+            super::Client::with_sharded_pools(vec![pool("memcache://localhost:1"), pool("memcache://localhost:12345")]);
+        let key = (0..)
+            .map(|i| format!("read_failover_{}", i))
+            .find(|key| client.connection_index(&client.servers(), key) == 0)
+            .unwrap();
+        assert!(client.get::<String>(&key).is_err());
+
+        client.read_failover = 1;
+        let server = super::Client::connect("memcache://localhost:12345").unwrap();
+        server.set(&key, "value", 10).unwrap();
+        assert_eq!(client.get::<String>(&key).unwrap(), Some(String::from("value")));
+        // writes are not retried on another server
+        assert!(client.set(&key, "value", 10).is_err());
+
+        #[cfg(feature = "sticky-keys")]
+        {
+            client.sticky_keys = vec![glob::Pattern::new("read_failover_*").unwrap()];
+            assert!(client.get::<String>(&key).is_err());
+            assert!(super::ClientBuilder::new()
+                .with_sticky_keys(&["[read_failover"])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn healthcheck() {
+        use crate::connection::{ConnectionManager, ShardedPool};
+
+        let pool = || {
+            let url = Url::parse("memcache://localhost:1").unwrap();
+            let pool = r2d2::Pool::builder()
+                .min_idle(Some(0))
+                .connection_timeout(Duration::from_millis(500))
+                .build(ConnectionManager::new(url.clone()))
+                .unwrap();
+            ShardedPool::new(url, vec![pool])
+        };
+        let client = super::Client::with_sharded_pools((0..4).map(|_| pool()).collect());
+        let started = std::time::Instant::now();
+        let unreachable = vec![String::from("memcache://localhost:1"); 4];
+        assert_eq!(client.healthcheck(), super::HealthStatus::Unhealthy(unreachable));
+        // the servers are checked concurrently instead of waiting for each connection timeout in turn
+        assert!(
+            started.elapsed() < Duration::from_millis(1500),
+            "{:?}",
+            started.elapsed()
+        );
+    }
+
+    #[test]
+    fn protocol_factory() {
+        use crate::protocol::Protocol;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_max_pool_size(2)
+            .with_protocol_factory(move |stream| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Protocol::ascii(stream)
+            })
+            .build()
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        match **client.servers().pools[0].get().unwrap() {
+            Protocol::Ascii(_) => {}
+            _ => panic!("expected the ASCII protocol"),
+        }
+        client.set("protocol_factory", "value", 10).unwrap();
+        assert_eq!(
+            client.get::<String>("protocol_factory").unwrap(),
+            Some(String::from("value"))
+        );
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn build_client_with_tls_insecure_skip_verify() {
+        let client = super::Client::builder()
+            .add_server("memcache+tls://localhost:12350")
+            .unwrap()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_tls_options(super::TlsOptions::insecure_skip_verify())
+            .build()
+            .unwrap();
+        let servers = client.servers();
+        let queries: Vec<Option<&str>> = servers.pools.iter().map(|pool| pool.url().query()).collect();
+        assert_eq!(queries, vec![Some("verify_mode=none"), None]);
+    }
+
+    #[test]
+    fn miss_observer() {
+        use std::sync::{Arc, Mutex};
+
+        let observed = Arc::new(Mutex::new(vec![]));
+        let recorder = observed.clone();
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_miss_observer(move |key, hit| recorder.lock().unwrap().push((key.to_string(), hit)))
+            .build()
+            .unwrap();
+        client.set("miss_observer_hit", "foo", 0).unwrap();
+        let _: Option<String> = client.get("miss_observer_hit").unwrap();
+        let _: Option<String> = client.get("miss_observer_miss").unwrap();
+        let _: std::collections::HashMap<String, String> =
+            client.gets(&["miss_observer_miss", "miss_observer_hit"]).unwrap();
+        assert_eq!(
+            *observed.lock().unwrap(),
+            vec![
+                ("miss_observer_hit".to_string(), true),
+                ("miss_observer_miss".to_string(), false),
+                ("miss_observer_miss".to_string(), false),
+                ("miss_observer_hit".to_string(), true),
+            ]
+        );
+        client.delete("miss_observer_hit").unwrap();
+    }
+
+    #[test]
+    fn display_client() {
+        fn custom_hash_function(_key: &str) -> u64 {
+            0
+        }
+
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .add_server("memcache:///tmp/memcached.sock")
+            .unwrap()
+            .with_max_pool_size(2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.to_string(),
+            r#"MemcacheClient { servers: ["localhost:12345", "/tmp/memcached.sock"], pool_size: 2, protocol: binary, hash_function: default }"#
+        );
+
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345?protocol=ascii")
+            .unwrap()
+            .with_named_hash_function("zero", custom_hash_function)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.to_string(),
+            r#"MemcacheClient { servers: ["localhost:12345"], pool_size: 1, protocol: ascii, hash_function: zero }"#
+        );
+
+        let client = super::Client::builder()
+            .add_server(vec![
+                "memcache://localhost:12345?protocol=ascii",
+                "memcache://localhost:12346",
+            ])
+            .unwrap()
+            .with_hash_function(custom_hash_function)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.to_string(),
+            r#"MemcacheClient { servers: ["localhost:12345", "localhost:12346"], pool_size: 1, protocol: mixed, hash_function: custom }"#
+        );
+
+        let client = super::Client::with_pool_size("memcache://localhost:12345", 3).unwrap();
+        assert_eq!(
+            client.to_string(),
+            r#"MemcacheClient { servers: ["localhost:12345"], pool_size: 3, protocol: binary, hash_function: default }"#
+        );
+
+        // a pool without idle connections still shows its server
+        let url = Url::parse("memcache://localhost:12345?protocol=ascii").unwrap();
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .min_idle(Some(0))
+            .build(super::ConnectionManager::new(url))
+            .unwrap();
+        let client = super::Client::with_pool(pool).unwrap();
+        assert_eq!(
+            client.to_string(),
+            r#"MemcacheClient { servers: ["localhost:12345"], pool_size: 1, protocol: ascii, hash_function: default }"#
+        );
+    }
+
+    #[test]
+    fn build_client_with_sharded_pool() {
+        let client = super::Client::builder()
+            .add_server("memcache://localhost:12345")
+            .unwrap()
+            .with_sharded_pool(4)
+            .build()
+            .unwrap();
+        assert_eq!(client.servers().pools[0].shards().len(), 4);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let client = client.clone();
+                std::thread::spawn(move || {
+                    let key = format!("sharded_pool_{}", i);
+                    client.set(&key, i, 0).unwrap();
+                    let value: Option<u32> = client.get(&key).unwrap();
+                    assert_eq!(value, Some(i));
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn stream_addrs() {
+        let client = super::Client::connect("memcache://127.0.0.1:12345").unwrap();
+        let (peer_addr, local_addr) = client
+            .with_connection_for_key("foo", |protocol| {
+                Ok((protocol.stream().peer_addr(), protocol.stream().local_addr()))
+            })
+            .unwrap();
+        assert_eq!(peer_addr, Some("127.0.0.1:12345".parse().unwrap()));
+        assert!(local_addr.is_some());
+
+        #[cfg(unix)]
+        {
+            let client = super::Client::connect("memcache:///tmp/memcached.sock").unwrap();
+            let peer_addr = client
+                .with_connection_for_key("foo", |protocol| Ok(protocol.stream().peer_addr()))
+                .unwrap();
+            assert_eq!(peer_addr, None);
+        }
+    }
+
     #[cfg(unix)]
     #[test]
     fn unix() {
@@ -1,24 +1,35 @@
-use std::net::TcpStream;
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::{SocketAddr, TcpStream};
 use std::ops::{Deref, DerefMut};
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 use url::Url;
 
 use crate::error::MemcacheError;
+#[cfg(feature = "test-mode")]
+use crate::mock::MockBackend;
 
-use crate::protocol::{AsciiProtocol, BinaryProtocol, Protocol, ProtocolTrait};
+#[cfg(feature = "test-mode")]
+use crate::protocol::MockProtocol;
+use crate::protocol::{Protocol, ProtocolTrait};
 use crate::stream::Stream;
 use crate::stream::UdpStream;
 #[cfg(feature = "tls")]
-use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
-use r2d2::ManageConnection;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode, SslVersion};
+use r2d2::{ManageConnection, Pool, PooledConnection};
 
 /// A connection to the memcached server
 pub struct Connection {
     pub protocol: Protocol,
-    pub url: Arc<String>,
+    pub url: Arc<Url>,
+    // set after an I/O error, which may leave a partial response on the stream, to discard the connection
+    pub(crate) broken: bool,
 }
 
 impl DerefMut for Connection {
@@ -34,40 +45,277 @@ impl Deref for Connection {
     }
 }
 
+pub(crate) type ConnectionCallback = Arc<dyn Fn(&str) + Send + Sync>;
+pub(crate) type ErrorCallback = Arc<dyn Fn(&str, &MemcacheError) + Send + Sync>;
+pub(crate) type ProtocolFactory = Arc<dyn Fn(Stream) -> Protocol + Send + Sync>;
+
+/// The callbacks fired on the lifecycle events of a server's connections, with the server's name.
+#[derive(Clone, Default)]
+pub(crate) struct ConnectionCallbacks {
+    pub(crate) on_connect: Option<ConnectionCallback>,
+    pub(crate) on_disconnect: Option<ConnectionCallback>,
+    pub(crate) on_error: Option<ErrorCallback>,
+}
+
 /// Memcache connection manager implementing rd2d Pool ManageConnection
 pub struct ConnectionManager {
     url: Url,
+    #[cfg(feature = "test-mode")]
+    mock: Option<Arc<dyn MockBackend>>,
+    callbacks: ConnectionCallbacks,
+    protocol_factory: Option<ProtocolFactory>,
+    pool_timeout: Option<Duration>,
 }
 
 impl ConnectionManager {
     /// Initialize connection manager with given Url
     pub fn new(url: Url) -> Self {
-        Self { url }
+        Self {
+            url,
+            #[cfg(feature = "test-mode")]
+            mock: None,
+            callbacks: ConnectionCallbacks::default(),
+            protocol_factory: None,
+            pool_timeout: None,
+        }
     }
-}
 
-impl ManageConnection for ConnectionManager {
-    type Connection = Connection;
-    type Error = MemcacheError;
+    /// Initialize connection manager with given Url and timeouts, instead of setting them with URL parameters.
+    /// `tcp_timeout` is the read and write timeout of the connections, replacing the URL's `timeout` parameter.
+    /// `pool_timeout` is how long to wait for a connection from the pool, which is set on the builder returned by
+    /// `pool_builder`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use memcache::{ConnectionManager, Url};
+    /// use std::time::Duration;
+    ///
+    /// let url = Url::parse("memcache://localhost:12345").unwrap();
+    /// let manager = ConnectionManager::new_with_options(url, Some(Duration::from_secs(1)), Some(Duration::from_secs(2)));
+    /// let pool = manager.pool_builder().max_size(4).build(manager).unwrap();
+    /// assert_eq!(pool.connection_timeout(), Duration::from_secs(2));
+    /// let client = memcache::Client::with_pool(pool).unwrap();
+    /// client.version().unwrap();
+    /// ```
+    pub fn new_with_options(mut url: Url, tcp_timeout: Option<Duration>, pool_timeout: Option<Duration>) -> Self {
+        if let Some(tcp_timeout) = tcp_timeout {
+            let params: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(k, _)| k != "timeout")
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(params)
+                .append_pair("timeout", &tcp_timeout.as_secs_f64().to_string());
+        }
+        Self {
+            pool_timeout,
+            ..Self::new(url)
+        }
+    }
 
-    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+    /// A connection pool builder for this manager, with the pool timeout given to `new_with_options`, if any.
+    pub fn pool_builder(&self) -> r2d2::Builder<ConnectionManager> {
+        let builder = Pool::builder();
+        match self.pool_timeout {
+            Some(timeout) => builder.connection_timeout(timeout),
+            None => builder,
+        }
+    }
+
+    /// A connection manager whose connections run the commands on `backend`, without connecting to `url`.
+    #[cfg(feature = "test-mode")]
+    pub(crate) fn mock(url: Url, backend: Arc<dyn MockBackend>) -> Self {
+        Self {
+            mock: Some(backend),
+            ..Self::new(url)
+        }
+    }
+
+    pub(crate) fn with_callbacks(mut self, callbacks: ConnectionCallbacks) -> Self {
+        self.callbacks = callbacks;
+        self
+    }
+
+    pub(crate) fn with_protocol_factory(mut self, protocol_factory: Option<ProtocolFactory>) -> Self {
+        self.protocol_factory = protocol_factory;
+        self
+    }
+
+    fn open(&self) -> Result<Connection, MemcacheError> {
         let url = &self.url;
-        let mut connection = Connection::connect(url)?;
+        #[cfg(feature = "test-mode")]
+        if let Some(ref backend) = self.mock {
+            return Ok(Connection {
+                url: Arc::new(url.clone()),
+                protocol: Protocol::Mock(MockProtocol::new(backend.clone())),
+                broken: false,
+            });
+        }
+        let mut connection = Connection::connect(url, self.protocol_factory.as_ref())?;
         if url.has_authority() && !url.username().is_empty() && url.password().is_some() {
             let username = url.username();
             let password = url.password().unwrap();
+            #[cfg(feature = "sasl-scram")]
+            {
+                if let Protocol::Binary(ref mut protocol) = connection.protocol {
+                    if protocol
+                        .list_sasl_mechanisms()?
+                        .iter()
+                        .any(|mechanism| mechanism == "SCRAM-SHA-1")
+                    {
+                        protocol.auth_scram(username, password)?;
+                        return Ok(connection);
+                    }
+                }
+            }
             connection.auth(username, password)?;
         }
         Ok(connection)
     }
 
+    fn fire_disconnect(&self) {
+        if let Some(ref callback) = self.callbacks.on_disconnect {
+            callback(&server_name(&self.url));
+        }
+    }
+
+    fn fire_error(&self, error: &MemcacheError) {
+        if let Some(ref callback) = self.callbacks.on_error {
+            callback(&server_name(&self.url), error);
+        }
+    }
+}
+
+/// The server of `url` as `host:port`, or the socket path for UNIX domain sockets, for logs and metrics.
+pub(crate) fn server_name(url: &Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => url.path().to_string(),
+    }
+}
+
+/// The connection pools of a single memcached server. Each thread always takes its connections from the same pool,
+/// so with several pools, threads running on different cores rarely contend on the same pool's lock.
+#[derive(Clone)]
+pub(crate) struct ShardedPool {
+    shards: Vec<Pool<ConnectionManager>>,
+    url: Url,
+}
+
+impl ShardedPool {
+    pub(crate) fn new(url: Url, shards: Vec<Pool<ConnectionManager>>) -> Self {
+        Self { shards, url }
+    }
+
+    /// Get a connection from the pool of the current thread.
+    pub(crate) fn get(&self) -> Result<PooledConnection<ConnectionManager>, r2d2::Error> {
+        self.get_connection_from_shard().get()
+    }
+
+    /// Get an idle connection from the pool of the current thread, without waiting for one.
+    pub(crate) fn try_get(&self) -> Option<PooledConnection<ConnectionManager>> {
+        self.get_connection_from_shard().try_get()
+    }
+
+    fn get_connection_from_shard(&self) -> &Pool<ConnectionManager> {
+        if self.shards.len() == 1 {
+            return &self.shards[0];
+        }
+        let mut hasher = DefaultHasher::new();
+        thread::current().id().hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    pub(crate) fn shards(&self) -> &[Pool<ConnectionManager>] {
+        &self.shards
+    }
+
+    /// The URL of the server.
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The server as `host:port`, or the socket path for UNIX domain sockets, for logs and metrics.
+    pub(crate) fn server_name(&self) -> String {
+        server_name(&self.url)
+    }
+
+    /// The maximum number of connections of each shard.
+    pub(crate) fn max_size(&self) -> u32 {
+        self.shards[0].max_size()
+    }
+
+    /// Set the socket read timeout of a connection of each shard, see `Client::set_read_timeout`.
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), MemcacheError> {
+        for shard in self.shards.iter() {
+            shard.get()?.protocol.stream().set_read_timeout(timeout)?;
+        }
+        Ok(())
+    }
+
+    /// Set the socket write timeout of a connection of each shard, see `Client::set_write_timeout`.
+    pub(crate) fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<(), MemcacheError> {
+        for shard in self.shards.iter() {
+            shard.get()?.protocol.stream().set_write_timeout(timeout)?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The number of connections which failed the check of `ConnectionManager::is_valid` on this thread, which the
+    /// pools run on the thread checking the connections out.
+    static INVALID_CONNECTIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The number of connections which failed the check on checkout on the current thread, and were discarded.
+pub(crate) fn invalid_connections() -> usize {
+    INVALID_CONNECTIONS.with(Cell::get)
+}
+
+impl ManageConnection for ConnectionManager {
+    type Connection = Connection;
+    type Error = MemcacheError;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let result = self.open();
+        match result {
+            Ok(_) => {
+                if let Some(ref callback) = self.callbacks.on_connect {
+                    callback(&server_name(&self.url));
+                }
+            }
+            Err(ref e) => self.fire_error(e),
+        }
+        result
+    }
+
     fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
-        conn.version().map(|_| ())
+        let result = match conn.protocol {
+            // a noop response is a bare header, while version responses carry the version string
+            Protocol::Binary(ref mut protocol) => protocol.noop(),
+            Protocol::Ascii(_) => conn.version().map(|_| ()),
+            #[cfg(feature = "test-mode")]
+            Protocol::Mock(_) => Ok(()),
+        };
+        // the pool discards the connections failing the check
+        if let Err(ref e) = result {
+            INVALID_CONNECTIONS.with(|count| count.set(count.get() + 1));
+            self.fire_error(e);
+            self.fire_disconnect();
+        }
+        result
     }
 
-    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
-        // TODO: fix this
-        false
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        if conn.broken {
+            self.fire_disconnect();
+        }
+        conn.broken
     }
 }
 
@@ -76,21 +324,55 @@ enum Transport {
     Udp,
     #[cfg(unix)]
     Unix,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    UnixAbstract,
     #[cfg(feature = "tls")]
-    Tls(TlsOptions),
+    Tls(TlsConnectOptions),
 }
 
 #[cfg(feature = "tls")]
-struct TlsOptions {
+struct TlsConnectOptions {
     tcp_options: TcpOptions,
     ca_path: Option<String>,
     key_path: Option<String>,
     cert_path: Option<String>,
     verify_mode: SslVerifyMode,
+    min_version: Option<SslVersion>,
+    cipher_list: Option<String>,
+}
+
+/// TLS settings of the `memcache+tls` servers of a client, set with `ClientBuilder::with_tls_options`. The parameters
+/// of a server URL take precedence over them.
+#[cfg(feature = "tls")]
+#[derive(Clone, Debug, Default)]
+pub struct TlsOptions {
+    skip_verify: bool,
+}
+
+#[cfg(feature = "tls")]
+impl TlsOptions {
+    /// TLS settings that don't verify the certificates of the servers, like the `verify_mode=none` URL parameter.
+    ///
+    /// This is dangerous: anyone on the network path can then impersonate the servers, and read or alter the cached
+    /// data. Only use it in development or tests, e.g. with self-signed certificates.
+    pub fn insecure_skip_verify() -> Self {
+        TlsOptions { skip_verify: true }
+    }
+
+    /// Add these settings to the parameters of a `memcache+tls` URL that doesn't already set them.
+    pub(crate) fn apply(&self, url: &mut Url) {
+        if url.scheme() != "memcache+tls" {
+            return;
+        }
+        if self.skip_verify && !url.query_pairs().any(|(k, _)| k == "verify_mode") {
+            url.query_pairs_mut().append_pair("verify_mode", "none");
+        }
+    }
 }
 
 struct TcpOptions {
     timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
     nodelay: bool,
 }
 
@@ -103,7 +385,7 @@ fn get_param(url: &Url, key: &str) -> Option<String> {
 }
 
 #[cfg(feature = "tls")]
-impl TlsOptions {
+impl TlsConnectOptions {
     fn from_url(url: &Url) -> Result<Self, MemcacheError> {
         let verify_mode = match get_param(url, "verify_mode").as_ref().map(String::as_str) {
             Some("none") => SslVerifyMode::NONE,
@@ -116,6 +398,19 @@ impl TlsOptions {
             None => SslVerifyMode::PEER,
         };
 
+        let min_version = match get_param(url, "tls_min_version").as_deref() {
+            Some("TLSv1") => Some(SslVersion::TLS1),
+            Some("TLSv1.1") => Some(SslVersion::TLS1_1),
+            Some("TLSv1.2") => Some(SslVersion::TLS1_2),
+            Some("TLSv1.3") => Some(SslVersion::TLS1_3),
+            Some(_) => {
+                return Err(MemcacheError::BadURL(
+                    "unknown tls_min_version, expected 'TLSv1', 'TLSv1.1', 'TLSv1.2' or 'TLSv1.3'".into(),
+                ))
+            }
+            None => None,
+        };
+
         let ca_path = get_param(url, "ca_path");
         let key_path = get_param(url, "key_path");
         let cert_path = get_param(url, "cert_path");
@@ -130,30 +425,46 @@ impl TlsOptions {
             ));
         }
 
-        Ok(TlsOptions {
-            tcp_options: TcpOptions::from_url(url),
+        Ok(TlsConnectOptions {
+            tcp_options: TcpOptions::from_url(url)?,
             ca_path: ca_path,
             key_path: key_path,
             cert_path: cert_path,
             verify_mode: verify_mode,
+            min_version,
+            cipher_list: get_param(url, "tls_cipher_list"),
         })
     }
 }
 
 impl TcpOptions {
-    fn from_url(url: &Url) -> Self {
-        let nodelay = !url
-            .query_pairs()
-            .any(|(ref k, ref v)| k == "tcp_nodelay" && v == "false");
+    fn from_url(url: &Url) -> Result<Self, MemcacheError> {
+        // Nagle's algorithm delays small requests, so it's disabled by default
+        let nodelay = match url.query_pairs().find(|(k, _)| k == "tcp_nodelay") {
+            Some((_, v)) if v == "true" => true,
+            Some((_, v)) if v == "false" => false,
+            Some(_) => {
+                return Err(MemcacheError::BadURL(
+                    "unknown tcp_nodelay, expected 'true' or 'false'".into(),
+                ))
+            }
+            None => true,
+        };
         let timeout = url
             .query_pairs()
             .find(|&(ref k, ref _v)| k == "timeout")
             .and_then(|(ref _k, ref v)| v.parse::<f64>().ok())
             .map(Duration::from_secs_f64);
-        TcpOptions {
+        let connect_timeout = url
+            .query_pairs()
+            .find(|(k, _)| k == "connect_timeout")
+            .and_then(|(_, v)| v.parse::<f64>().ok())
+            .map(Duration::from_secs_f64);
+        Ok(TcpOptions {
             nodelay: nodelay,
             timeout: timeout,
-        }
+            connect_timeout,
+        })
     }
 }
 
@@ -172,12 +483,16 @@ impl Transport {
         // scheme has highest priority
         if let Some(proto) = parts.next() {
             return match proto {
-                "tcp" => Ok(Transport::Tcp(TcpOptions::from_url(url))),
+                "tcp" => Ok(Transport::Tcp(TcpOptions::from_url(url)?)),
                 "udp" => Ok(Transport::Udp),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                "unix" if is_abstract(url) => Ok(Transport::UnixAbstract),
                 #[cfg(unix)]
                 "unix" => Ok(Transport::Unix),
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                "unix-abstract" => Ok(Transport::UnixAbstract),
                 #[cfg(feature = "tls")]
-                "tls" => Ok(Transport::Tls(TlsOptions::from_url(url)?)),
+                "tls" => Ok(Transport::Tls(TlsConnectOptions::from_url(url)?)),
                 _ => Err(MemcacheError::BadURL(
                     "memcache URL's scheme should be 'memcache+tcp' or 'memcache+udp' or 'memcache+unix' or 'memcache+tls'".into(),
                 )),
@@ -192,16 +507,60 @@ impl Transport {
         #[cfg(unix)]
         {
             if url.host().is_none() && url.port() == None {
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                {
+                    if is_abstract(url) {
+                        return Ok(Transport::UnixAbstract);
+                    }
+                }
                 return Ok(Transport::Unix);
             }
         }
 
-        Ok(Transport::Tcp(TcpOptions::from_url(url)))
+        Ok(Transport::Tcp(TcpOptions::from_url(url)?))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_abstract(url: &Url) -> bool {
+    url.query_pairs().any(|(ref k, ref v)| k == "abstract" && v == "true")
+}
+
+/// Connect to a socket in the Linux abstract namespace. The name is the URL's path, with the leading `/@` stripped
+/// if present, so both `memcache+unix-abstract:///@memcached` and `memcache:///memcached?abstract=true` work.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unix_abstract_stream(url: &Url) -> Result<UnixStream, MemcacheError> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let path = url.path();
+    let name = path.strip_prefix("/@").unwrap_or(path);
+    let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+    Ok(UnixStream::connect_addr(&addr)?)
+}
+
+/// Connect to the first of `addrs` accepting the connection, in order, e.g. to the addresses of a host name with several
+/// A records. Each address is given `timeout`, so an unreachable address doesn't use up the time of the others.
+fn connect_any(addrs: &[SocketAddr], timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in addrs {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(addr, timeout),
+            None => TcpStream::connect(addr),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
     }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")))
 }
 
 fn tcp_stream(url: &Url, opts: &TcpOptions) -> Result<TcpStream, MemcacheError> {
-    let tcp_stream = TcpStream::connect(&*url.socket_addrs(|| None)?)?;
+    let tcp_stream = connect_any(&url.socket_addrs(|| None)?, opts.connect_timeout)?;
     if opts.timeout.is_some() {
         tcp_stream.set_read_timeout(opts.timeout)?;
         tcp_stream.set_write_timeout(opts.timeout)?;
@@ -211,11 +570,14 @@ fn tcp_stream(url: &Url, opts: &TcpOptions) -> Result<TcpStream, MemcacheError>
 }
 
 impl Connection {
-    pub(crate) fn get_url(&self) -> String {
-        self.url.to_string()
+    /// The URL this connection was created from.
+    pub fn get_url(&self) -> Url {
+        Url::clone(&self.url)
     }
 
-    pub(crate) fn connect(url: &Url) -> Result<Self, MemcacheError> {
+    /// Connect to `url`, using the protocol built by `protocol_factory` if any, or the one of the `protocol` URL
+    /// parameter.
+    pub(crate) fn connect(url: &Url, protocol_factory: Option<&ProtocolFactory>) -> Result<Self, MemcacheError> {
         let transport = Transport::from_url(url)?;
         let is_ascii = url.query_pairs().any(|(ref k, ref v)| k == "protocol" && v == "ascii");
         let stream: Stream = match transport {
@@ -223,6 +585,8 @@ impl Connection {
             Transport::Udp => Stream::Udp(UdpStream::new(url)?),
             #[cfg(unix)]
             Transport::Unix => Stream::Unix(UnixStream::connect(url.path())?),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Transport::UnixAbstract => Stream::Unix(unix_abstract_stream(url)?),
             #[cfg(feature = "tls")]
             Transport::Tls(options) => {
                 let host = url
@@ -231,6 +595,11 @@ impl Connection {
 
                 let mut builder = SslConnector::builder(SslMethod::tls())?;
                 builder.set_verify(options.verify_mode);
+                builder.set_min_proto_version(options.min_version)?;
+
+                if let Some(cipher_list) = &options.cipher_list {
+                    builder.set_cipher_list(cipher_list)?;
+                }
 
                 if options.ca_path.is_some() {
                     builder.set_ca_file(&options.ca_path.unwrap())?;
@@ -251,15 +620,16 @@ impl Connection {
             }
         };
 
-        let protocol = if is_ascii {
-            Protocol::Ascii(AsciiProtocol::new(stream))
-        } else {
-            Protocol::Binary(BinaryProtocol { stream: stream })
+        let protocol = match protocol_factory {
+            Some(factory) => factory(stream),
+            None if is_ascii => Protocol::ascii(stream),
+            None => Protocol::binary(stream),
         };
 
         Ok(Connection {
-            url: Arc::new(url.to_string()),
+            url: Arc::new(url.clone()),
             protocol: protocol,
+            broken: false,
         })
     }
 }
@@ -276,4 +646,163 @@ mod tests {
             _ => assert!(false, "transport is not unix"),
         }
     }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_tls_url() {
+        use super::Transport;
+        use openssl::ssl::SslVersion;
+        use url::Url;
+        let url = "memcache+tls://localhost:12345?tls_min_version=TLSv1.2&tls_cipher_list=ECDHE-RSA-AES128-GCM-SHA256";
+        match Transport::from_url(&Url::parse(url).unwrap()).unwrap() {
+            Transport::Tls(options) => {
+                assert_eq!(options.min_version, Some(SslVersion::TLS1_2));
+                assert_eq!(options.cipher_list.as_deref(), Some("ECDHE-RSA-AES128-GCM-SHA256"));
+            }
+            _ => panic!("transport is not tls"),
+        }
+        let url = Url::parse("memcache+tls://localhost:12345?tls_min_version=SSLv3").unwrap();
+        assert!(Transport::from_url(&url).is_err());
+    }
+
+    #[test]
+    fn test_is_valid() {
+        use super::ConnectionManager;
+        use r2d2::ManageConnection;
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use url::Url;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 24];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(&request[..2], &[0x80, 0x0a]);
+            // a failed AppendQ sent before is skipped
+            let mut response = [0u8; 24];
+            response[..2].copy_from_slice(&[0x81, 0x19]);
+            response[7] = 0x05;
+            stream.write_all(&response).unwrap();
+            let mut response = [0u8; 24];
+            response[..2].copy_from_slice(&[0x81, 0x0a]);
+            stream.write_all(&response).unwrap();
+            stream.read_exact(&mut request).unwrap();
+            response[0] = 0x80;
+            stream.write_all(&response).unwrap();
+        });
+
+        let manager = ConnectionManager::new(Url::parse(&format!("memcache://127.0.0.1:{}", port)).unwrap());
+        let mut connection = manager.connect().unwrap();
+        assert!(manager.is_valid(&mut connection).is_ok());
+        assert!(manager.is_valid(&mut connection).is_err());
+        server.join().unwrap();
+
+        let manager = ConnectionManager::new(Url::parse("memcache://localhost:12345?protocol=ascii").unwrap());
+        let mut connection = manager.connect().unwrap();
+        assert!(manager.is_valid(&mut connection).is_ok());
+    }
+
+    #[test]
+    fn test_tcp_nodelay_url() {
+        use super::{TcpOptions, Transport};
+        use url::Url;
+
+        for (url, nodelay) in [
+            ("memcache://localhost:12345", true),
+            ("memcache://localhost:12345?tcp_nodelay=true", true),
+            ("memcache://localhost:12345?tcp_nodelay=false", false),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                TcpOptions::from_url(&Url::parse(url).unwrap()).unwrap().nodelay,
+                *nodelay
+            );
+        }
+        assert!(Transport::from_url(&Url::parse("memcache://localhost:12345?tcp_nodelay=1").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_new_with_options() {
+        use super::{ConnectionManager, TcpOptions};
+        use std::time::Duration;
+        use url::Url;
+
+        let url = Url::parse("memcache://localhost:12345?timeout=10&protocol=ascii").unwrap();
+        let manager = ConnectionManager::new_with_options(url, Some(Duration::from_millis(1500)), None);
+        assert_eq!(
+            manager.url.as_str(),
+            "memcache://localhost:12345?protocol=ascii&timeout=1.5"
+        );
+        let options = TcpOptions::from_url(&manager.url).unwrap();
+        assert_eq!(options.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(
+            manager.pool_builder().build_unchecked(manager).connection_timeout(),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_connect_any() {
+        use super::{connect_any, TcpOptions};
+        use std::net::TcpListener;
+        use std::time::Duration;
+        use url::Url;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let addrs = [closed, listener.local_addr().unwrap()];
+        for timeout in [None, Some(Duration::from_secs(1))].iter() {
+            let stream = connect_any(&addrs, *timeout).unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), addrs[1]);
+        }
+        assert!(connect_any(&addrs[..1], None).is_err());
+        assert!(connect_any(&[], None).is_err());
+
+        let url = Url::parse("memcache://localhost:12345?connect_timeout=0.5").unwrap();
+        assert_eq!(
+            TcpOptions::from_url(&url).unwrap().connect_timeout,
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unix_abstract() {
+        use super::{Connection, Transport};
+        use crate::protocol::ProtocolTrait;
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixListener};
+        use url::Url;
+
+        for url in [
+            "memcache+unix-abstract:///@memcache-test",
+            "memcache:///memcache-test?abstract=true",
+        ]
+        .iter()
+        {
+            match Transport::from_url(&Url::parse(url).unwrap()).unwrap() {
+                Transport::UnixAbstract => (),
+                _ => panic!("{} should be an abstract unix socket", url),
+            }
+        }
+
+        let name = format!("memcache-test-{}", std::process::id());
+        let listener = UnixListener::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes()).unwrap()).unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            assert_eq!(line, "version\r\n");
+            (&stream).write_all(b"VERSION 1.6.26\r\n").unwrap();
+        });
+
+        let url = Url::parse(&format!("memcache+unix-abstract:///@{}?protocol=ascii", name)).unwrap();
+        let mut connection = Connection::connect(&url, None).unwrap();
+        assert_eq!(connection.version().unwrap(), "1.6.26");
+        server.join().unwrap();
+    }
 }
@@ -0,0 +1,208 @@
+use std::io;
+use std::sync::{Arc, PoisonError, RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+use trust_dns_resolver::Resolver;
+use url::Url;
+
+use crate::client::Servers;
+use crate::connection::ShardedPool;
+use crate::error::MemcacheError;
+
+type PoolBuilder = dyn Fn(&str) -> Result<ShardedPool, MemcacheError> + Send + Sync;
+
+/// Resolve a DNS SRV record into memcache server URLs.
+pub(crate) fn resolve_srv(srv_name: &str) -> Result<Vec<String>, MemcacheError> {
+    let resolver = Resolver::from_system_conf()?;
+    let lookup = resolver.srv_lookup(srv_name).map_err(io::Error::from)?;
+    srv_urls(lookup.iter().map(|srv| (srv.target().to_utf8(), srv.port())))
+}
+
+/// The memcache server URLs of SRV targets, as `(host, port)`. The URLs are sorted, so a unchanged record always
+/// produces the same server list, and keys keep being hashed to the same servers.
+fn srv_urls(targets: impl IntoIterator<Item = (String, u16)>) -> Result<Vec<String>, MemcacheError> {
+    let mut urls = vec![];
+    for (host, port) in targets {
+        let url = Url::parse(&format!("memcache://{}:{}", host.trim_end_matches('.'), port))?;
+        urls.push(url.to_string());
+    }
+    urls.sort();
+    urls.dedup();
+    Ok(urls)
+}
+
+fn normalize(urls: &[String]) -> Result<Vec<String>, MemcacheError> {
+    let mut normalized = Vec::with_capacity(urls.len());
+    for url in urls {
+        normalized.push(Url::parse(url)?.to_string());
+    }
+    Ok(normalized)
+}
+
+/// Server discovery state of a client built with `ClientBuilder::with_srv_discovery`, owned by its refresh thread.
+pub(crate) struct SrvDiscovery {
+    srv_name: String,
+    refresh_interval: Duration,
+    static_urls: Vec<String>,
+    urls: Vec<String>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    build_pool: Box<PoolBuilder>,
+}
+
+impl SrvDiscovery {
+    pub(crate) fn new<F>(
+        srv_name: String,
+        refresh_interval: Duration,
+        static_urls: Vec<String>,
+        urls: &[String],
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+        build_pool: F,
+    ) -> Result<Self, MemcacheError>
+    where
+        F: Fn(&str) -> Result<ShardedPool, MemcacheError> + Send + Sync + 'static,
+    {
+        Ok(SrvDiscovery {
+            srv_name,
+            refresh_interval,
+            static_urls,
+            urls: normalize(urls)?,
+            read_timeout,
+            write_timeout,
+            build_pool: Box::new(build_pool),
+        })
+    }
+
+    /// Refresh `servers` every refresh interval from a background thread, until the client and its clones holding
+    /// them are dropped.
+    pub(crate) fn spawn(mut self, servers: Weak<RwLock<Arc<Servers>>>) {
+        thread::spawn(move || loop {
+            thread::sleep(self.refresh_interval);
+            let servers = match servers.upgrade() {
+                Some(servers) => servers,
+                None => return,
+            };
+            // a failed resolution keeps the current servers, and is retried at the next refresh
+            let _ = self.refresh(&servers);
+        });
+    }
+
+    /// Resolve the SRV record again, and replace the servers if the full server list (the statically added servers
+    /// followed by the discovered ones) changed.
+    fn refresh(&mut self, servers: &RwLock<Arc<Servers>>) -> Result<bool, MemcacheError> {
+        let mut urls = normalize(&self.static_urls)?;
+        urls.extend(resolve_srv(&self.srv_name)?);
+        self.replace(servers, urls)
+    }
+
+    /// Replace the servers with the pools of `urls`, keeping the current pools of the servers still present, and
+    /// return whether they changed.
+    fn replace(&mut self, servers: &RwLock<Arc<Servers>>, urls: Vec<String>) -> Result<bool, MemcacheError> {
+        if urls == self.urls {
+            return Ok(false);
+        }
+        let current = Arc::clone(&servers.read().unwrap_or_else(PoisonError::into_inner));
+        let mut pools = Vec::with_capacity(urls.len());
+        for url in &urls {
+            match self.urls.iter().position(|current_url| current_url == url) {
+                Some(index) => pools.push(current.pools[index].clone()),
+                None => {
+                    let pool = (self.build_pool)(url)?;
+                    pool.set_read_timeout(self.read_timeout)?;
+                    pool.set_write_timeout(self.write_timeout)?;
+                    pools.push(pool);
+                }
+            }
+        }
+        let replacement = Arc::new(current.replace_pools(pools));
+        *servers.write().unwrap_or_else(PoisonError::into_inner) = replacement;
+        self.urls = urls;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{srv_urls, SrvDiscovery};
+    use crate::client::Servers;
+    use crate::connection::{ConnectionManager, ShardedPool};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+    use url::Url;
+
+    fn build_pool(url: &str) -> ShardedPool {
+        let url = Url::parse(url).unwrap();
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .min_idle(Some(0))
+            .build(ConnectionManager::new(url.clone()))
+            .unwrap();
+        ShardedPool::new(url, vec![pool])
+    }
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn srv_urls_are_sorted_without_duplicates() {
+        let targets = vec![
+            ("b.example.com.".to_string(), 11211),
+            ("a.example.com.".to_string(), 11212),
+            ("b.example.com".to_string(), 11211),
+            ("a.example.com".to_string(), 11211),
+        ];
+        assert_eq!(
+            srv_urls(targets).unwrap(),
+            urls(&[
+                "memcache://a.example.com:11211",
+                "memcache://a.example.com:11212",
+                "memcache://b.example.com:11211",
+            ])
+        );
+        assert_eq!(srv_urls(vec![]).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn replace_keeps_the_pools_of_remaining_servers() {
+        let a = "memcache://localhost:12345";
+        let b = "memcache://localhost:12346";
+        let c = "memcache://localhost:12347";
+        let built = Arc::new(AtomicUsize::new(0));
+        let counter = built.clone();
+        let mut discovery = SrvDiscovery::new(
+            "_memcache._tcp.example.com".to_string(),
+            Duration::from_secs(60),
+            vec![],
+            &urls(&[a, b]),
+            None,
+            None,
+            move |url: &str| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(build_pool(url))
+            },
+        )
+        .unwrap();
+        #[cfg(feature = "ketama")]
+        let servers = RwLock::new(Arc::new(Servers::with_ketama_ring(vec![build_pool(a), build_pool(b)])));
+        #[cfg(not(feature = "ketama"))]
+        let servers = RwLock::new(Arc::new(Servers::new(vec![build_pool(a), build_pool(b)])));
+
+        assert!(!discovery.replace(&servers, urls(&[a, b])).unwrap());
+        assert_eq!(built.load(Ordering::SeqCst), 0);
+
+        assert!(discovery.replace(&servers, urls(&[b, c])).unwrap());
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        let current = servers.read().unwrap().clone();
+        let replaced: Vec<String> = current.pools.iter().map(|pool| pool.url().to_string()).collect();
+        assert_eq!(replaced, urls(&[b, c]));
+        #[cfg(feature = "ketama")]
+        assert!(current.ketama_ring.is_some());
+
+        assert!(!discovery.replace(&servers, urls(&[b, c])).unwrap());
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+    }
+}
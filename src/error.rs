@@ -5,12 +5,15 @@ use std::io;
 use std::num;
 use std::str;
 use std::string;
+use std::sync;
 
 /// Client-side errors
 #[derive(Debug, PartialEq)]
 pub enum ClientError {
-    /// The key provided was longer than 250 bytes.
+    /// The key provided was longer than 250 bytes, or the length set with `ClientBuilder::with_max_key_length`.
     KeyTooLong,
+    /// The key provided contained whitespace or control characters, which memcached doesn't accept.
+    KeyInvalidCharacters,
     /// The server returned an error prefixed with CLIENT_ERROR in response to a command.
     Error(Cow<'static, str>),
 }
@@ -19,6 +22,9 @@ impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ClientError::KeyTooLong => write!(f, "The provided key was too long."),
+            ClientError::KeyInvalidCharacters => {
+                write!(f, "The provided key contained whitespace or control characters.")
+            }
             ClientError::Error(s) => write!(f, "{}", s),
         }
     }
@@ -237,6 +243,10 @@ pub enum MemcacheError {
     ParseError(ParseError),
     /// ConnectionPool errors
     PoolError(r2d2::Error),
+    /// A lock was poisoned by a thread which panicked while holding it
+    LockPoisoned(String),
+    /// No connection was available in the pool, returned by the methods which don't wait for one
+    PoolExhausted,
 }
 
 impl fmt::Display for MemcacheError {
@@ -251,10 +261,46 @@ impl fmt::Display for MemcacheError {
             MemcacheError::ServerError(ref err) => err.fmt(f),
             MemcacheError::CommandError(ref err) => err.fmt(f),
             MemcacheError::PoolError(ref err) => err.fmt(f),
+            MemcacheError::LockPoisoned(ref s) => s.fmt(f),
+            MemcacheError::PoolExhausted => write!(f, "No connection available in the pool."),
         }
     }
 }
 
+impl MemcacheError {
+    /// The category of the error, like `IO` or `Command`, which is the prefix displayed by `with_category`.
+    pub fn category(&self) -> &'static str {
+        match *self {
+            MemcacheError::BadURL(_) => "URL",
+            MemcacheError::IOError(_) => "IO",
+            #[cfg(feature = "tls")]
+            MemcacheError::OpensslError(_) => "TLS",
+            MemcacheError::ParseError(_) => "Parse",
+            MemcacheError::ClientError(_) => "Client",
+            MemcacheError::ServerError(_) => "Server",
+            MemcacheError::CommandError(_) => "Command",
+            MemcacheError::PoolError(_) => "Pool",
+            MemcacheError::LockPoisoned(_) => "Lock",
+            MemcacheError::PoolExhausted => "Pool",
+        }
+    }
+
+    /// Display the error prefixed with its category, like `[Command] Key was not found in the server.`, which makes
+    /// the errors of a category easy to find in logs. The error's own `Display` doesn't have the prefix.
+    pub fn with_category(&self) -> CategorizedError<'_> {
+        CategorizedError(self)
+    }
+}
+
+/// A `MemcacheError` displayed with its category prefix, returned by `MemcacheError::with_category`.
+pub struct CategorizedError<'a>(&'a MemcacheError);
+
+impl<'a> fmt::Display for CategorizedError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.0.category(), self.0)
+    }
+}
+
 impl error::Error for MemcacheError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
@@ -267,6 +313,8 @@ impl error::Error for MemcacheError {
             MemcacheError::ServerError(_) => None,
             MemcacheError::CommandError(_) => None,
             MemcacheError::PoolError(ref p) => p.source(),
+            MemcacheError::LockPoisoned(_) => None,
+            MemcacheError::PoolExhausted => None,
         }
     }
 }
@@ -296,3 +344,212 @@ impl From<r2d2::Error> for MemcacheError {
         MemcacheError::PoolError(err)
     }
 }
+
+impl<T> From<sync::PoisonError<T>> for MemcacheError {
+    fn from(err: sync::PoisonError<T>) -> MemcacheError {
+        MemcacheError::LockPoisoned(err.to_string())
+    }
+}
+
+/// `MemcacheError` is serialized as an object with the error's `type`, its `message`, and for some types its `kind`,
+/// like `{"type": "CommandError", "kind": "KeyNotFound", "message": "Key was not found in the server."}`. I/O errors
+/// have the `os_error` code, if any, and the unknown command errors and bad magic server errors have a `code`.
+///
+/// Errors wrapping types which can't be built back from their message, which are the OpenSSL, parse and connection
+/// pool errors, are deserialized as `ClientError::Error` with their message.
+#[cfg(feature = "serde")]
+mod serialization {
+    use super::{ClientError, CommandError, MemcacheError, ServerError};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::borrow::Cow;
+    use std::io;
+
+    #[derive(Serialize, Deserialize)]
+    struct SerializedError {
+        #[serde(rename = "type")]
+        error_type: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<u16>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        os_error: Option<i32>,
+        message: String,
+    }
+
+    impl SerializedError {
+        fn new(error_type: &str, kind: Option<&str>, message: String) -> Self {
+            SerializedError {
+                error_type: error_type.to_string(),
+                kind: kind.map(str::to_string),
+                code: None,
+                os_error: None,
+                message,
+            }
+        }
+    }
+
+    impl<'a> From<&'a MemcacheError> for SerializedError {
+        fn from(error: &'a MemcacheError) -> Self {
+            let message = error.to_string();
+            match *error {
+                MemcacheError::BadURL(_) => SerializedError::new("BadURL", None, message),
+                MemcacheError::IOError(ref e) => SerializedError {
+                    os_error: e.raw_os_error(),
+                    ..SerializedError::new("IOError", None, message)
+                },
+                MemcacheError::ClientError(ref e) => {
+                    let kind = match e {
+                        ClientError::KeyTooLong => "KeyTooLong",
+                        ClientError::KeyInvalidCharacters => "KeyInvalidCharacters",
+                        ClientError::Error(_) => "Error",
+                    };
+                    SerializedError::new("ClientError", Some(kind), message)
+                }
+                MemcacheError::ServerError(ref e) => match e {
+                    ServerError::BadMagic(magic) => SerializedError {
+                        code: Some((*magic).into()),
+                        ..SerializedError::new("ServerError", Some("BadMagic"), message)
+                    },
+                    ServerError::BadResponse(s) => {
+                        SerializedError::new("ServerError", Some("BadResponse"), s.to_string())
+                    }
+                    ServerError::Error(_) => SerializedError::new("ServerError", Some("Error"), message),
+                },
+                MemcacheError::CommandError(ref e) => {
+                    let kind = match e {
+                        CommandError::KeyExists => "KeyExists",
+                        CommandError::KeyNotFound => "KeyNotFound",
+                        CommandError::ValueTooLarge => "ValueTooLarge",
+                        CommandError::InvalidArguments => "InvalidArguments",
+                        CommandError::AuthenticationRequired => "AuthenticationRequired",
+                        CommandError::Unknown(code) => {
+                            return SerializedError {
+                                code: Some(*code),
+                                ..SerializedError::new("CommandError", Some("Unknown"), message)
+                            }
+                        }
+                        CommandError::InvalidCommand => "InvalidCommand",
+                    };
+                    SerializedError::new("CommandError", Some(kind), message)
+                }
+                #[cfg(feature = "tls")]
+                MemcacheError::OpensslError(_) => SerializedError::new("OpensslError", None, message),
+                MemcacheError::ParseError(_) => SerializedError::new("ParseError", None, message),
+                MemcacheError::PoolError(_) => SerializedError::new("PoolError", None, message),
+                MemcacheError::LockPoisoned(_) => SerializedError::new("LockPoisoned", None, message),
+                MemcacheError::PoolExhausted => SerializedError::new("PoolExhausted", None, message),
+            }
+        }
+    }
+
+    impl From<SerializedError> for MemcacheError {
+        fn from(error: SerializedError) -> Self {
+            let message = error.message;
+            match (error.error_type.as_str(), error.kind.as_deref()) {
+                ("BadURL", _) => MemcacheError::BadURL(message),
+                ("IOError", _) => MemcacheError::IOError(match error.os_error {
+                    Some(code) => io::Error::from_raw_os_error(code),
+                    None => io::Error::new(io::ErrorKind::Other, message),
+                }),
+                ("ClientError", Some("KeyTooLong")) => ClientError::KeyTooLong.into(),
+                ("ClientError", Some("KeyInvalidCharacters")) => ClientError::KeyInvalidCharacters.into(),
+                ("ServerError", Some("BadMagic")) => ServerError::BadMagic(error.code.unwrap_or_default() as u8).into(),
+                ("ServerError", Some("BadResponse")) => ServerError::BadResponse(Cow::Owned(message)).into(),
+                ("ServerError", _) => ServerError::Error(message).into(),
+                ("CommandError", Some("KeyExists")) => CommandError::KeyExists.into(),
+                ("CommandError", Some("KeyNotFound")) => CommandError::KeyNotFound.into(),
+                ("CommandError", Some("ValueTooLarge")) => CommandError::ValueTooLarge.into(),
+                ("CommandError", Some("InvalidArguments")) => CommandError::InvalidArguments.into(),
+                ("CommandError", Some("AuthenticationRequired")) => CommandError::AuthenticationRequired.into(),
+                ("CommandError", Some("InvalidCommand")) => CommandError::InvalidCommand.into(),
+                ("CommandError", _) => CommandError::Unknown(error.code.unwrap_or_default()).into(),
+                ("LockPoisoned", _) => MemcacheError::LockPoisoned(message),
+                ("PoolExhausted", _) => MemcacheError::PoolExhausted,
+                _ => ClientError::Error(Cow::Owned(message)).into(),
+            }
+        }
+    }
+
+    impl Serialize for MemcacheError {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SerializedError::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for MemcacheError {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            SerializedError::deserialize(deserializer).map(MemcacheError::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientError, CommandError, MemcacheError};
+    use std::io;
+
+    #[test]
+    fn with_category() {
+        let error = MemcacheError::from(CommandError::KeyNotFound);
+        assert_eq!(error.to_string(), "Key was not found in the server.");
+        assert_eq!(
+            error.with_category().to_string(),
+            "[Command] Key was not found in the server."
+        );
+        assert_eq!(
+            MemcacheError::from(ClientError::KeyTooLong).with_category().to_string(),
+            format!("[Client] {}", ClientError::KeyTooLong)
+        );
+        let error = MemcacheError::from(io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            "connection reset by peer",
+        ));
+        assert_eq!(error.with_category().to_string(), "[IO] connection reset by peer");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use super::ServerError;
+
+        let error = MemcacheError::from(CommandError::KeyNotFound);
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "CommandError", "kind": "KeyNotFound", "message": error.to_string()})
+        );
+        match serde_json::from_value(json).unwrap() {
+            MemcacheError::CommandError(CommandError::KeyNotFound) => {}
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        let error = MemcacheError::from(io::Error::from_raw_os_error(104));
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["type"], "IOError");
+        assert_eq!(json["os_error"], 104);
+        match serde_json::from_value(json).unwrap() {
+            MemcacheError::IOError(e) => assert_eq!(e.raw_os_error(), Some(104)),
+            error => panic!("unexpected error {:?}", error),
+        }
+
+        for error in [
+            MemcacheError::from(CommandError::Unknown(0x86)),
+            ServerError::BadMagic(0x42).into(),
+            ServerError::BadResponse("VALUE".into()).into(),
+            ClientError::KeyTooLong.into(),
+            MemcacheError::BadURL("no host".into()),
+        ] {
+            let json = serde_json::to_string(&error).unwrap();
+            let deserialized: MemcacheError = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized.to_string(), error.to_string(), "{}", json);
+        }
+
+        let error = MemcacheError::from("x".parse::<u32>().unwrap_err());
+        let deserialized: MemcacheError = serde_json::from_value(serde_json::to_value(&error).unwrap()).unwrap();
+        match deserialized {
+            MemcacheError::ClientError(ClientError::Error(message)) => assert_eq!(message, error.to_string()),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+use crate::error::{ClientError, MemcacheError};
+
+/// Parse an expiration time in seconds from a number with an optional `s`, `m`, `h` or `d` suffix, like `"90"`,
+/// `"5m"` or `"1d"`, or from several of them like `"2h30m"`. `"0"` means that the value doesn't expire.
+///
+/// memcached treats expiration times longer than 30 days as unix timestamps, so longer durations won't behave as
+/// expected.
+///
+/// Example:
+///
+/// ```rust
+/// assert_eq!(memcache::parse_expiry("0").unwrap(), 0);
+/// assert_eq!(memcache::parse_expiry("5m").unwrap(), 300);
+/// assert_eq!(memcache::parse_expiry("2h30m").unwrap(), 9000);
+/// assert!(memcache::parse_expiry("5 minutes").is_err());
+/// ```
+pub fn parse_expiry(expiry: &str) -> Result<u32, MemcacheError> {
+    let invalid = || {
+        MemcacheError::from(ClientError::Error(Cow::Owned(format!(
+            "invalid expiry '{}', expected a number of seconds or a duration like '5m' or '2h30m'",
+            expiry
+        ))))
+    };
+    if expiry.is_empty() {
+        return Err(invalid());
+    }
+    let mut total: u32 = 0;
+    let mut rest = expiry;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let amount: u32 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit = match rest.chars().next() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => 24 * 60 * 60,
+            // a bare number is only accepted on its own, like "90"
+            None if digits == expiry.len() => 1,
+            _ => return Err(invalid()),
+        };
+        rest = rest.get(1..).unwrap_or("");
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_expiry;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_expiry("0").unwrap(), 0);
+        assert_eq!(parse_expiry("90").unwrap(), 90);
+        assert_eq!(parse_expiry("45s").unwrap(), 45);
+        assert_eq!(parse_expiry("5m").unwrap(), 300);
+        assert_eq!(parse_expiry("1d").unwrap(), 86400);
+        assert_eq!(parse_expiry("1d2h30m15s").unwrap(), 86400 + 9015);
+        for invalid in &["", "m", "5w", "-5m", "5m30", "1.5h", " 5m", "99999999999", "50000d"] {
+            assert!(parse_expiry(invalid).is_err(), "{} should be invalid", invalid);
+        }
+    }
+}
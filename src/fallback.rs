@@ -0,0 +1,118 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::client::Client;
+use crate::error::MemcacheError;
+use crate::protocol::RawValue;
+use crate::value::{FromMemcacheValueExt, WithFlags};
+
+/// A client which reads from a secondary client when its primary client misses, e.g. to serve stale values from a
+/// backup cache and avoid a stampede on the backend. Created with `Client::with_fallback`.
+///
+/// Only `get` consults the fallback, the other methods of `Client` are run on the primary client.
+///
+/// Example:
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// let backup = Arc::new(memcache::Client::connect("memcache://localhost:12346").unwrap());
+/// backup.set("fallback_key", "stale", 0).unwrap();
+/// let client = memcache::Client::connect("memcache://localhost:12345")
+///     .unwrap()
+///     .with_fallback(backup)
+///     .with_promotion(60);
+/// # client.primary().delete("fallback_key").unwrap();
+/// let value: Option<String> = client.get("fallback_key").unwrap();
+/// assert_eq!(value, Some(String::from("stale")));
+/// let promoted: Option<String> = client.primary().get("fallback_key").unwrap();
+/// assert_eq!(promoted, Some(String::from("stale")));
+/// ```
+pub struct FallbackClient {
+    primary: Client,
+    fallback: Arc<Client>,
+    promotion: Option<u32>,
+}
+
+impl FallbackClient {
+    pub(crate) fn new(primary: Client, fallback: Arc<Client>) -> Self {
+        FallbackClient {
+            primary,
+            fallback,
+            promotion: None,
+        }
+    }
+
+    /// Store the values found in the fallback client into the primary client, expiring after `expiration` seconds.
+    /// Failing to store them doesn't fail `get`.
+    pub fn with_promotion(mut self, expiration: u32) -> Self {
+        self.promotion = Some(expiration);
+        self
+    }
+
+    /// Get a key from the primary client, or from the fallback client if the primary client misses.
+    pub fn get<V: FromMemcacheValueExt>(&self, key: &str) -> Result<Option<V>, MemcacheError> {
+        if let Some(value) = self.primary.get(key)? {
+            return Ok(Some(value));
+        }
+        let (value, flags, cas) = match self.fallback.get::<RawValue>(key)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if let Some(expiration) = self.promotion {
+            let bytes: &[u8] = &value;
+            let _ = self.primary.set(key, WithFlags { value: &bytes, flags }, expiration);
+        }
+        Ok(Some(V::from_memcache_value(value, flags, cas)?))
+    }
+
+    /// The primary client.
+    pub fn primary(&self) -> &Client {
+        &self.primary
+    }
+
+    /// The fallback client.
+    pub fn fallback(&self) -> &Arc<Client> {
+        &self.fallback
+    }
+}
+
+impl Deref for FallbackClient {
+    type Target = Client;
+    fn deref(&self) -> &Self::Target {
+        &self.primary
+    }
+}
+
+#[cfg(all(test, feature = "test-mode"))]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::client::Client;
+    use crate::mock::InMemoryBackend;
+
+    fn mock_client(backend: &InMemoryBackend) -> Client {
+        Client::builder().with_test_mode(backend.clone()).build().unwrap()
+    }
+
+    #[test]
+    fn fallback_get() {
+        let primary = InMemoryBackend::new();
+        let secondary = InMemoryBackend::new();
+        let fallback = Arc::new(mock_client(&secondary));
+        fallback.set("stale", 42u32, 0).unwrap();
+
+        let client = mock_client(&primary).with_fallback(fallback.clone());
+        client.set("fresh", "value", 0).unwrap();
+        assert_eq!(client.get::<String>("fresh").unwrap(), Some("value".to_string()));
+        assert_eq!(client.get::<u32>("stale").unwrap(), Some(42));
+        assert_eq!(client.get::<u32>("missing").unwrap(), None);
+        // without promotion, the primary client is left alone
+        assert_eq!(client.primary().get::<u32>("stale").unwrap(), None);
+
+        let client = client.with_promotion(60);
+        assert_eq!(client.get::<u32>("stale").unwrap(), Some(42));
+        assert_eq!(client.primary().get::<u32>("stale").unwrap(), Some(42));
+        primary.assert_set_called_with("stale", "42", 60);
+    }
+}
@@ -0,0 +1,128 @@
+use md5::{Digest, Md5};
+
+/// The number of MD5 digests computed for each server, each of them giving 4 points on the ring.
+const DIGESTS_PER_SERVER: usize = 40;
+
+/// Hash a key like ketama does, with the first 4 bytes of its MD5 digest. Used by
+/// `ClientBuilder::with_ketama_hashing` to find the key's position on the ring of servers.
+///
+/// Example:
+///
+/// ```rust
+/// assert_eq!(memcache::ketama_hash("foo"), memcache::ketama_hash("foo"));
+/// assert!(memcache::ketama_hash("foo") <= u64::from(u32::MAX));
+/// ```
+pub fn ketama_hash(key: &str) -> u64 {
+    let digest = Md5::digest(key.as_bytes());
+    u64::from(u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]))
+}
+
+/// A consistent hashing ring of servers, in the way of ketama. Each server is placed at 160 points of the ring and a
+/// key goes to the server of the first point following its hash, so removing a server only moves the keys which were
+/// on it.
+#[derive(Debug)]
+pub(crate) struct KetamaRing {
+    points: Vec<(u32, usize)>,
+}
+
+impl KetamaRing {
+    /// Build the ring of servers named like `host:port`, the indexes returned by `index` are the ones of `servers`.
+    pub(crate) fn new<S: AsRef<str>>(servers: &[S]) -> Self {
+        let mut points = Vec::with_capacity(servers.len() * DIGESTS_PER_SERVER * 4);
+        for (index, server) in servers.iter().enumerate() {
+            for i in 0..DIGESTS_PER_SERVER {
+                let digest = Md5::digest(format!("{}-{}", server.as_ref(), i).as_bytes());
+                for point in digest.chunks_exact(4) {
+                    points.push((u32::from_le_bytes([point[0], point[1], point[2], point[3]]), index));
+                }
+            }
+        }
+        points.sort_unstable();
+        KetamaRing { points }
+    }
+
+    /// The index of the server of the first point following `hash`, wrapping around the ring.
+    pub(crate) fn index(&self, hash: u64) -> usize {
+        let hash = hash as u32;
+        let position = self.points.partition_point(|&(point, _)| point < hash);
+        match self.points.get(position) {
+            Some(&(_, index)) => index,
+            None => self.points.first().map_or(0, |&(_, index)| index),
+        }
+    }
+
+    /// The indexes of up to `count` distinct servers, in the order of their first point following `hash`. The first
+    /// one is `index(hash)`, and each next one is where the key would go if the servers before it were removed.
+    pub(crate) fn successors(&self, hash: u64, count: usize) -> Vec<usize> {
+        let hash = hash as u32;
+        let position = self.points.partition_point(|&(point, _)| point < hash);
+        let mut indexes = Vec::with_capacity(count);
+        for &(_, index) in self.points[position..].iter().chain(&self.points[..position]) {
+            if indexes.len() == count {
+                break;
+            }
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        indexes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ketama_hash, KetamaRing};
+
+    #[test]
+    fn remove_server() {
+        let servers = ["localhost:12346", "localhost:12347", "localhost:12348"];
+        let ring = KetamaRing::new(&servers);
+        let smaller_ring = KetamaRing::new(&servers[..2]);
+
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key_{}", i)).collect();
+        let mut counts = [0; 3];
+        let mut moved = 0;
+        for key in keys.iter() {
+            let index = ring.index(ketama_hash(key));
+            counts[index] += 1;
+            if servers[index] != servers[smaller_ring.index(ketama_hash(key))] {
+                moved += 1;
+            }
+        }
+        assert!(moved < 4_000, "{} keys moved", moved);
+        // only the keys of the removed server move
+        assert_eq!(moved, counts[2]);
+        for count in counts.iter() {
+            assert!(*count > 2_000, "unbalanced ring: {:?}", counts);
+        }
+    }
+
+    #[test]
+    fn successors() {
+        let servers = ["localhost:12346", "localhost:12347", "localhost:12348"];
+        let ring = KetamaRing::new(&servers);
+        let smaller_ring = KetamaRing::new(&servers[..2]);
+
+        for i in 0..1_000 {
+            let hash = ketama_hash(&format!("key_{}", i));
+            let successors = ring.successors(hash, 3);
+            assert_eq!(successors[0], ring.index(hash));
+            let mut sorted = successors.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, vec![0, 1, 2]);
+            // the keys of the removed server move to its successor
+            if successors[0] == 2 {
+                assert_eq!(successors[1], smaller_ring.index(hash));
+            }
+        }
+        assert_eq!(ring.successors(0, 1), vec![ring.index(0)]);
+    }
+
+    #[test]
+    fn single_server() {
+        let ring = KetamaRing::new(&["localhost:12345"]);
+        assert_eq!(ring.index(0), 0);
+        assert_eq!(ring.index(u64::from(u32::MAX)), 0);
+        assert_eq!(ring.successors(0, 2), vec![0]);
+    }
+}
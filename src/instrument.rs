@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+
+/// The `status` label of an operation which succeeded, `hit` or `miss` for the ones looking up a single key.
+pub(crate) trait OperationStatus {
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    fn status(&self) -> &'static str {
+        "ok"
+    }
+}
+
+impl OperationStatus for () {}
+
+impl OperationStatus for bool {}
+
+impl OperationStatus for u64 {}
+
+impl OperationStatus for String {}
+
+impl<K, V> OperationStatus for HashMap<K, V> {}
+
+impl<T> OperationStatus for Vec<T> {}
+
+/// The result of a command sent by the application through `Client::with_connection_for_key`, which is recorded as
+/// `ok` whatever it is.
+pub(crate) struct Custom<T>(pub(crate) T);
+
+impl<T> OperationStatus for Custom<T> {}
+
+impl<V> OperationStatus for Option<V> {
+    fn status(&self) -> &'static str {
+        match self {
+            Some(_) => "hit",
+            None => "miss",
+        }
+    }
+}
+
+/// Record an operation with the `metrics` crate facade, which forwards it to the recorder installed by the
+/// application, if any.
+#[cfg(feature = "metrics")]
+pub(crate) fn record(operation: &'static str, server: String, status: &'static str, elapsed: Duration) {
+    metrics::counter!(
+        "memcache.operations.total",
+        "operation" => operation,
+        "status" => status,
+        "server" => server.clone()
+    )
+    .increment(1);
+    metrics::histogram!(
+        "memcache.operation.duration_seconds",
+        "operation" => operation,
+        "server" => server
+    )
+    .record(elapsed.as_secs_f64());
+}
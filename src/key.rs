@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::client::{check_key_len, MAX_KEY_LENGTH};
+use crate::error::{ClientError, MemcacheError};
+
+/// Check that `key` is accepted by memcached, e.g. before adding it to a batch, returning
+/// `ClientError::KeyTooLong` if it's longer than 250 bytes, `ClientError::KeyInvalidCharacters` if it contains
+/// whitespace or control characters, or `ClientError::Error` if it's empty.
+///
+/// Example:
+///
+/// ```rust
+/// use memcache::{ClientError, MemcacheError};
+///
+/// assert!(memcache::validate_key("users:42").is_ok());
+/// match memcache::validate_key("users 42") {
+///     Err(MemcacheError::ClientError(ClientError::KeyInvalidCharacters)) => {}
+///     _ => panic!("expected an invalid characters error"),
+/// }
+/// ```
+pub fn validate_key(key: &str) -> Result<(), MemcacheError> {
+    check_key_len(key, MAX_KEY_LENGTH)?;
+    check_key_chars(key)
+}
+
+pub(crate) fn check_key_chars(key: &str) -> Result<(), MemcacheError> {
+    if key.chars().any(is_invalid_key_char) {
+        Err(ClientError::KeyInvalidCharacters)?
+    }
+    Ok(())
+}
+
+fn is_invalid_key_char(c: char) -> bool {
+    c.is_whitespace() || c.is_control()
+}
+
+/// Build a key from a namespace and segments joined by a separator, like `"myapp:users:42"`, so that different
+/// components sharing a memcached cluster don't accidentally use the same keys.
+///
+/// Segments containing whitespace or control characters, which are invalid in memcached keys, are reported by `build`.
+///
+/// Example:
+///
+/// ```rust
+/// use memcache::KeyBuilder;
+///
+/// let key = KeyBuilder::new("myapp").segment("users").id(42).build().unwrap();
+/// assert_eq!(key, "myapp:users:42");
+/// assert!(KeyBuilder::new("myapp").segment("bad key").build().is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct KeyBuilder {
+    key: String,
+    separator: char,
+    invalid_segment: Option<String>,
+    max_length: usize,
+}
+
+impl KeyBuilder {
+    /// Start a key with the given namespace, using `:` as the separator.
+    pub fn new(namespace: &str) -> Self {
+        Self::with_separator(namespace, ':')
+    }
+
+    /// Start a key with the given namespace and separator.
+    pub fn with_separator(namespace: &str, separator: char) -> Self {
+        let mut builder = KeyBuilder {
+            key: String::new(),
+            separator,
+            invalid_segment: None,
+            max_length: MAX_KEY_LENGTH,
+        };
+        builder.push(namespace);
+        builder
+    }
+
+    /// Reject keys longer than `max_length` bytes instead of 250, for clients with a stricter limit.
+    pub(crate) fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    /// Append a segment to the key.
+    pub fn segment(mut self, segment: &str) -> Self {
+        self.key.push(self.separator);
+        self.push(segment);
+        self
+    }
+
+    /// Append an id, or anything else that can be displayed, to the key.
+    pub fn id<T: fmt::Display>(self, id: T) -> Self {
+        self.segment(&id.to_string())
+    }
+
+    /// Return the key, or an error if a segment is invalid or the key is too long.
+    pub fn build(self) -> Result<String, MemcacheError> {
+        if let Some(segment) = self.invalid_segment {
+            Err(ClientError::Error(Cow::Owned(format!(
+                "key segment '{}' contains whitespace or control characters",
+                segment.escape_debug()
+            ))))?
+        }
+        check_key_len(&self.key, self.max_length)?;
+        Ok(self.key)
+    }
+
+    fn push(&mut self, segment: &str) {
+        if self.invalid_segment.is_none() && segment.chars().any(is_invalid_key_char) {
+            self.invalid_segment = Some(segment.to_string());
+        }
+        self.key.push_str(segment);
+    }
+}
+
+/// The key built so far, without validating it.
+impl AsRef<str> for KeyBuilder {
+    fn as_ref(&self) -> &str {
+        &self.key
+    }
+}
+
+/// The key built so far, without validating it.
+impl From<KeyBuilder> for String {
+    fn from(builder: KeyBuilder) -> String {
+        builder.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_key, KeyBuilder};
+    use crate::error::{ClientError, MemcacheError};
+
+    #[test]
+    fn build_key() {
+        let key = KeyBuilder::new("ns").segment("users").id(42u64).build().unwrap();
+        assert_eq!(key, "ns:users:42");
+
+        let key = KeyBuilder::with_separator("ns", '/')
+            .segment("users")
+            .id(-1)
+            .build()
+            .unwrap();
+        assert_eq!(key, "ns/users/-1");
+
+        let builder = KeyBuilder::new("ns").segment("a");
+        assert_eq!(builder.as_ref(), "ns:a");
+        assert_eq!(String::from(builder), "ns:a");
+    }
+
+    #[test]
+    fn build_invalid_key() {
+        assert!(KeyBuilder::new("ns").segment("a b").build().is_err());
+        assert!(KeyBuilder::new("ns").segment("a\r\nb").segment("c").build().is_err());
+        assert!(KeyBuilder::new("bad ns").build().is_err());
+        assert!(KeyBuilder::new("ns").segment(&"a".repeat(250)).build().is_err());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(validate_key("ns:users:42").is_ok());
+        assert!(validate_key(&"a".repeat(250)).is_ok());
+        match validate_key(&"a".repeat(251)) {
+            Err(MemcacheError::ClientError(ClientError::KeyTooLong)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(validate_key(&"é".repeat(125)).is_ok());
+        assert!(validate_key(&"é".repeat(126)).is_err());
+        match validate_key("") {
+            Err(MemcacheError::ClientError(ClientError::Error(_))) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        for key in &["a b", "a\r\nb", "a\tb", "a\u{0}b"] {
+            match validate_key(key) {
+                Err(MemcacheError::ClientError(ClientError::KeyInvalidCharacters)) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+}
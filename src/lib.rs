@@ -22,12 +22,15 @@ memcache = "*"
   - <input type="checkbox"  disabled checked/> TLS connection
 - <input type="checkbox"  disabled /> Encodings
   - <input type="checkbox"  disabled checked /> Typed interface
-  - <input type="checkbox"  disabled /> Automatically compress
+  - <input type="checkbox"  disabled checked /> Automatically compress, with the `compression` feature
   - <input type="checkbox"  disabled /> Automatically serialize to JSON / msgpack etc
 - <input type="checkbox"  disabled checked /> Mutiple server support with custom key hash algorithm
 - <input type="checkbox"  disabled checked /> Authority
   - <input type="checkbox"  disabled checked /> Binary protocol (plain SASL authority)
+  - <input type="checkbox"  disabled checked /> Binary protocol (SCRAM-SHA-1 SASL authority, with the `sasl-scram` feature)
   - <input type="checkbox"  disabled checked /> ASCII protocol
+- <input type="checkbox"  disabled checked /> Operation counters and durations through the `metrics` crate, with the `metrics` feature
+- <input type="checkbox"  disabled checked /> Serializing errors with serde, with the `serde` feature
 
 # Basic usage:
 
@@ -65,25 +68,85 @@ assert_eq!(answer, 42);
 
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::needless_return))]
 
+#[cfg(feature = "sasl-scram")]
+extern crate base64;
 extern crate byteorder;
 extern crate enum_dispatch;
+#[cfg(feature = "compression")]
+extern crate flate2;
+#[cfg(feature = "sticky-keys")]
+extern crate glob;
+#[cfg(feature = "sasl-scram")]
+extern crate hmac;
+#[cfg(feature = "ketama")]
+extern crate md5;
+#[cfg(feature = "metrics")]
+extern crate metrics;
 #[cfg(feature = "tls")]
 extern crate openssl;
+#[cfg(feature = "sasl-scram")]
+extern crate pbkdf2;
 extern crate r2d2;
 extern crate rand;
+#[cfg(feature = "semver")]
+extern crate semver;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "sasl-scram")]
+extern crate sha1;
+#[cfg(feature = "key-hashing")]
+extern crate sha2;
+#[cfg(feature = "srv-discovery")]
+extern crate trust_dns_resolver;
 extern crate url;
+#[cfg(feature = "uuid")]
+extern crate uuid;
 
 mod client;
 mod connection;
+#[cfg(feature = "srv-discovery")]
+mod discovery;
 mod error;
+mod expiry;
+mod fallback;
+#[cfg(feature = "ketama")]
+mod hash;
+mod instrument;
+mod key;
+#[cfg(feature = "test-mode")]
+mod mock;
+mod options;
+mod pipeline;
 mod protocol;
+mod shadow;
+mod stats;
 mod stream;
 mod value;
 
 pub use crate::client::{Client, ClientBuilder, Connectable};
 pub use crate::connection::ConnectionManager;
-pub use crate::error::{ClientError, CommandError, MemcacheError, ServerError};
+#[cfg(feature = "tls")]
+pub use crate::connection::TlsOptions;
+pub use crate::error::{CategorizedError, ClientError, CommandError, MemcacheError, ServerError};
+pub use crate::expiry::parse_expiry;
+pub use crate::fallback::FallbackClient;
+#[cfg(feature = "ketama")]
+pub use crate::hash::ketama_hash;
+pub use crate::key::{validate_key, KeyBuilder};
+#[cfg(feature = "test-mode")]
+pub use crate::mock::{InMemoryBackend, MockBackend, MockCall};
+pub use crate::options::ClientOptions;
+pub use crate::pipeline::Pipeline;
+pub use crate::protocol::{
+    AddResult, DynProtocol, PipelineReply, PipelineResult, Protocol, ProtocolTrait, RawValue, ValueWithMetadata,
+};
+pub use crate::stats::{
+    CachedItem, ClusterInfo, ConnectionInfo, HealthStatus, ItemStats, ServerInfo, ServerLatency, ServerSummary,
+    SlabItemStats, Version,
+};
 pub use crate::stream::Stream;
+#[cfg(feature = "compression")]
+pub use crate::value::CompressedValue;
 pub use crate::value::{FromMemcacheValue, FromMemcacheValueExt, ToMemcacheValue};
 pub use r2d2::Error;
 pub use url::{ParseError as UrlParseError, Url};
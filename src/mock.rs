@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::client::Stats;
+use crate::error::{CommandError, MemcacheError};
+use crate::protocol::RawValue;
+
+/// The commands of a memcached server, called by a client built with `ClientBuilder::with_test_mode` instead of
+/// sending them over the network.
+///
+/// Values are raw bytes and flags, as they would be stored on a server. `InMemoryBackend` implements this trait with
+/// a `HashMap`, implement it directly to simulate failures.
+pub trait MockBackend: Send + Sync {
+    fn version(&self) -> Result<String, MemcacheError>;
+    fn flush(&self) -> Result<(), MemcacheError>;
+    fn get(&self, key: &str) -> Result<Option<RawValue>, MemcacheError>;
+    fn set(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn cas(&self, key: &str, value: &[u8], flags: u32, expiration: u32, cas: u64) -> Result<bool, MemcacheError>;
+    fn add(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn replace(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn append(&self, key: &str, value: &[u8]) -> Result<(), MemcacheError>;
+    fn prepend(&self, key: &str, value: &[u8]) -> Result<(), MemcacheError>;
+    fn delete(&self, key: &str) -> Result<bool, MemcacheError>;
+    fn increment(&self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
+    fn decrement(&self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
+    fn touch(&self, key: &str, expiration: u32) -> Result<bool, MemcacheError>;
+    fn stats(&self) -> Result<Stats, MemcacheError>;
+}
+
+/// A command received by an `InMemoryBackend`. The value and expiration are `None` for commands without them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockCall {
+    pub command: &'static str,
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+    pub expiration: Option<u32>,
+}
+
+struct Item {
+    value: Vec<u8>,
+    flags: u32,
+    cas: u64,
+}
+
+#[derive(Default)]
+struct State {
+    items: HashMap<String, Item>,
+    calls: Vec<MockCall>,
+    last_cas: u64,
+}
+
+impl State {
+    fn record(&mut self, command: &'static str, key: &str, value: Option<&[u8]>, expiration: Option<u32>) {
+        self.calls.push(MockCall {
+            command,
+            key: key.to_string(),
+            value: value.map(<[u8]>::to_vec),
+            expiration,
+        });
+    }
+
+    fn store(&mut self, key: &str, value: Vec<u8>, flags: u32) {
+        self.last_cas += 1;
+        let cas = self.last_cas;
+        self.items.insert(key.to_string(), Item { value, flags, cas });
+    }
+
+    fn update<F>(&mut self, key: &str, f: F) -> Result<(), MemcacheError>
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        self.last_cas += 1;
+        match self.items.get_mut(key) {
+            Some(item) => {
+                f(&mut item.value);
+                item.cas = self.last_cas;
+                Ok(())
+            }
+            None => Err(CommandError::KeyNotFound)?,
+        }
+    }
+
+    fn add_to_counter(&mut self, key: &str, amount: i128) -> Result<u64, MemcacheError> {
+        let mut result = Ok(0);
+        self.update(key, |value| {
+            result = std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or(MemcacheError::CommandError(CommandError::InvalidArguments))
+                .map(|counter| (i128::from(counter) + amount).clamp(0, u64::MAX.into()) as u64);
+            if let Ok(counter) = result {
+                *value = counter.to_string().into_bytes();
+            }
+        })?;
+        result
+    }
+}
+
+/// An in-memory `MockBackend`, which records every command it receives for assertions. Clones share the same items
+/// and calls, so keep a clone to inspect it after passing it to `ClientBuilder::with_test_mode`.
+///
+/// Expiration times are recorded, but items never expire.
+///
+/// Example:
+///
+/// ```rust
+/// use memcache::InMemoryBackend;
+///
+/// let mock = InMemoryBackend::new();
+/// let client = memcache::Client::builder().with_test_mode(mock.clone()).build().unwrap();
+/// client.set("foo", "bar", 60).unwrap();
+/// let value: Option<String> = client.get("foo").unwrap();
+/// assert_eq!(value, Some(String::from("bar")));
+/// mock.assert_set_called_with("foo", "bar", 60);
+/// ```
+#[derive(Clone, Default)]
+pub struct InMemoryBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl InMemoryBackend {
+    /// Create an empty backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The commands received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.lock().calls.clone()
+    }
+
+    /// Forget the commands received so far, keeping the stored items.
+    pub fn clear_calls(&self) {
+        self.lock().calls.clear();
+    }
+
+    /// Panic unless `key` was set to `value` with `expiration` seconds, listing the received commands.
+    pub fn assert_set_called_with<V: AsRef<[u8]>>(&self, key: &str, value: V, expiration: u32) {
+        let expected = MockCall {
+            command: "set",
+            key: key.to_string(),
+            value: Some(value.as_ref().to_vec()),
+            expiration: Some(expiration),
+        };
+        let calls = self.calls();
+        assert!(
+            calls.contains(&expected),
+            "expected {:?}, received {:?}",
+            expected,
+            calls
+        );
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // a test which panicked while holding the lock shouldn't hide the state from other tests
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl MockBackend for InMemoryBackend {
+    fn version(&self) -> Result<String, MemcacheError> {
+        Ok("1.6.0-mock".to_string())
+    }
+
+    fn flush(&self) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("flush", "", None, None);
+        state.items.clear();
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<RawValue>, MemcacheError> {
+        let mut state = self.lock();
+        state.record("get", key, None, None);
+        Ok(state
+            .items
+            .get(key)
+            .map(|item| (item.value.clone(), item.flags, Some(item.cas))))
+    }
+
+    fn set(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("set", key, Some(value), Some(expiration));
+        state.store(key, value.to_vec(), flags);
+        Ok(())
+    }
+
+    fn cas(&self, key: &str, value: &[u8], flags: u32, expiration: u32, cas: u64) -> Result<bool, MemcacheError> {
+        let mut state = self.lock();
+        state.record("cas", key, Some(value), Some(expiration));
+        match state.items.get(key) {
+            Some(item) if item.cas == cas => {
+                state.store(key, value.to_vec(), flags);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn add(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("add", key, Some(value), Some(expiration));
+        if state.items.contains_key(key) {
+            Err(CommandError::KeyExists)?
+        }
+        state.store(key, value.to_vec(), flags);
+        Ok(())
+    }
+
+    fn replace(&self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("replace", key, Some(value), Some(expiration));
+        if !state.items.contains_key(key) {
+            Err(CommandError::KeyNotFound)?
+        }
+        state.store(key, value.to_vec(), flags);
+        Ok(())
+    }
+
+    fn append(&self, key: &str, value: &[u8]) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("append", key, Some(value), None);
+        state.update(key, |item| item.extend_from_slice(value))
+    }
+
+    fn prepend(&self, key: &str, value: &[u8]) -> Result<(), MemcacheError> {
+        let mut state = self.lock();
+        state.record("prepend", key, Some(value), None);
+        state.update(key, |item| {
+            item.splice(0..0, value.iter().copied());
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<bool, MemcacheError> {
+        let mut state = self.lock();
+        state.record("delete", key, None, None);
+        Ok(state.items.remove(key).is_some())
+    }
+
+    fn increment(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        let mut state = self.lock();
+        state.record("increment", key, None, None);
+        state.add_to_counter(key, amount.into())
+    }
+
+    fn decrement(&self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        let mut state = self.lock();
+        state.record("decrement", key, None, None);
+        state.add_to_counter(key, -i128::from(amount))
+    }
+
+    fn touch(&self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+        let mut state = self.lock();
+        state.record("touch", key, None, Some(expiration));
+        Ok(state.items.contains_key(key))
+    }
+
+    fn stats(&self) -> Result<Stats, MemcacheError> {
+        let state = self.lock();
+        let mut stats = Stats::new();
+        stats.insert("curr_items".to_string(), state.items.len().to_string());
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemoryBackend, MockBackend, MockCall};
+    use crate::error::{CommandError, MemcacheError};
+
+    #[test]
+    fn in_memory_backend() {
+        let mock = InMemoryBackend::new();
+        assert_eq!(mock.get("foo").unwrap(), None);
+        mock.set("foo", b"bar", 7, 60).unwrap();
+        let (value, flags, cas) = mock.get("foo").unwrap().unwrap();
+        assert_eq!((value.as_slice(), flags), (&b"bar"[..], 7));
+
+        assert!(!mock.cas("foo", b"baz", 0, 0, cas.unwrap() + 1).unwrap());
+        assert!(mock.cas("foo", b"baz", 0, 0, cas.unwrap()).unwrap());
+        match mock.add("foo", b"qux", 0, 0) {
+            Err(MemcacheError::CommandError(CommandError::KeyExists)) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+        mock.append("foo", b"!").unwrap();
+        mock.prepend("foo", b"<").unwrap();
+        assert_eq!(mock.get("foo").unwrap().unwrap().0, b"<baz!");
+        assert!(mock.append("missing", b"!").is_err());
+
+        mock.set("counter", b"10", 0, 0).unwrap();
+        assert_eq!(mock.increment("counter", 5).unwrap(), 15);
+        assert_eq!(mock.decrement("counter", 20).unwrap(), 0);
+        assert!(mock.increment("foo", 1).is_err());
+
+        assert!(mock.delete("foo").unwrap());
+        assert!(!mock.touch("foo", 10).unwrap());
+        mock.assert_set_called_with("foo", "bar", 60);
+        assert_eq!(
+            mock.calls().last(),
+            Some(&MockCall {
+                command: "touch",
+                key: "foo".to_string(),
+                value: None,
+                expiration: Some(10),
+            })
+        );
+        mock.clear_calls();
+        assert!(mock.calls().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected MockCall")]
+    fn assert_set_called_with() {
+        let mock = InMemoryBackend::new();
+        mock.set("foo", b"bar", 0, 60).unwrap();
+        mock.assert_set_called_with("foo", "bar", 30);
+    }
+}
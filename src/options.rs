@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+/// The configuration of a `ClientBuilder` as plain data, e.g. to load it from a configuration file with the `serde`
+/// feature, then create the builder with `ClientBuilder::from_options`. The missing fields take their default value,
+/// which is the builder's default.
+///
+/// The durations are serialized like serde does by default, as `{"secs": 1, "nanos": 0}`.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// let options = memcache::ClientOptions {
+///     servers: vec!["memcache://localhost:12345".to_string()],
+///     max_size: 4,
+///     read_timeout: Some(Duration::from_secs(1)),
+///     ..Default::default()
+/// };
+/// let client = memcache::ClientBuilder::from_options(options).unwrap().build().unwrap();
+/// client.set("foo", "bar", 0).unwrap();
+/// # client.flush().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ClientOptions {
+    /// The URLs of the servers, like `memcache://localhost:11211?timeout=1`.
+    pub servers: Vec<String>,
+    /// See `ClientBuilder::with_max_pool_size`.
+    pub max_size: u32,
+    /// See `ClientBuilder::with_min_idle_conns`.
+    pub min_idle: Option<u32>,
+    /// See `ClientBuilder::with_max_conn_lifetime`.
+    pub max_lifetime: Option<Duration>,
+    /// See `ClientBuilder::with_read_timeout`.
+    pub read_timeout: Option<Duration>,
+    /// See `ClientBuilder::with_write_timeout`.
+    pub write_timeout: Option<Duration>,
+    /// See `ClientBuilder::with_connection_timeout`.
+    pub connection_timeout: Option<Duration>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            servers: Vec::new(),
+            max_size: 1,
+            min_idle: None,
+            max_lifetime: None,
+            read_timeout: None,
+            write_timeout: None,
+            connection_timeout: None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::ClientOptions;
+    use std::time::Duration;
+
+    #[test]
+    fn deserialize() {
+        let options: ClientOptions = serde_json::from_str(
+            r#"{"servers": ["memcache://localhost:12345"], "max_size": 4, "read_timeout": {"secs": 1, "nanos": 0}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            ClientOptions {
+                servers: vec!["memcache://localhost:12345".to_string()],
+                max_size: 4,
+                read_timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            }
+        );
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(serde_json::from_str::<ClientOptions>(&json).unwrap(), options);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::client::Client;
+use crate::error::MemcacheError;
+use crate::protocol::PipelineCommand;
+use crate::value::ToMemcacheValue;
+
+/// Commands queued by the closure of `Client::pipeline_for_key`, which are sent in a single write once it returns.
+///
+/// Keys are prepared like with the other client methods, so they are validated and hashed when queued.
+pub struct Pipeline<'a> {
+    client: &'a Client,
+    pub(crate) commands: Vec<PipelineCommand>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Pipeline {
+            client,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Queue a `set` command, replied with `PipelineReply::Stored`.
+    pub fn set<V: ToMemcacheValue<Vec<u8>>>(
+        &mut self,
+        key: &str,
+        value: V,
+        expiration: u32,
+    ) -> Result<(), MemcacheError> {
+        let key = self.client.prepare_key(key)?.into_owned();
+        let flags = value.get_flags();
+        let mut bytes = Vec::with_capacity(value.get_length());
+        value.write_to(&mut bytes)?;
+        self.commands.push(PipelineCommand::Set {
+            key,
+            value: bytes,
+            flags,
+            expiration,
+        });
+        Ok(())
+    }
+
+    /// Queue a `get` command, replied with `PipelineReply::Value`.
+    pub fn get(&mut self, key: &str) -> Result<(), MemcacheError> {
+        let key = self.client.prepare_key(key)?.into_owned();
+        self.commands.push(PipelineCommand::Get(key));
+        Ok(())
+    }
+
+    /// Queue a `delete` command, replied with `PipelineReply::Deleted`.
+    pub fn delete(&mut self, key: &str) -> Result<(), MemcacheError> {
+        let key = self.client.prepare_key(key)?.into_owned();
+        self.commands.push(PipelineCommand::Delete(key));
+        Ok(())
+    }
+
+    /// The number of queued commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Whether no command is queued.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
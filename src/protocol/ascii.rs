@@ -2,11 +2,12 @@ use std::collections::HashMap;
 use std::fmt;
 use std::io::{Read, Write};
 
-use super::ProtocolTrait;
+use super::{AddResult, PipelineCommand, PipelineReply, PipelineResult, ProtocolTrait, RawValue, ValueWithMetadata};
 use crate::client::Stats;
 use crate::error::{ClientError, CommandError, MemcacheError, ServerError};
+use crate::stats::{self, CachedItem, Version};
 use crate::stream::Stream;
-use crate::value::{FromMemcacheValueExt, ToMemcacheValue};
+use crate::value::{FromMemcacheValueExt, ToMemcacheValue, WithFlags};
 use std::borrow::Cow;
 
 #[derive(Default)]
@@ -121,8 +122,66 @@ impl<C: Read> CappedLineReader<C> {
     }
 }
 
+/// Parse a `VALUE <key> <flags> <bytes> [<cas unique>]\r\n` header line. Repeated spaces between
+/// the tokens are tolerated, but missing or extra tokens are reported as distinct errors.
+fn parse_value_header(buf: &str, has_cas: bool) -> Result<(String, u32, usize, Option<u64>), MemcacheError> {
+    let line = buf.trim_end_matches("\r\n");
+    let mut header = line.split(' ').filter(|token| !token.is_empty());
+    let mut next_or_err = |name: &str| {
+        header.next().ok_or_else(|| {
+            ServerError::BadResponse(Cow::Owned(format!("missing {} token in VALUE line '{}'", name, line)))
+        })
+    };
+    let _ = next_or_err("VALUE")?;
+    let key = next_or_err("key")?;
+    let flags: u32 = next_or_err("flags")?.parse()?;
+    let length: usize = next_or_err("bytes")?.parse()?;
+    let cas: Option<u64> = if has_cas {
+        Some(next_or_err("cas")?.parse()?)
+    } else {
+        None
+    };
+    if let Some(token) = header.next() {
+        return Err(ServerError::BadResponse(Cow::Owned(format!(
+            "unexpected extra token '{}' in VALUE line '{}'",
+            token, line
+        )))
+        .into());
+    }
+    if !key.is_ascii() {
+        return Err(ServerError::BadResponse(Cow::Owned(format!("non-ASCII key in VALUE line '{}'", line))).into());
+    }
+    Ok((key.to_string(), flags, length, cas))
+}
+
+/// Parse a `VA <bytes> f<flags> c<cas> t<ttl>\r\n` meta get header line.
+fn parse_meta_value_header(buf: &str) -> Result<(usize, u32, u64, i64), MemcacheError> {
+    let line = buf.trim_end_matches("\r\n");
+    let bad_response = || ServerError::BadResponse(Cow::Owned(format!("invalid meta get response '{}'", line)));
+    let mut tokens = line.split(' ').filter(|token| !token.is_empty());
+    if tokens.next() != Some("VA") {
+        return Err(bad_response().into());
+    }
+    let length: usize = tokens.next().ok_or_else(bad_response)?.parse()?;
+    let (mut flags, mut cas, mut ttl) = (None, None, None);
+    for token in tokens {
+        match token.split_at(1) {
+            ("f", value) => flags = Some(value.parse()?),
+            ("c", value) => cas = Some(value.parse()?),
+            ("t", value) => ttl = Some(value.parse()?),
+            _ => {}
+        }
+    }
+    match (flags, cas, ttl) {
+        (Some(flags), Some(cas), Some(ttl)) => Ok((length, flags, cas, ttl)),
+        _ => Err(bad_response())?,
+    }
+}
+
 pub struct AsciiProtocol<C: Read + Write + Sized> {
     reader: CappedLineReader<C>,
+    // whether the server supports the meta commands, detected by its version on first use
+    supports_meta: Option<bool>,
 }
 
 impl ProtocolTrait for AsciiProtocol<Stream> {
@@ -131,7 +190,7 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
     }
 
     fn version(&mut self) -> Result<String, MemcacheError> {
-        self.reader.get_mut().write(b"version\r\n")?;
+        self.reader.get_mut().write_all(b"version\r\n")?;
         self.reader.get_mut().flush()?;
         self.reader.read_line(|response| {
             let response = MemcacheError::try_from(response)?;
@@ -145,6 +204,7 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
 
     fn flush(&mut self) -> Result<(), MemcacheError> {
         write!(self.reader.get_mut(), "flush_all\r\n")?;
+        self.reader.get_mut().flush()?;
         self.parse_ok_response()
     }
 
@@ -156,37 +216,91 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
 
     fn get<V: FromMemcacheValueExt>(&mut self, key: &str) -> Result<Option<V>, MemcacheError> {
         write!(self.reader.get_mut(), "get {}\r\n", key)?;
+        self.reader.get_mut().flush()?;
+        self.parse_single_get_response(key, false)
+    }
 
-        if let Some((k, v)) = self.parse_get_response(false)? {
-            if k != key {
-                Err(ServerError::BadResponse(Cow::Borrowed(
-                    "key doesn't match in the response",
-                )))?
-            } else if self.parse_get_response::<V>(false)?.is_none() {
-                Ok(Some(v))
-            } else {
-                Err(ServerError::BadResponse(Cow::Borrowed("Expected end of get response")))?
+    fn get_and_touch<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+        expiration: u32,
+    ) -> Result<Option<V>, MemcacheError> {
+        write!(self.reader.get_mut(), "gat {} {}\r\n", expiration, key)?;
+        self.reader.get_mut().flush()?;
+        match self.parse_single_get_response(key, false) {
+            // servers older than 1.4.20 don't know the gat command
+            Err(MemcacheError::CommandError(CommandError::InvalidCommand)) => {
+                let value = self.get(key)?;
+                if value.is_some() {
+                    self.touch(key, expiration)?;
+                }
+                Ok(value)
             }
-        } else {
-            Ok(None)
+            result => result,
         }
     }
 
     fn gets<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
         write!(self.reader.get_mut(), "gets {}\r\n", keys.join(" "))?;
+        self.reader.get_mut().flush()?;
+        self.parse_multi_get_response(keys.len(), true)
+    }
 
-        let mut result: HashMap<String, V> = HashMap::with_capacity(keys.len());
-        // there will be atmost keys.len() "VALUE <...>" responses and one END response
-        for _ in 0..=keys.len() {
-            match self.parse_get_response(true)? {
-                Some((key, value)) => {
-                    result.insert(key, value);
+    fn get_many<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
+        write!(self.reader.get_mut(), "get {}\r\n", keys.join(" "))?;
+        self.reader.get_mut().flush()?;
+        self.parse_multi_get_response(keys.len(), false)
+    }
+
+    fn get_with_metadata<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<ValueWithMetadata<V>>, MemcacheError> {
+        if !self.supports_meta()? {
+            return match self.gets_single::<RawValue>(key)? {
+                Some((value, flags, cas)) => {
+                    let cas = cas.unwrap_or_default();
+                    Ok(Some((
+                        V::from_memcache_value(value, flags, Some(cas))?,
+                        flags,
+                        cas,
+                        None,
+                    )))
                 }
-                None => return Ok(result),
+                None => Ok(None),
+            };
+        }
+
+        write!(self.reader.get_mut(), "mg {} v f c t\r\n", key)?;
+        self.reader.get_mut().flush()?;
+        let header = self.reader.read_line(|response| {
+            let response = MemcacheError::try_from(response)?;
+            if response == "EN\r\n" {
+                return Ok(None);
             }
+            parse_meta_value_header(response).map(Some)
+        })?;
+        let (length, flags, cas, ttl) = match header {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let mut value = vec![0u8; length + 2];
+        self.reader.read_exact(value.as_mut_slice())?;
+        if &value[length..] != b"\r\n" {
+            return Err(ServerError::BadResponse(Cow::Owned(String::from_utf8(value)?)))?;
         }
+        value.truncate(length);
+        Ok(Some((
+            V::from_memcache_value(value, flags, Some(cas))?,
+            flags,
+            cas,
+            Some(ttl),
+        )))
+    }
 
-        Err(ServerError::BadResponse(Cow::Borrowed("Expected end of gets response")))?
+    fn exists_batch(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        let values: HashMap<String, Vec<u8>> = self.gets(keys)?;
+        Ok(keys.iter().map(|key| values.contains_key(*key)).collect())
     }
 
     fn cas<V: ToMemcacheValue<Stream>>(
@@ -218,6 +332,35 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
         self.store(StoreCommand::Set, key, value, &options).map(|_| ())
     }
 
+    fn sets<K, V, I>(&mut self, entries: I) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let mut count = 0;
+        for (key, value, expiration) in entries {
+            let options = Options {
+                exptime: expiration,
+                ..Default::default()
+            };
+            self.write_store_command(StoreCommand::Set, key.as_ref(), value, &options)?;
+            count += 1;
+        }
+        self.reader.get_mut().flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Ok(());
+        for _ in 0..count {
+            match self.parse_store_response() {
+                Ok(_) => {}
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+
     fn add<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
         let options = Options {
             exptime: expiration,
@@ -226,6 +369,35 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
         self.store(StoreCommand::Add, key, value, &options).map(|_| ())
     }
 
+    fn adds<K, V, I>(&mut self, entries: I) -> Result<Vec<AddResult>, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let mut count = 0;
+        for (key, value, expiration) in entries {
+            let options = Options {
+                exptime: expiration,
+                ..Default::default()
+            };
+            self.write_store_command(StoreCommand::Add, key.as_ref(), value, &options)?;
+            count += 1;
+        }
+        self.reader.get_mut().flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.parse_store_response() {
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(MemcacheError::CommandError(CommandError::KeyExists)) => result.push(Ok(false)),
+                added => result.push(added),
+            }
+        }
+        Ok(result)
+    }
+
     fn replace<V: ToMemcacheValue<Stream>>(
         &mut self,
         key: &str,
@@ -249,52 +421,127 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
             .map(|_| ())
     }
 
+    fn append_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let options = Options {
+            noreply: true,
+            ..Default::default()
+        };
+        self.store(StoreCommand::Append, key, value, &options).map(|_| ())
+    }
+
+    fn prepend_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let options = Options {
+            noreply: true,
+            ..Default::default()
+        };
+        self.store(StoreCommand::Prepend, key, value, &options).map(|_| ())
+    }
+
     fn delete(&mut self, key: &str) -> Result<bool, MemcacheError> {
         write!(self.reader.get_mut(), "delete {}\r\n", key)?;
         self.reader.get_mut().flush()?;
-        self.reader
-            .read_line(|response| match MemcacheError::try_from(response) {
-                Ok(s) => {
-                    if s == "DELETED\r\n" {
-                        Ok(true)
-                    } else {
-                        Err(ServerError::BadResponse(Cow::Owned(s.into())).into())
+        self.parse_delete_response()
+    }
+
+    fn deletes(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        for key in keys {
+            write!(self.reader.get_mut(), "delete {}\r\n", key)?;
+        }
+        self.reader.get_mut().flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Ok(Vec::with_capacity(keys.len()));
+        for _ in keys {
+            match self.parse_delete_response() {
+                Ok(deleted) => {
+                    if let Ok(ref mut deletes) = result {
+                        deletes.push(deleted);
                     }
                 }
-                Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
-                Err(e) => Err(e),
-            })
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+
+    fn deletes_noreply(&mut self, keys: &[&str]) -> Result<(), MemcacheError> {
+        for key in keys {
+            write!(self.reader.get_mut(), "delete {} noreply\r\n", key)?;
+        }
+        self.reader.get_mut().flush()?;
+        Ok(())
+    }
+
+    fn compare_and_delete(&mut self, key: &str, cas: u64) -> Result<bool, MemcacheError> {
+        // the ASCII protocol can't delete by cas, so replace the item by an empty one which expires immediately
+        write!(self.reader.get_mut(), "cas {} 0 -1 0 {}\r\n\r\n", key, cas)?;
+        self.reader.get_mut().flush()?;
+        match self.parse_store_response() {
+            Ok(t) => Ok(t),
+            Err(MemcacheError::CommandError(e)) if e == CommandError::KeyExists || e == CommandError::KeyNotFound => {
+                Ok(false)
+            }
+            e => e,
+        }
     }
 
     fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
         write!(self.reader.get_mut(), "incr {} {}\r\n", key, amount)?;
+        self.reader.get_mut().flush()?;
         self.parse_u64_response()
     }
 
+    fn increment_noreply(&mut self, key: &str, amount: u64) -> Result<(), MemcacheError> {
+        write!(self.reader.get_mut(), "incr {} {} noreply\r\n", key, amount)?;
+        self.reader.get_mut().flush()?;
+        Ok(())
+    }
+
     fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
         write!(self.reader.get_mut(), "decr {} {}\r\n", key, amount)?;
+        self.reader.get_mut().flush()?;
         self.parse_u64_response()
     }
 
     fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
         write!(self.reader.get_mut(), "touch {} {}\r\n", key, expiration)?;
         self.reader.get_mut().flush()?;
-        self.reader
-            .read_line(|response| match MemcacheError::try_from(response) {
-                Ok(s) => {
-                    if s == "TOUCHED\r\n" {
-                        Ok(true)
-                    } else {
-                        Err(ServerError::BadResponse(Cow::Owned(s.into())).into())
+        self.parse_touch_response()
+    }
+
+    fn touches(&mut self, entries: &[(&str, u32)]) -> Result<Vec<bool>, MemcacheError> {
+        for (key, expiration) in entries {
+            write!(self.reader.get_mut(), "touch {} {}\r\n", key, expiration)?;
+        }
+        self.reader.get_mut().flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Ok(Vec::with_capacity(entries.len()));
+        for _ in entries {
+            match self.parse_touch_response() {
+                Ok(touched) => {
+                    if let Ok(ref mut touches) = result {
+                        touches.push(touched);
                     }
                 }
-                Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
-                Err(e) => Err(e),
-            })
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
     }
 
     fn stats(&mut self) -> Result<Stats, MemcacheError> {
-        self.reader.get_mut().write(b"stats\r\n")?;
+        self.stats_with_arg("")
+    }
+
+    fn stats_with_arg(&mut self, arg: &str) -> Result<Stats, MemcacheError> {
+        if arg.is_empty() {
+            self.reader.get_mut().write_all(b"stats\r\n")?;
+        } else {
+            write!(self.reader.get_mut(), "stats {}\r\n", arg)?;
+        }
         self.reader.get_mut().flush()?;
 
         enum Loop {
@@ -305,20 +552,15 @@ impl ProtocolTrait for AsciiProtocol<Stream> {
         let mut stats: Stats = HashMap::new();
         loop {
             let status = self.reader.read_line(|response| {
-                if response != END {
+                if response == END {
                     return Ok(Loop::Break);
                 }
                 let s = MemcacheError::try_from(response)?;
-                if !s.starts_with("STAT") {
+                let stat: Vec<_> = s.trim_end_matches("\r\n").splitn(3, ' ').collect();
+                if stat.len() < 3 || stat[0] != "STAT" {
                     return Err(ServerError::BadResponse(Cow::Owned(s.into())))?;
                 }
-                let stat: Vec<_> = s.trim_end_matches("\r\n").split(" ").collect();
-                if stat.len() < 3 {
-                    return Err(ServerError::BadResponse(Cow::Owned(s.into())).into());
-                }
-                let key = stat[1];
-                let value = s.trim_start_matches(format!("STAT {}", key).as_str());
-                stats.insert(key.into(), value.into());
+                stats.insert(stat[1].into(), stat[2].into());
 
                 Ok(Loop::Continue)
             })?;
@@ -334,6 +576,7 @@ impl AsciiProtocol<Stream> {
     pub(crate) fn new(stream: Stream) -> Self {
         Self {
             reader: CappedLineReader::new(stream),
+            supports_meta: None,
         }
     }
 
@@ -341,6 +584,77 @@ impl AsciiProtocol<Stream> {
         self.reader.get_mut()
     }
 
+    /// Set `entries` with `noreply`, followed by a `version` command whose reply confirms that the server processed
+    /// them, without reading a reply per entry. memcached only replies to the failed `noreply` commands, and not
+    /// always, so errors may go unnoticed.
+    pub(crate) fn sets_noreply<K, V, I>(&mut self, entries: I) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        for (key, value, expiration) in entries {
+            let options = Options {
+                noreply: true,
+                exptime: expiration,
+                ..Default::default()
+            };
+            self.write_store_command(StoreCommand::Set, key.as_ref(), value, &options)?;
+        }
+        self.reader.get_mut().write_all(b"version\r\n")?;
+        self.reader.get_mut().flush()?;
+
+        // read the error replies until the version reply, to keep the stream in sync
+        let mut result = Ok(());
+        loop {
+            let response = self.reader.read_line(|response| Ok(response.to_string()))?;
+            if response.starts_with("VERSION") {
+                return result;
+            }
+            let error = match MemcacheError::try_from(response.as_str()) {
+                Err(e) => e,
+                Ok(_) => ServerError::BadResponse(Cow::Owned(response)).into(),
+            };
+            result = result.and(Err(error));
+        }
+    }
+
+    /// Get a single key with its cas id with the `gets` command, without collecting it into a map like `gets`.
+    pub(crate) fn gets_single<V: FromMemcacheValueExt>(&mut self, key: &str) -> Result<Option<V>, MemcacheError> {
+        write!(self.reader.get_mut(), "gets {}\r\n", key)?;
+        self.reader.get_mut().flush()?;
+        self.parse_single_get_response(key, true)
+    }
+
+    /// List up to `limit` items of the slab class `slab_id` with the `stats cachedump` command, 0 meaning all of them.
+    pub(crate) fn stats_cachedump(&mut self, slab_id: u32, limit: u32) -> Result<Vec<CachedItem>, MemcacheError> {
+        write!(self.reader.get_mut(), "stats cachedump {} {}\r\n", slab_id, limit)?;
+        self.reader.get_mut().flush()?;
+        let mut items = Vec::new();
+        loop {
+            let item = self.reader.read_line(|response| {
+                let response = MemcacheError::try_from(response)?;
+                if response == END {
+                    return Ok(None);
+                }
+                stats::parse_cachedump_item(response.trim_end_matches("\r\n")).map(Some)
+            })?;
+            match item {
+                Some(item) => items.push(item),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    fn supports_meta(&mut self) -> Result<bool, MemcacheError> {
+        if let Some(supports_meta) = self.supports_meta {
+            return Ok(supports_meta);
+        }
+        let supports_meta = Version::parse(&self.version()?)? >= Version(1, 6, 0);
+        self.supports_meta = Some(supports_meta);
+        Ok(supports_meta)
+    }
+
     fn store<V: ToMemcacheValue<Stream>>(
         &mut self,
         command: StoreCommand,
@@ -348,6 +662,23 @@ impl AsciiProtocol<Stream> {
         value: V,
         options: &Options,
     ) -> Result<bool, MemcacheError> {
+        self.write_store_command(command, key, value, options)?;
+        self.reader.get_mut().flush()?;
+
+        if options.noreply {
+            return Ok(true);
+        }
+
+        self.parse_store_response()
+    }
+
+    fn write_store_command<V: ToMemcacheValue<Stream>>(
+        &mut self,
+        command: StoreCommand,
+        key: &str,
+        value: V,
+        options: &Options,
+    ) -> Result<(), MemcacheError> {
         if command == StoreCommand::Cas {
             if options.cas.is_none() {
                 Err(ClientError::Error(Cow::Borrowed(
@@ -382,13 +713,83 @@ impl AsciiProtocol<Stream> {
         }
 
         value.write_to(self.reader.get_mut())?;
-        self.reader.get_mut().write(b"\r\n")?;
+        self.reader.get_mut().write_all(b"\r\n")?;
+        Ok(())
+    }
+
+    pub(crate) fn pipeline(&mut self, commands: &[PipelineCommand]) -> Result<Vec<PipelineResult>, MemcacheError> {
+        for command in commands {
+            match command {
+                PipelineCommand::Set {
+                    key,
+                    value,
+                    flags,
+                    expiration,
+                } => {
+                    let options = Options {
+                        exptime: *expiration,
+                        ..Default::default()
+                    };
+                    let value: &[u8] = value;
+                    let value = WithFlags {
+                        value: &value,
+                        flags: *flags,
+                    };
+                    self.write_store_command(StoreCommand::Set, key, value, &options)?;
+                }
+                PipelineCommand::Get(key) => write!(self.reader.get_mut(), "get {}\r\n", key)?,
+                PipelineCommand::Delete(key) => write!(self.reader.get_mut(), "delete {}\r\n", key)?,
+            }
+        }
         self.reader.get_mut().flush()?;
 
-        if options.noreply {
-            return Ok(true);
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Vec::with_capacity(commands.len());
+        for command in commands {
+            let reply = match command {
+                PipelineCommand::Set { .. } => self.parse_store_response().map(|_| PipelineReply::Stored),
+                PipelineCommand::Get(key) => self.parse_single_get_response(key, false).map(PipelineReply::Value),
+                PipelineCommand::Delete(_) => self.parse_delete_response().map(PipelineReply::Deleted),
+            };
+            match reply {
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                reply => result.push(reply),
+            }
         }
+        Ok(result)
+    }
+
+    fn parse_delete_response(&mut self) -> Result<bool, MemcacheError> {
+        self.reader
+            .read_line(|response| match MemcacheError::try_from(response) {
+                Ok(s) => {
+                    if s == "DELETED\r\n" {
+                        Ok(true)
+                    } else {
+                        Err(ServerError::BadResponse(Cow::Owned(s.into())).into())
+                    }
+                }
+                Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
+                Err(e) => Err(e),
+            })
+    }
+
+    fn parse_touch_response(&mut self) -> Result<bool, MemcacheError> {
+        self.reader
+            .read_line(|response| match MemcacheError::try_from(response) {
+                Ok(s) => {
+                    if s == "TOUCHED\r\n" {
+                        Ok(true)
+                    } else {
+                        Err(ServerError::BadResponse(Cow::Owned(s.into())).into())
+                    }
+                }
+                Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
+                Err(e) => Err(e),
+            })
+    }
 
+    fn parse_store_response(&mut self) -> Result<bool, MemcacheError> {
         self.reader.read_line(|response| {
             let response = MemcacheError::try_from(response)?;
             match response {
@@ -412,6 +813,47 @@ impl AsciiProtocol<Stream> {
         })
     }
 
+    /// Parse the response of a command retrieving the single `key`.
+    fn parse_single_get_response<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+        has_cas: bool,
+    ) -> Result<Option<V>, MemcacheError> {
+        if let Some((k, v)) = self.parse_get_response(has_cas)? {
+            if k != key {
+                Err(ServerError::BadResponse(Cow::Borrowed(
+                    "key doesn't match in the response",
+                )))?
+            } else if self.parse_get_response::<V>(has_cas)?.is_none() {
+                Ok(Some(v))
+            } else {
+                Err(ServerError::BadResponse(Cow::Borrowed("Expected end of get response")))?
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Parse the response of a command retrieving `keys_count` keys.
+    fn parse_multi_get_response<V: FromMemcacheValueExt>(
+        &mut self,
+        keys_count: usize,
+        has_cas: bool,
+    ) -> Result<HashMap<String, V>, MemcacheError> {
+        let mut result: HashMap<String, V> = HashMap::with_capacity(keys_count);
+        // there will be atmost keys_count "VALUE <...>" responses and one END response
+        for _ in 0..=keys_count {
+            match self.parse_get_response(has_cas)? {
+                Some((key, value)) => {
+                    result.insert(key, value);
+                }
+                None => return Ok(result),
+            }
+        }
+
+        Err(ServerError::BadResponse(Cow::Borrowed("Expected end of gets response")))?
+    }
+
     fn parse_get_response<V: FromMemcacheValueExt>(
         &mut self,
         has_cas: bool,
@@ -424,21 +866,7 @@ impl AsciiProtocol<Stream> {
             if !buf.starts_with("VALUE") {
                 return Err(ServerError::BadResponse(Cow::Owned(buf.into())))?;
             }
-            let mut header = buf.trim_end_matches("\r\n").split(" ");
-            let mut next_or_err = || {
-                header
-                    .next()
-                    .ok_or_else(|| ServerError::BadResponse(Cow::Owned(buf.into())))
-            };
-            let _ = next_or_err()?;
-            let key = next_or_err()?;
-            let flags: u32 = next_or_err()?.parse()?;
-            let length: usize = next_or_err()?.parse()?;
-            let cas: Option<u64> = if has_cas { Some(next_or_err()?.parse()?) } else { None };
-            if header.next().is_some() {
-                return Err(ServerError::BadResponse(Cow::Owned(buf.into())))?;
-            }
-            Ok(Some((key.to_string(), flags, length, cas)))
+            parse_value_header(buf, has_cas).map(Some)
         })?;
         match result {
             Some((key, flags, length, cas)) => {
@@ -489,6 +917,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_meta_value_header() {
+        assert_eq!(parse_meta_value_header("VA 3 f1 c42 t-1\r\n").unwrap(), (3, 1, 42, -1));
+        assert_eq!(parse_meta_value_header("VA 0 t100 c7 f0\r\n").unwrap(), (0, 0, 7, 100));
+        assert!(parse_meta_value_header("VA 3 f1 c42\r\n").is_err());
+        assert!(parse_meta_value_header("HD\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_value_header() {
+        assert_eq!(
+            parse_value_header("VALUE foo 1 3\r\n", false).unwrap(),
+            ("foo".to_string(), 1, 3, None)
+        );
+        assert_eq!(
+            parse_value_header("VALUE  foo 1  3 42 \r\n", true).unwrap(),
+            ("foo".to_string(), 1, 3, Some(42))
+        );
+
+        let missing = parse_value_header("VALUE foo 1\r\n", false).unwrap_err().to_string();
+        assert!(missing.contains("missing bytes token"), "{}", missing);
+        let extra = parse_value_header("VALUE foo 1 3 42\r\n", false)
+            .unwrap_err()
+            .to_string();
+        assert!(extra.contains("unexpected extra token '42'"), "{}", extra);
+        let unicode = parse_value_header("VALUE f\u{f6}\u{f6} 1 3\r\n", false)
+            .unwrap_err()
+            .to_string();
+        assert!(unicode.contains("non-ASCII key"), "{}", unicode);
+    }
+
     #[test]
     fn test_capped_line_reader() {
         let mock_reader = MockReader {
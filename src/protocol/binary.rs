@@ -1,12 +1,18 @@
+#[cfg(feature = "sasl-scram")]
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::Write;
 
-use super::ProtocolTrait;
+#[cfg(feature = "sasl-scram")]
+use super::scram::{self, ScramClient};
+use super::{AddResult, PipelineCommand, PipelineResult, ProtocolTrait, RawValue, ValueWithMetadata};
 use crate::client::Stats;
 use crate::error::MemcacheError;
+#[cfg(feature = "sasl-scram")]
+use crate::error::ServerError;
 use crate::protocol::binary_packet::{self, Magic, Opcode, PacketHeader};
 use crate::stream::Stream;
-use crate::value::{FromMemcacheValueExt, ToMemcacheValue};
+use crate::value::{FromMemcacheValueExt, ToMemcacheValue, WithFlags};
 use byteorder::{BigEndian, WriteBytesExt};
 
 pub struct BinaryProtocol {
@@ -15,18 +21,8 @@ pub struct BinaryProtocol {
 
 impl ProtocolTrait for BinaryProtocol {
     fn auth(&mut self, username: &str, password: &str) -> Result<(), MemcacheError> {
-        let key = "PLAIN";
-        let request_header = PacketHeader {
-            magic: Magic::Request as u8,
-            opcode: Opcode::StartAuth as u8,
-            key_length: key.len() as u16,
-            total_body_length: (key.len() + username.len() + password.len() + 2) as u32,
-            ..Default::default()
-        };
-        request_header.write(&mut self.stream)?;
-        self.stream.write_all(key.as_bytes())?;
-        write!(&mut self.stream, "\x00{}\x00{}", username, password)?;
-        self.stream.flush()?;
+        let credentials = format!("\x00{}\x00{}", username, password);
+        self.send_sasl_request(Opcode::StartAuth, "PLAIN", credentials.as_bytes())?;
         binary_packet::parse_start_auth_response(&mut self.stream).map(|_| ())
     }
 
@@ -50,7 +46,9 @@ impl ProtocolTrait for BinaryProtocol {
         };
         request_header.write(&mut self.stream)?;
         self.stream.flush()?;
-        binary_packet::parse_response(&mut self.stream)?.err().map(|_| ())
+        binary_packet::parse_response(&mut self.stream)?
+            .check_status()
+            .map(|_| ())
     }
 
     fn flush_with_delay(&mut self, delay: u32) -> Result<(), MemcacheError> {
@@ -64,7 +62,9 @@ impl ProtocolTrait for BinaryProtocol {
         request_header.write(&mut self.stream)?;
         self.stream.write_u32::<BigEndian>(delay)?;
         self.stream.flush()?;
-        binary_packet::parse_response(&mut self.stream)?.err().map(|_| ())
+        binary_packet::parse_response(&mut self.stream)?
+            .check_status()
+            .map(|_| ())
     }
 
     fn get<V: FromMemcacheValueExt>(&mut self, key: &str) -> Result<Option<V>, MemcacheError> {
@@ -82,16 +82,8 @@ impl ProtocolTrait for BinaryProtocol {
     }
 
     fn gets<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
-        for key in keys {
-            let request_header = PacketHeader {
-                magic: Magic::Request as u8,
-                opcode: Opcode::GetKQ as u8,
-                key_length: key.len() as u16,
-                total_body_length: key.len() as u32,
-                ..Default::default()
-            };
-            request_header.write(&mut self.stream)?;
-            self.stream.write_all(key.as_bytes())?;
+        for (index, key) in keys.iter().enumerate() {
+            self.get_quiet(key, index as u32)?;
         }
         let noop_request_header = PacketHeader {
             magic: Magic::Request as u8,
@@ -99,7 +91,51 @@ impl ProtocolTrait for BinaryProtocol {
             ..Default::default()
         };
         noop_request_header.write(&mut self.stream)?;
-        return binary_packet::parse_gets_response(&mut self.stream, keys.len());
+        self.stream.flush()?;
+        return binary_packet::parse_gets_quiet_response(&mut self.stream, keys);
+    }
+
+    fn get_and_touch<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+        expiration: u32,
+    ) -> Result<Option<V>, MemcacheError> {
+        self.write_expiration_request(Opcode::Gat, key, expiration)?;
+        self.stream.flush()?;
+        return binary_packet::parse_get_response(&mut self.stream);
+    }
+
+    fn get_with_metadata<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<ValueWithMetadata<V>>, MemcacheError> {
+        // the binary protocol can't report the time to live
+        match self.get::<RawValue>(key)? {
+            Some((value, flags, cas)) => {
+                let cas = cas.unwrap_or_default();
+                Ok(Some((
+                    V::from_memcache_value(value, flags, Some(cas))?,
+                    flags,
+                    cas,
+                    None,
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn exists_batch(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        for (index, key) in keys.iter().enumerate() {
+            self.get_quiet(key, index as u32)?;
+        }
+        let noop_request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        noop_request_header.write(&mut self.stream)?;
+        self.stream.flush()?;
+        return binary_packet::parse_exists_quiet_response(&mut self.stream, keys.len());
     }
 
     fn cas<V: ToMemcacheValue<Stream>>(
@@ -117,10 +153,50 @@ impl ProtocolTrait for BinaryProtocol {
         return self.store(Opcode::Set, key, value, expiration, None);
     }
 
+    fn sets<K, V, I>(&mut self, entries: I) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        for (key, value, expiration) in entries {
+            self.write_request(Opcode::SetQ, key.as_ref(), value, expiration, None, 0)?;
+        }
+        let noop_request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        noop_request_header.write(&mut self.stream)?;
+        self.stream.flush()?;
+        binary_packet::parse_quiet_responses(&mut self.stream)
+    }
+
     fn add<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
         return self.store(Opcode::Add, key, value, expiration, None);
     }
 
+    fn adds<K, V, I>(&mut self, entries: I) -> Result<Vec<AddResult>, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let mut count = 0;
+        for (key, value, expiration) in entries {
+            self.write_request(Opcode::AddQ, key.as_ref(), value, expiration, None, count)?;
+            count += 1;
+        }
+        let noop_request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        noop_request_header.write(&mut self.stream)?;
+        self.stream.flush()?;
+        binary_packet::parse_add_quiet_responses(&mut self.stream, count as usize)
+    }
+
     fn replace<V: ToMemcacheValue<Stream>>(
         &mut self,
         key: &str,
@@ -131,76 +207,287 @@ impl ProtocolTrait for BinaryProtocol {
     }
 
     fn append<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        self.write_concat_request(Opcode::Append, key, value)?;
+        self.stream.flush()?;
+        binary_packet::parse_response(&mut self.stream)?
+            .check_status()
+            .map(|_| ())
+    }
+
+    fn prepend<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        self.write_concat_request(Opcode::Prepend, key, value)?;
+        self.stream.flush()?;
+        binary_packet::parse_response(&mut self.stream)?
+            .check_status()
+            .map(|_| ())
+    }
+
+    fn append_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        self.write_concat_request(Opcode::AppendQ, key, value)?;
+        self.skip_quiet_responses()
+    }
+
+    fn prepend_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        self.write_concat_request(Opcode::PrependQ, key, value)?;
+        self.skip_quiet_responses()
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Append as u8,
+            opcode: Opcode::Delete as u8,
             key_length: key.len() as u16,
-            total_body_length: (key.len() + value.get_length()) as u32,
+            total_body_length: key.len() as u32,
             ..Default::default()
         };
         request_header.write(&mut self.stream)?;
         self.stream.write_all(key.as_bytes())?;
-        value.write_to(&mut self.stream)?;
         self.stream.flush()?;
-        binary_packet::parse_response(&mut self.stream)?.err().map(|_| ())
+        return binary_packet::parse_delete_response(&mut self.stream);
     }
 
-    fn prepend<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+    fn deletes(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        for key in keys {
+            let request_header = PacketHeader {
+                magic: Magic::Request as u8,
+                opcode: Opcode::Delete as u8,
+                key_length: key.len() as u16,
+                total_body_length: key.len() as u32,
+                ..Default::default()
+            };
+            request_header.write(&mut self.stream)?;
+            self.stream.write_all(key.as_bytes())?;
+        }
+        self.stream.flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Ok(Vec::with_capacity(keys.len()));
+        for _ in keys {
+            match binary_packet::parse_delete_response(&mut self.stream) {
+                Ok(deleted) => {
+                    if let Ok(ref mut deletes) = result {
+                        deletes.push(deleted);
+                    }
+                }
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+
+    fn deletes_noreply(&mut self, keys: &[&str]) -> Result<(), MemcacheError> {
+        for key in keys {
+            let request_header = PacketHeader {
+                magic: Magic::Request as u8,
+                opcode: Opcode::DeleteQ as u8,
+                key_length: key.len() as u16,
+                total_body_length: key.len() as u32,
+                ..Default::default()
+            };
+            request_header.write(&mut self.stream)?;
+            self.stream.write_all(key.as_bytes())?;
+        }
+        self.skip_quiet_responses()
+    }
+
+    fn compare_and_delete(&mut self, key: &str, cas: u64) -> Result<bool, MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Prepend as u8,
+            opcode: Opcode::Delete as u8,
             key_length: key.len() as u16,
-            total_body_length: (key.len() + value.get_length()) as u32,
+            total_body_length: key.len() as u32,
+            cas,
             ..Default::default()
         };
         request_header.write(&mut self.stream)?;
         self.stream.write_all(key.as_bytes())?;
-        value.write_to(&mut self.stream)?;
         self.stream.flush()?;
-        binary_packet::parse_response(&mut self.stream).map(|_| ())
+        return binary_packet::parse_cas_response(&mut self.stream);
     }
 
-    fn delete(&mut self, key: &str) -> Result<bool, MemcacheError> {
+    fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        self.write_counter_request(Opcode::Increment, key, amount, 0, 0)?;
+        self.stream.flush()?;
+        return binary_packet::parse_counter_response(&mut self.stream);
+    }
+
+    fn increment_noreply(&mut self, key: &str, amount: u64) -> Result<(), MemcacheError> {
+        self.increment_quiet(key, amount, 0, 0)?;
+        self.skip_quiet_responses()
+    }
+
+    fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        self.write_counter_request(Opcode::Decrement, key, amount, 0, 0)?;
+        self.stream.flush()?;
+        return binary_packet::parse_counter_response(&mut self.stream);
+    }
+
+    fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+        self.write_expiration_request(Opcode::Touch, key, expiration)?;
+        self.stream.flush()?;
+        return binary_packet::parse_touch_response(&mut self.stream);
+    }
+
+    fn touches(&mut self, entries: &[(&str, u32)]) -> Result<Vec<bool>, MemcacheError> {
+        for (key, expiration) in entries {
+            self.write_expiration_request(Opcode::Touch, key, *expiration)?;
+        }
+        self.stream.flush()?;
+
+        // read every response even if some of them failed, to keep the stream in sync
+        let mut result = Ok(Vec::with_capacity(entries.len()));
+        for _ in entries {
+            match binary_packet::parse_touch_response(&mut self.stream) {
+                Ok(touched) => {
+                    if let Ok(ref mut touches) = result {
+                        touches.push(touched);
+                    }
+                }
+                Err(MemcacheError::IOError(e)) => return Err(MemcacheError::IOError(e)),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result
+    }
+
+    fn stats(&mut self) -> Result<Stats, MemcacheError> {
+        return self.stats_with_arg("");
+    }
+
+    fn stats_with_arg(&mut self, arg: &str) -> Result<Stats, MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Delete as u8,
-            key_length: key.len() as u16,
-            total_body_length: key.len() as u32,
+            opcode: Opcode::Stat as u8,
+            key_length: arg.len() as u16,
+            total_body_length: arg.len() as u32,
             ..Default::default()
         };
         request_header.write(&mut self.stream)?;
-        self.stream.write_all(key.as_bytes())?;
+        self.stream.write_all(arg.as_bytes())?;
         self.stream.flush()?;
-        return binary_packet::parse_delete_response(&mut self.stream);
+        let stats_info = binary_packet::parse_stats_response(&mut self.stream)?;
+        return Ok(stats_info);
     }
+}
 
-    fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+impl BinaryProtocol {
+    /// Get the SASL mechanisms supported by the server, like `PLAIN` or `SCRAM-SHA-1`.
+    #[cfg(feature = "sasl-scram")]
+    pub fn list_sasl_mechanisms(&mut self) -> Result<Vec<String>, MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Increment as u8,
-            key_length: key.len() as u16,
-            extras_length: 20,
-            total_body_length: (20 + key.len()) as u32,
+            opcode: Opcode::ListSaslMechanisms as u8,
             ..Default::default()
         };
-        let extras = binary_packet::CounterExtras {
-            amount,
-            initial_value: 0,
-            expiration: 0,
+        request_header.write(&mut self.stream)?;
+        self.stream.flush()?;
+        binary_packet::parse_list_sasl_mechanisms_response(&mut self.stream)
+    }
+
+    /// Authenticate with the SCRAM-SHA-1 SASL mechanism, which doesn't send the password to the server, and also
+    /// verifies that the server knows the password.
+    #[cfg(feature = "sasl-scram")]
+    pub fn auth_scram(&mut self, username: &str, password: &str) -> Result<(), MemcacheError> {
+        let mut scram = ScramClient::new(username, password);
+        self.send_sasl_request(Opcode::StartAuth, scram::MECHANISM, scram.client_first().as_bytes())?;
+        let server_first = match binary_packet::parse_auth_step_response(&mut self.stream)? {
+            (true, server_first) => server_first,
+            (false, _) => Err(ServerError::BadResponse(Cow::Borrowed(
+                "expected a SCRAM challenge from the server",
+            )))?,
+        };
+        let client_final = scram.client_final(&server_first)?;
+        self.send_sasl_request(Opcode::StepAuth, scram::MECHANISM, client_final.as_bytes())?;
+        match binary_packet::parse_auth_step_response(&mut self.stream)? {
+            (false, server_final) => scram.verify_server_final(&server_final),
+            (true, _) => Err(ServerError::BadResponse(Cow::Borrowed(
+                "unexpected SCRAM challenge after the final message",
+            )))?,
+        }
+    }
+
+    fn send_sasl_request(&mut self, opcode: Opcode, mechanism: &str, data: &[u8]) -> Result<(), MemcacheError> {
+        let request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: opcode as u8,
+            key_length: mechanism.len() as u16,
+            total_body_length: (mechanism.len() + data.len()) as u32,
+            ..Default::default()
         };
         request_header.write(&mut self.stream)?;
-        self.stream.write_u64::<BigEndian>(extras.amount)?;
-        self.stream.write_u64::<BigEndian>(extras.initial_value)?;
-        self.stream.write_u32::<BigEndian>(extras.expiration)?;
-        self.stream.write_all(key.as_bytes())?;
+        self.stream.write_all(mechanism.as_bytes())?;
+        self.stream.write_all(data)?;
         self.stream.flush()?;
-        return binary_packet::parse_counter_response(&mut self.stream);
+        Ok(())
     }
 
-    fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+    /// Send `commands` with quiet opcodes followed by a `Noop`, so that the server only responds to hits and errors.
+    pub(crate) fn pipeline(&mut self, commands: &[PipelineCommand]) -> Result<Vec<PipelineResult>, MemcacheError> {
+        for (index, command) in commands.iter().enumerate() {
+            let opaque = index as u32;
+            match command {
+                PipelineCommand::Set {
+                    key,
+                    value,
+                    flags,
+                    expiration,
+                } => {
+                    let value: &[u8] = value;
+                    let value = WithFlags {
+                        value: &value,
+                        flags: *flags,
+                    };
+                    self.write_request(Opcode::SetQ, key, value, *expiration, None, opaque)?;
+                }
+                PipelineCommand::Get(key) => self.get_quiet(key, opaque)?,
+                PipelineCommand::Delete(key) => {
+                    let request_header = PacketHeader {
+                        magic: Magic::Request as u8,
+                        opcode: Opcode::DeleteQ as u8,
+                        key_length: key.len() as u16,
+                        total_body_length: key.len() as u32,
+                        opaque,
+                        ..Default::default()
+                    };
+                    request_header.write(&mut self.stream)?;
+                    self.stream.write_all(key.as_bytes())?;
+                }
+            }
+        }
+        let noop_request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        noop_request_header.write(&mut self.stream)?;
+        self.stream.flush()?;
+        binary_packet::parse_pipeline_quiet_responses(&mut self.stream, commands)
+    }
+
+    /// Write an `IncrQ` request without flushing, e.g. to batch several of them. A missing key is created with
+    /// `initial` unless `expiration` is `0xFFFFFFFF`, and the server only responds on errors.
+    pub(crate) fn increment_quiet(
+        &mut self,
+        key: &str,
+        amount: u64,
+        initial: u64,
+        expiration: u32,
+    ) -> Result<(), MemcacheError> {
+        self.write_counter_request(Opcode::IncrementQ, key, amount, initial, expiration)
+    }
+
+    fn write_counter_request(
+        &mut self,
+        opcode: Opcode,
+        key: &str,
+        amount: u64,
+        initial_value: u64,
+        expiration: u32,
+    ) -> Result<(), MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Decrement as u8,
+            opcode: opcode as u8,
             key_length: key.len() as u16,
             extras_length: 20,
             total_body_length: (20 + key.len()) as u32,
@@ -208,48 +495,96 @@ impl ProtocolTrait for BinaryProtocol {
         };
         let extras = binary_packet::CounterExtras {
             amount,
-            initial_value: 0,
-            expiration: 0,
+            initial_value,
+            expiration,
         };
         request_header.write(&mut self.stream)?;
         self.stream.write_u64::<BigEndian>(extras.amount)?;
         self.stream.write_u64::<BigEndian>(extras.initial_value)?;
         self.stream.write_u32::<BigEndian>(extras.expiration)?;
         self.stream.write_all(key.as_bytes())?;
+        Ok(())
+    }
+
+    /// Send a `Noop` request, which is answered with a bare header, e.g. to check that the connection is alive.
+    pub(crate) fn noop(&mut self) -> Result<(), MemcacheError> {
+        let request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        request_header.write(&mut self.stream)?;
         self.stream.flush()?;
-        return binary_packet::parse_counter_response(&mut self.stream);
+        binary_packet::parse_noop_response(&mut self.stream)
     }
 
-    fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+    /// Write a `GetQ` request without flushing. The server only responds on a hit, and the response carries `opaque`
+    /// instead of the key, which saves the key bytes compared to `GetKQ`.
+    fn get_quiet(&mut self, key: &str, opaque: u32) -> Result<(), MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Touch as u8,
+            opcode: Opcode::GetQ as u8,
             key_length: key.len() as u16,
-            extras_length: 4,
-            total_body_length: (key.len() as u32 + 4),
+            total_body_length: key.len() as u32,
+            opaque,
             ..Default::default()
         };
         request_header.write(&mut self.stream)?;
-        self.stream.write_u32::<BigEndian>(expiration)?;
         self.stream.write_all(key.as_bytes())?;
-        self.stream.flush()?;
-        return binary_packet::parse_touch_response(&mut self.stream);
+        Ok(())
     }
 
-    fn stats(&mut self) -> Result<Stats, MemcacheError> {
+    fn write_concat_request<V: ToMemcacheValue<Stream>>(
+        &mut self,
+        opcode: Opcode,
+        key: &str,
+        value: V,
+    ) -> Result<(), MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
-            opcode: Opcode::Stat as u8,
+            opcode: opcode as u8,
+            key_length: key.len() as u16,
+            total_body_length: (key.len() + value.get_length()) as u32,
             ..Default::default()
         };
         request_header.write(&mut self.stream)?;
+        self.stream.write_all(key.as_bytes())?;
+        value.write_to(&mut self.stream)?;
+        Ok(())
+    }
+
+    /// Terminate the quiet requests written before with a `Noop`, and read through the responses of the failed ones,
+    /// which aren't reported, so that they aren't read as the response of the next command on this connection.
+    fn skip_quiet_responses(&mut self) -> Result<(), MemcacheError> {
+        let noop_request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: Opcode::Noop as u8,
+            ..Default::default()
+        };
+        noop_request_header.write(&mut self.stream)?;
         self.stream.flush()?;
-        let stats_info = binary_packet::parse_stats_response(&mut self.stream)?;
-        return Ok(stats_info);
+        match binary_packet::parse_quiet_responses(&mut self.stream) {
+            Err(MemcacheError::CommandError(_)) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Write a request whose only extras are the expiration, like `Touch` and `Gat`.
+    fn write_expiration_request(&mut self, opcode: Opcode, key: &str, expiration: u32) -> Result<(), MemcacheError> {
+        let request_header = PacketHeader {
+            magic: Magic::Request as u8,
+            opcode: opcode as u8,
+            key_length: key.len() as u16,
+            extras_length: 4,
+            total_body_length: (key.len() as u32 + 4),
+            ..Default::default()
+        };
+        request_header.write(&mut self.stream)?;
+        self.stream.write_u32::<BigEndian>(expiration)?;
+        self.stream.write_all(key.as_bytes())?;
+        Ok(())
     }
-}
 
-impl BinaryProtocol {
     fn send_request<V: ToMemcacheValue<Stream>>(
         &mut self,
         opcode: Opcode,
@@ -257,6 +592,19 @@ impl BinaryProtocol {
         value: V,
         expiration: u32,
         cas: Option<u64>,
+    ) -> Result<(), MemcacheError> {
+        self.write_request(opcode, key, value, expiration, cas, 0)?;
+        self.stream.flush().map_err(Into::into)
+    }
+
+    fn write_request<V: ToMemcacheValue<Stream>>(
+        &mut self,
+        opcode: Opcode,
+        key: &str,
+        value: V,
+        expiration: u32,
+        cas: Option<u64>,
+        opaque: u32,
     ) -> Result<(), MemcacheError> {
         let request_header = PacketHeader {
             magic: Magic::Request as u8,
@@ -265,6 +613,7 @@ impl BinaryProtocol {
             extras_length: 8,
             total_body_length: (8 + key.len() + value.get_length()) as u32,
             cas: cas.unwrap_or(0),
+            opaque,
             ..Default::default()
         };
         let extras = binary_packet::StoreExtras {
@@ -275,8 +624,7 @@ impl BinaryProtocol {
         self.stream.write_u32::<BigEndian>(extras.flags)?;
         self.stream.write_u32::<BigEndian>(extras.expiration)?;
         self.stream.write_all(key.as_bytes())?;
-        value.write_to(&mut self.stream)?;
-        self.stream.flush().map_err(Into::into)
+        value.write_to(&mut self.stream).map_err(Into::into)
     }
 
     fn store<V: ToMemcacheValue<Stream>>(
@@ -288,6 +636,8 @@ impl BinaryProtocol {
         cas: Option<u64>,
     ) -> Result<(), MemcacheError> {
         self.send_request(opcode, key, value, expiration, cas)?;
-        binary_packet::parse_response(&mut self.stream)?.err().map(|_| ())
+        binary_packet::parse_response(&mut self.stream)?
+            .check_status()
+            .map(|_| ())
     }
 }
@@ -1,4 +1,5 @@
 use crate::error::{CommandError, MemcacheError, ServerError};
+use crate::protocol::{AddResult, PipelineCommand, PipelineReply, PipelineResult};
 use crate::value::FromMemcacheValueExt;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::borrow::Cow;
@@ -6,6 +7,8 @@ use std::collections::HashMap;
 use std::io::{self, Cursor};
 
 const OK_STATUS: u16 = 0x0;
+#[cfg(feature = "sasl-scram")]
+const AUTH_CONTINUE_STATUS: u16 = 0x21;
 
 #[allow(dead_code)]
 pub enum Opcode {
@@ -14,17 +17,27 @@ pub enum Opcode {
     Add = 0x02,
     Replace = 0x03,
     Delete = 0x04,
+    DeleteQ = 0x14,
     Increment = 0x05,
     Decrement = 0x06,
+    IncrementQ = 0x15,
     Flush = 0x08,
+    GetQ = 0x09,
     Stat = 0x10,
+    SetQ = 0x11,
+    AddQ = 0x12,
     Noop = 0x0a,
     Version = 0x0b,
     GetKQ = 0x0d,
     Append = 0x0e,
     Prepend = 0x0f,
+    AppendQ = 0x19,
+    PrependQ = 0x1a,
     Touch = 0x1c,
+    Gat = 0x1d,
+    ListSaslMechanisms = 0x20,
     StartAuth = 0x21,
+    StepAuth = 0x22,
 }
 
 pub enum Magic {
@@ -100,7 +113,8 @@ pub struct Response {
 }
 
 impl Response {
-    pub(crate) fn err(self) -> Result<Self, MemcacheError> {
+    /// Return the response if its status is OK, or the error matching its status.
+    pub(crate) fn check_status(self) -> Result<Self, MemcacheError> {
         let status = self.header.vbucket_id_or_status;
         if status == OK_STATUS {
             Ok(self)
@@ -132,7 +146,7 @@ pub fn parse_response<R: io::Read>(reader: &mut R) -> Result<Response, MemcacheE
 }
 
 pub fn parse_cas_response<R: io::Read>(reader: &mut R) -> Result<bool, MemcacheError> {
-    match parse_response(reader)?.err() {
+    match parse_response(reader)?.check_status() {
         Err(MemcacheError::CommandError(e)) if e == CommandError::KeyNotFound || e == CommandError::KeyExists => {
             Ok(false)
         }
@@ -141,13 +155,35 @@ pub fn parse_cas_response<R: io::Read>(reader: &mut R) -> Result<bool, MemcacheE
     }
 }
 
+pub fn parse_noop_response<R: io::Read>(reader: &mut R) -> Result<(), MemcacheError> {
+    let mut response = parse_response(reader)?;
+    // skip the failures of earlier noreply requests, which were sent without reading their responses
+    let quiet_opcodes = [
+        Opcode::DeleteQ as u8,
+        Opcode::AppendQ as u8,
+        Opcode::PrependQ as u8,
+        Opcode::IncrementQ as u8,
+    ];
+    while quiet_opcodes.contains(&response.header.opcode) {
+        response = parse_response(reader)?;
+    }
+    let Response { header, .. } = response.check_status()?;
+    if header.opcode != Opcode::Noop as u8 {
+        Err(ServerError::BadResponse(Cow::Owned(format!(
+            "expected a noop response, got opcode {:#04x}",
+            header.opcode
+        ))))?
+    }
+    Ok(())
+}
+
 pub fn parse_version_response<R: io::Read>(reader: &mut R) -> Result<String, MemcacheError> {
-    let Response { value, .. } = parse_response(reader)?.err()?;
+    let Response { value, .. } = parse_response(reader)?.check_status()?;
     Ok(String::from_utf8(value)?)
 }
 
 pub fn parse_get_response<R: io::Read, V: FromMemcacheValueExt>(reader: &mut R) -> Result<Option<V>, MemcacheError> {
-    match parse_response(reader)?.err() {
+    match parse_response(reader)?.check_status() {
         Ok(Response {
             header, extras, value, ..
         }) => {
@@ -163,23 +199,28 @@ pub fn parse_get_response<R: io::Read, V: FromMemcacheValueExt>(reader: &mut R)
     }
 }
 
-pub fn parse_gets_response<R: io::Read, V: FromMemcacheValueExt>(
+/// Read the responses of pipelined `GetQ` requests until the terminating `Noop` response. The responses don't
+/// contain the key, so it's looked up from `keys` by the opaque value, which is the index of the requested key.
+pub fn parse_gets_quiet_response<R: io::Read, V: FromMemcacheValueExt>(
     reader: &mut R,
-    max_responses: usize,
+    keys: &[&str],
 ) -> Result<HashMap<String, V>, MemcacheError> {
     let mut result = HashMap::new();
-    for _ in 0..=max_responses {
+    for _ in 0..=keys.len() {
         let Response {
-            header,
-            key,
-            extras,
-            value,
-        } = parse_response(reader)?.err()?;
+            header, extras, value, ..
+        } = parse_response(reader)?.check_status()?;
         if header.opcode == Opcode::Noop as u8 {
             return Ok(result);
         }
+        let key = match keys.get(header.opaque as usize) {
+            Some(key) => key.to_string(),
+            None => Err(ServerError::BadResponse(Cow::Owned(format!(
+                "Unexpected opaque value {} in gets response",
+                header.opaque
+            ))))?,
+        };
         let flags = Cursor::new(extras).read_u32::<BigEndian>()?;
-        let key = String::from_utf8(key)?;
         result.insert(
             key,
             FromMemcacheValueExt::from_memcache_value(value, flags, Some(header.cas))?,
@@ -188,8 +229,117 @@ pub fn parse_gets_response<R: io::Read, V: FromMemcacheValueExt>(
     Err(ServerError::BadResponse(Cow::Borrowed("Expected end of gets response")))?
 }
 
+/// Like `parse_gets_quiet_response`, but only record which of the `keys_count` requested keys were hits, indexed by
+/// the opaque value.
+pub fn parse_exists_quiet_response<R: io::Read>(reader: &mut R, keys_count: usize) -> Result<Vec<bool>, MemcacheError> {
+    let mut result = vec![false; keys_count];
+    for _ in 0..=keys_count {
+        let Response { header, .. } = parse_response(reader)?.check_status()?;
+        if header.opcode == Opcode::Noop as u8 {
+            return Ok(result);
+        }
+        match result.get_mut(header.opaque as usize) {
+            Some(exists) => *exists = true,
+            None => Err(ServerError::BadResponse(Cow::Owned(format!(
+                "Unexpected opaque value {} in gets response",
+                header.opaque
+            ))))?,
+        }
+    }
+    Err(ServerError::BadResponse(Cow::Borrowed("Expected end of gets response")))?
+}
+
+/// Read the responses of pipelined quiet requests until the terminating `Noop` response,
+/// returning the first error reported by the server.
+pub fn parse_quiet_responses<R: io::Read>(reader: &mut R) -> Result<(), MemcacheError> {
+    let mut result = Ok(());
+    loop {
+        let response = parse_response(reader)?;
+        if response.header.opcode == Opcode::Noop as u8 {
+            return result;
+        }
+        result = result.and(response.check_status().map(|_| ()));
+    }
+}
+
+/// Read the responses of `count` pipelined `AddQ` requests until the terminating `Noop` response. Only the failed
+/// requests have a response, which is matched to the request by the opaque value, the index of the request.
+pub fn parse_add_quiet_responses<R: io::Read>(reader: &mut R, count: usize) -> Result<Vec<AddResult>, MemcacheError> {
+    let mut result: Vec<AddResult> = (0..count).map(|_| Ok(true)).collect();
+    for _ in 0..=count {
+        let response = parse_response(reader)?;
+        if response.header.opcode == Opcode::Noop as u8 {
+            return Ok(result);
+        }
+        let added = match result.get_mut(response.header.opaque as usize) {
+            Some(added) => added,
+            None => Err(ServerError::BadResponse(Cow::Owned(format!(
+                "Unexpected opaque value {} in add response",
+                response.header.opaque
+            ))))?,
+        };
+        *added = match response.check_status() {
+            Ok(_) => Ok(true),
+            Err(MemcacheError::CommandError(CommandError::KeyExists)) => Ok(false),
+            Err(e) => Err(e),
+        };
+    }
+    Err(ServerError::BadResponse(Cow::Borrowed("Expected end of add response")))?
+}
+
+/// Parse the responses to the quiet requests of a pipeline, indexed by the opaque value. Commands without a response
+/// succeeded: sets were stored, gets missed and deletes found their key.
+pub fn parse_pipeline_quiet_responses<R: io::Read>(
+    reader: &mut R,
+    commands: &[PipelineCommand],
+) -> Result<Vec<PipelineResult>, MemcacheError> {
+    let mut result: Vec<PipelineResult> = commands
+        .iter()
+        .map(|command| match command {
+            PipelineCommand::Set { .. } => Ok(PipelineReply::Stored),
+            PipelineCommand::Get(_) => Ok(PipelineReply::Value(None)),
+            PipelineCommand::Delete(_) => Ok(PipelineReply::Deleted(true)),
+        })
+        .collect();
+    for _ in 0..=commands.len() {
+        let response = parse_response(reader)?;
+        if response.header.opcode == Opcode::Noop as u8 {
+            return Ok(result);
+        }
+        let index = response.header.opaque as usize;
+        let (command, reply) = match (commands.get(index), result.get_mut(index)) {
+            (Some(command), Some(reply)) => (command, reply),
+            _ => Err(ServerError::BadResponse(Cow::Owned(format!(
+                "Unexpected opaque value {} in pipeline response",
+                response.header.opaque
+            ))))?,
+        };
+        *reply = match (command, response.check_status()) {
+            (
+                PipelineCommand::Get(_),
+                Ok(Response {
+                    header, extras, value, ..
+                }),
+            ) => {
+                let flags = Cursor::new(extras).read_u32::<BigEndian>()?;
+                Ok(PipelineReply::Value(Some((value, flags, Some(header.cas)))))
+            }
+            (PipelineCommand::Delete(_), Err(MemcacheError::CommandError(CommandError::KeyNotFound))) => {
+                Ok(PipelineReply::Deleted(false))
+            }
+            (_, Ok(_)) => Err(ServerError::BadResponse(Cow::Borrowed(
+                "Unexpected response to a quiet request",
+            )))?,
+            (_, Err(e)) => Err(e),
+        };
+    }
+    Err(ServerError::BadResponse(Cow::Borrowed(
+        "Expected end of pipeline response",
+    )))?
+}
+
 pub fn parse_delete_response<R: io::Read>(reader: &mut R) -> Result<bool, MemcacheError> {
-    match parse_response(reader)?.err() {
+    match parse_response(reader)?.check_status() {
         Ok(_) => Ok(true),
         Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
         Err(e) => Err(e),
@@ -197,12 +347,12 @@ pub fn parse_delete_response<R: io::Read>(reader: &mut R) -> Result<bool, Memcac
 }
 
 pub fn parse_counter_response<R: io::Read>(reader: &mut R) -> Result<u64, MemcacheError> {
-    let Response { value, .. } = parse_response(reader)?.err()?;
+    let Response { value, .. } = parse_response(reader)?.check_status()?;
     Ok(Cursor::new(value).read_u64::<BigEndian>()?)
 }
 
 pub fn parse_touch_response<R: io::Read>(reader: &mut R) -> Result<bool, MemcacheError> {
-    match parse_response(reader)?.err() {
+    match parse_response(reader)?.check_status() {
         Ok(_) => Ok(true),
         Err(MemcacheError::CommandError(CommandError::KeyNotFound)) => Ok(false),
         Err(e) => Err(e),
@@ -212,7 +362,7 @@ pub fn parse_touch_response<R: io::Read>(reader: &mut R) -> Result<bool, Memcach
 pub fn parse_stats_response<R: io::Read>(reader: &mut R) -> Result<HashMap<String, String>, MemcacheError> {
     let mut result = HashMap::new();
     loop {
-        let Response { key, value, .. } = parse_response(reader)?.err()?;
+        let Response { key, value, .. } = parse_response(reader)?.check_status()?;
         let key = String::from_utf8(key)?;
         let value = String::from_utf8(value)?;
         if key.is_empty() && value.is_empty() {
@@ -223,6 +373,22 @@ pub fn parse_stats_response<R: io::Read>(reader: &mut R) -> Result<HashMap<Strin
     Ok(result)
 }
 
+#[cfg(feature = "sasl-scram")]
+pub fn parse_list_sasl_mechanisms_response<R: io::Read>(reader: &mut R) -> Result<Vec<String>, MemcacheError> {
+    let Response { value, .. } = parse_response(reader)?.check_status()?;
+    Ok(String::from_utf8(value)?.split_whitespace().map(String::from).collect())
+}
+
+/// Parse the response of a SASL start or step request, which is the server's challenge and whether the exchange
+/// continues with another step.
+#[cfg(feature = "sasl-scram")]
+pub fn parse_auth_step_response<R: io::Read>(reader: &mut R) -> Result<(bool, String), MemcacheError> {
+    let response = parse_response(reader)?;
+    let more = response.header.vbucket_id_or_status == AUTH_CONTINUE_STATUS;
+    let Response { value, .. } = if more { response } else { response.check_status()? };
+    Ok((more, String::from_utf8(value)?))
+}
+
 pub fn parse_start_auth_response<R: io::Read>(reader: &mut R) -> Result<bool, MemcacheError> {
-    parse_response(reader)?.err().map(|_| true)
+    parse_response(reader)?.check_status().map(|_| true)
 }
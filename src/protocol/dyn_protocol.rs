@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use super::ProtocolTrait;
+use crate::client::Stats;
+use crate::error::MemcacheError;
+use crate::value::WithFlags;
+
+/// A value as stored in memcached: the raw bytes, the flags, and the cas id if the server returned one.
+pub type RawValue = (Vec<u8>, u32, Option<u64>);
+
+/// An object safe version of `ProtocolTrait`, which works on raw bytes and flags instead of generic values, so that
+/// it can be used as `Box<dyn DynProtocol>`, e.g. to pick a protocol at runtime or to plug in a mock implementation.
+///
+/// Every `ProtocolTrait` implementation is also a `DynProtocol`.
+pub trait DynProtocol {
+    fn auth(&mut self, username: &str, password: &str) -> Result<(), MemcacheError>;
+    fn version(&mut self) -> Result<String, MemcacheError>;
+    fn flush(&mut self) -> Result<(), MemcacheError>;
+    fn flush_with_delay(&mut self, delay: u32) -> Result<(), MemcacheError>;
+    fn get_raw(&mut self, key: &str) -> Result<Option<RawValue>, MemcacheError>;
+    fn gets_raw(&mut self, keys: &[&str]) -> Result<HashMap<String, RawValue>, MemcacheError>;
+    fn set_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn cas_raw(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        flags: u32,
+        expiration: u32,
+        cas: u64,
+    ) -> Result<bool, MemcacheError>;
+    fn add_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn replace_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError>;
+    fn append_raw(&mut self, key: &str, value: &[u8]) -> Result<(), MemcacheError>;
+    fn prepend_raw(&mut self, key: &str, value: &[u8]) -> Result<(), MemcacheError>;
+    fn delete(&mut self, key: &str) -> Result<bool, MemcacheError>;
+    fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
+    fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
+    fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError>;
+    fn stats(&mut self) -> Result<Stats, MemcacheError>;
+}
+
+impl<P: ProtocolTrait> DynProtocol for P {
+    fn auth(&mut self, username: &str, password: &str) -> Result<(), MemcacheError> {
+        ProtocolTrait::auth(self, username, password)
+    }
+
+    fn version(&mut self) -> Result<String, MemcacheError> {
+        ProtocolTrait::version(self)
+    }
+
+    fn flush(&mut self) -> Result<(), MemcacheError> {
+        ProtocolTrait::flush(self)
+    }
+
+    fn flush_with_delay(&mut self, delay: u32) -> Result<(), MemcacheError> {
+        ProtocolTrait::flush_with_delay(self, delay)
+    }
+
+    fn get_raw(&mut self, key: &str) -> Result<Option<RawValue>, MemcacheError> {
+        ProtocolTrait::get(self, key)
+    }
+
+    fn gets_raw(&mut self, keys: &[&str]) -> Result<HashMap<String, RawValue>, MemcacheError> {
+        ProtocolTrait::gets(self, keys)
+    }
+
+    fn set_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        ProtocolTrait::set(self, key, WithFlags { value: &value, flags }, expiration)
+    }
+
+    fn cas_raw(
+        &mut self,
+        key: &str,
+        value: &[u8],
+        flags: u32,
+        expiration: u32,
+        cas: u64,
+    ) -> Result<bool, MemcacheError> {
+        ProtocolTrait::cas(self, key, WithFlags { value: &value, flags }, expiration, cas)
+    }
+
+    fn add_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        ProtocolTrait::add(self, key, WithFlags { value: &value, flags }, expiration)
+    }
+
+    fn replace_raw(&mut self, key: &str, value: &[u8], flags: u32, expiration: u32) -> Result<(), MemcacheError> {
+        ProtocolTrait::replace(self, key, WithFlags { value: &value, flags }, expiration)
+    }
+
+    fn append_raw(&mut self, key: &str, value: &[u8]) -> Result<(), MemcacheError> {
+        ProtocolTrait::append(self, key, value)
+    }
+
+    fn prepend_raw(&mut self, key: &str, value: &[u8]) -> Result<(), MemcacheError> {
+        ProtocolTrait::prepend(self, key, value)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, MemcacheError> {
+        ProtocolTrait::delete(self, key)
+    }
+
+    fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        ProtocolTrait::increment(self, key, amount)
+    }
+
+    fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        ProtocolTrait::decrement(self, key, amount)
+    }
+
+    fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+        ProtocolTrait::touch(self, key, expiration)
+    }
+
+    fn stats(&mut self) -> Result<Stats, MemcacheError> {
+        ProtocolTrait::stats(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynProtocol;
+    use crate::connection::Connection;
+    use url::Url;
+
+    #[test]
+    fn boxed_protocols() {
+        let urls = [
+            "memcache://localhost:12345?protocol=ascii",
+            "memcache://localhost:12346",
+        ];
+        for url in urls.iter() {
+            let connection = Connection::connect(&Url::parse(url).unwrap(), None).unwrap();
+            let mut protocol: Box<dyn DynProtocol> = Box::new(connection.protocol);
+
+            protocol.set_raw("dyn_protocol", b"bar", 42, 0).unwrap();
+            protocol.append_raw("dyn_protocol", b"baz").unwrap();
+            let (value, flags, _) = protocol.get_raw("dyn_protocol").unwrap().unwrap();
+            assert_eq!((value.as_slice(), flags), (&b"barbaz"[..], 42));
+
+            let values = protocol.gets_raw(&["dyn_protocol", "dyn_protocol_missing"]).unwrap();
+            assert_eq!(values.len(), 1);
+            let cas = values["dyn_protocol"].2.unwrap();
+            assert!(protocol.cas_raw("dyn_protocol", b"qux", 7, 0, cas).unwrap());
+            assert_eq!(protocol.get_raw("dyn_protocol").unwrap().unwrap().1, 7);
+
+            assert!(protocol.delete("dyn_protocol").unwrap());
+            assert_eq!(protocol.get_raw("dyn_protocol").unwrap(), None);
+        }
+    }
+}
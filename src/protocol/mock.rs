@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+use super::{AddResult, PipelineCommand, PipelineReply, PipelineResult, ProtocolTrait, RawValue, ValueWithMetadata};
+use crate::client::Stats;
+use crate::error::{CommandError, MemcacheError};
+use crate::mock::MockBackend;
+use crate::stream::Stream;
+use crate::value::{FromMemcacheValueExt, ToMemcacheValue};
+
+/// The protocol of clients built with `ClientBuilder::with_test_mode`, which runs the commands on a `MockBackend`.
+pub struct MockProtocol {
+    backend: Arc<dyn MockBackend>,
+    // values are serialized into this buffer before being passed to the backend
+    pub(crate) stream: Stream,
+}
+
+impl MockProtocol {
+    pub(crate) fn new(backend: Arc<dyn MockBackend>) -> Self {
+        MockProtocol {
+            backend,
+            stream: Stream::Memory(Vec::new()),
+        }
+    }
+
+    pub(crate) fn pipeline(&mut self, commands: &[PipelineCommand]) -> Result<Vec<PipelineResult>, MemcacheError> {
+        let result = commands
+            .iter()
+            .map(|command| match command {
+                PipelineCommand::Set {
+                    key,
+                    value,
+                    flags,
+                    expiration,
+                } => self
+                    .backend
+                    .set(key, value, *flags, *expiration)
+                    .map(|_| PipelineReply::Stored),
+                PipelineCommand::Get(key) => self.get::<RawValue>(key).map(PipelineReply::Value),
+                PipelineCommand::Delete(key) => self.backend.delete(key).map(PipelineReply::Deleted),
+            })
+            .collect();
+        Ok(result)
+    }
+
+    fn encode<V: ToMemcacheValue<Stream>>(&mut self, value: V) -> Result<(Vec<u8>, u32), MemcacheError> {
+        self.stream = Stream::Memory(Vec::with_capacity(value.get_length()));
+        value.write_to(&mut self.stream)?;
+        match mem::replace(&mut self.stream, Stream::Memory(Vec::new())) {
+            Stream::Memory(bytes) => Ok((bytes, value.get_flags())),
+            _ => unreachable!("the mock protocol's stream is always in memory"),
+        }
+    }
+}
+
+impl ProtocolTrait for MockProtocol {
+    fn auth(&mut self, _username: &str, _password: &str) -> Result<(), MemcacheError> {
+        Ok(())
+    }
+
+    fn version(&mut self) -> Result<String, MemcacheError> {
+        self.backend.version()
+    }
+
+    fn flush(&mut self) -> Result<(), MemcacheError> {
+        self.backend.flush()
+    }
+
+    fn flush_with_delay(&mut self, _delay: u32) -> Result<(), MemcacheError> {
+        self.backend.flush()
+    }
+
+    fn get<V: FromMemcacheValueExt>(&mut self, key: &str) -> Result<Option<V>, MemcacheError> {
+        match self.backend.get(key)? {
+            Some((value, flags, cas)) => Ok(Some(V::from_memcache_value(value, flags, cas)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn gets<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
+        let mut result = HashMap::new();
+        for key in keys {
+            if let Some(value) = self.get(key)? {
+                result.insert(key.to_string(), value);
+            }
+        }
+        Ok(result)
+    }
+
+    fn get_with_metadata<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<ValueWithMetadata<V>>, MemcacheError> {
+        match self.backend.get(key)? {
+            Some((value, flags, cas)) => {
+                let cas = cas.unwrap_or_default();
+                Ok(Some((
+                    V::from_memcache_value(value, flags, Some(cas))?,
+                    flags,
+                    cas,
+                    None,
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn exists_batch(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        let mut result = Vec::with_capacity(keys.len());
+        for key in keys {
+            result.push(self.backend.get(key)?.is_some());
+        }
+        Ok(result)
+    }
+
+    fn set<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
+        let (value, flags) = self.encode(value)?;
+        self.backend.set(key, &value, flags, expiration)
+    }
+
+    fn sets<K, V, I>(&mut self, entries: I) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let mut result = Ok(());
+        for (key, value, expiration) in entries {
+            result = result.and(self.set(key.as_ref(), value, expiration));
+        }
+        result
+    }
+
+    fn cas<V: ToMemcacheValue<Stream>>(
+        &mut self,
+        key: &str,
+        value: V,
+        expiration: u32,
+        cas: u64,
+    ) -> Result<bool, MemcacheError> {
+        let (value, flags) = self.encode(value)?;
+        self.backend.cas(key, &value, flags, expiration, cas)
+    }
+
+    fn add<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError> {
+        let (value, flags) = self.encode(value)?;
+        self.backend.add(key, &value, flags, expiration)
+    }
+
+    fn adds<K, V, I>(&mut self, entries: I) -> Result<Vec<AddResult>, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>,
+    {
+        let mut result = vec![];
+        for (key, value, expiration) in entries {
+            match self.add(key.as_ref(), value, expiration) {
+                Ok(()) => result.push(Ok(true)),
+                Err(MemcacheError::CommandError(CommandError::KeyExists)) => result.push(Ok(false)),
+                Err(e) => result.push(Err(e)),
+            }
+        }
+        Ok(result)
+    }
+
+    fn replace<V: ToMemcacheValue<Stream>>(
+        &mut self,
+        key: &str,
+        value: V,
+        expiration: u32,
+    ) -> Result<(), MemcacheError> {
+        let (value, flags) = self.encode(value)?;
+        self.backend.replace(key, &value, flags, expiration)
+    }
+
+    fn append<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let (value, _) = self.encode(value)?;
+        self.backend.append(key, &value)
+    }
+
+    fn prepend<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let (value, _) = self.encode(value)?;
+        self.backend.prepend(key, &value)
+    }
+
+    fn append_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        // like a server, errors aren't reported for noreply commands
+        let _ = self.append(key, value);
+        Ok(())
+    }
+
+    fn prepend_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError> {
+        let _ = self.prepend(key, value);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<bool, MemcacheError> {
+        self.backend.delete(key)
+    }
+
+    fn deletes(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError> {
+        let mut result = Vec::with_capacity(keys.len());
+        for key in keys {
+            result.push(self.backend.delete(key)?);
+        }
+        Ok(result)
+    }
+
+    fn deletes_noreply(&mut self, keys: &[&str]) -> Result<(), MemcacheError> {
+        for key in keys {
+            let _ = self.backend.delete(key);
+        }
+        Ok(())
+    }
+
+    fn compare_and_delete(&mut self, key: &str, cas: u64) -> Result<bool, MemcacheError> {
+        match self.backend.get(key)? {
+            Some((_, _, Some(current))) if current == cas => self.backend.delete(key),
+            _ => Ok(false),
+        }
+    }
+
+    fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        self.backend.increment(key, amount)
+    }
+
+    fn increment_noreply(&mut self, key: &str, amount: u64) -> Result<(), MemcacheError> {
+        let _ = self.backend.increment(key, amount);
+        Ok(())
+    }
+
+    fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError> {
+        self.backend.decrement(key, amount)
+    }
+
+    fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError> {
+        self.backend.touch(key, expiration)
+    }
+
+    fn touches(&mut self, entries: &[(&str, u32)]) -> Result<Vec<bool>, MemcacheError> {
+        let mut result = Vec::with_capacity(entries.len());
+        for (key, expiration) in entries {
+            result.push(self.backend.touch(key, *expiration)?);
+        }
+        Ok(result)
+    }
+
+    fn stats(&mut self) -> Result<Stats, MemcacheError> {
+        self.backend.stats()
+    }
+
+    fn stats_with_arg(&mut self, _arg: &str) -> Result<Stats, MemcacheError> {
+        Ok(Stats::new())
+    }
+}
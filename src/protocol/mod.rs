@@ -1,31 +1,167 @@
 mod ascii;
 mod binary;
 mod binary_packet;
+mod dyn_protocol;
+#[cfg(feature = "test-mode")]
+mod mock;
+#[cfg(feature = "sasl-scram")]
+mod scram;
 
 use crate::client::Stats;
-use crate::error::MemcacheError;
+use crate::error::{ClientError, MemcacheError};
 pub(crate) use crate::protocol::ascii::AsciiProtocol;
 pub(crate) use crate::protocol::binary::BinaryProtocol;
+pub use crate::protocol::dyn_protocol::{DynProtocol, RawValue};
+#[cfg(feature = "test-mode")]
+pub(crate) use crate::protocol::mock::MockProtocol;
 use crate::stream::Stream;
 use crate::value::{FromMemcacheValueExt, ToMemcacheValue};
 use enum_dispatch::enum_dispatch;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// A value with its flags, cas id, and remaining time to live in seconds. The time to live is `Some(-1)` for a value
+/// which doesn't expire, and `None` if the server can't report it.
+pub type ValueWithMetadata<V> = (V, u32, u64, Option<i64>);
+
+/// The result of adding a single key with `Client::add_many`: `Ok(true)` if the key was added, `Ok(false)` if it
+/// already existed, or the error reported by the server for this key.
+pub type AddResult = Result<bool, MemcacheError>;
+
+/// A command queued by a `Pipeline`, with its key already prepared and its value serialized.
+pub(crate) enum PipelineCommand {
+    Set {
+        key: String,
+        value: Vec<u8>,
+        flags: u32,
+        expiration: u32,
+    },
+    Get(String),
+    Delete(String),
+}
+
+/// The reply to a command of a `Pipeline`.
+#[derive(Debug, PartialEq)]
+pub enum PipelineReply {
+    /// The value of a `set` was stored.
+    Stored,
+    /// The raw value, flags and cas id returned by a `get`, or `None` if the key doesn't exist.
+    Value(Option<RawValue>),
+    /// Whether the key of a `delete` existed.
+    Deleted(bool),
+}
+
+/// The result of a single command of a `Pipeline`: its reply, or the error reported by the server for this command.
+pub type PipelineResult = Result<PipelineReply, MemcacheError>;
+
+/// The protocol of a connection, either ASCII or binary.
 #[enum_dispatch]
 pub enum Protocol {
     Ascii(AsciiProtocol<Stream>),
     Binary(BinaryProtocol),
+    #[cfg(feature = "test-mode")]
+    Mock(MockProtocol),
+}
+
+impl Protocol {
+    /// The ASCII protocol over `stream`, e.g. for a factory set with `ClientBuilder::with_protocol_factory`.
+    pub fn ascii(stream: Stream) -> Self {
+        Protocol::Ascii(AsciiProtocol::new(stream))
+    }
+
+    /// The binary protocol over `stream`, e.g. for a factory set with `ClientBuilder::with_protocol_factory`.
+    pub fn binary(stream: Stream) -> Self {
+        Protocol::Binary(BinaryProtocol { stream })
+    }
+
+    /// The underlying stream of the connection, for sending commands which aren't supported by this crate. The
+    /// whole response of such command must be read before the connection is used again.
+    pub fn stream(&mut self) -> &mut Stream {
+        match self {
+            Protocol::Ascii(protocol) => protocol.stream(),
+            Protocol::Binary(protocol) => &mut protocol.stream,
+            #[cfg(feature = "test-mode")]
+            Protocol::Mock(protocol) => &mut protocol.stream,
+        }
+    }
+
+    /// Get a single key as its raw bytes, flags, and cas id: the binary protocol returns the cas id of a plain get,
+    /// while the ASCII protocol needs a `gets` command.
+    pub(crate) fn get_with_cas(&mut self, key: &str) -> Result<Option<(Vec<u8>, u32, u64)>, MemcacheError> {
+        let value: Option<RawValue> = match self {
+            Protocol::Ascii(protocol) => protocol.gets_single(key)?,
+            Protocol::Binary(protocol) => protocol.get(key)?,
+            #[cfg(feature = "test-mode")]
+            Protocol::Mock(protocol) => protocol.get(key)?,
+        };
+        match value {
+            Some((value, flags, Some(cas))) => Ok(Some((value, flags, cas))),
+            Some(_) => Err(ClientError::Error(Cow::Borrowed("the server didn't return a cas id")))?,
+            None => Ok(None),
+        }
+    }
+
+    /// Send all `commands` in a single write, then read their replies in order.
+    pub(crate) fn pipeline(&mut self, commands: &[PipelineCommand]) -> Result<Vec<PipelineResult>, MemcacheError> {
+        match self {
+            Protocol::Ascii(protocol) => protocol.pipeline(commands),
+            Protocol::Binary(protocol) => protocol.pipeline(commands),
+            #[cfg(feature = "test-mode")]
+            Protocol::Mock(protocol) => protocol.pipeline(commands),
+        }
+    }
+}
+
+mod sealed {
+    /// Keeps `ProtocolTrait` from being implemented outside of this crate, so that methods can be added to it.
+    pub trait Sealed {}
+
+    impl Sealed for super::Protocol {}
+    impl Sealed for super::AsciiProtocol<super::Stream> {}
+    impl Sealed for super::BinaryProtocol {}
+    #[cfg(feature = "test-mode")]
+    impl Sealed for super::MockProtocol {}
 }
 
+/// The commands of a connection. This trait is sealed, implement `DynProtocol` for custom protocols instead.
 #[enum_dispatch(Protocol)]
-pub trait ProtocolTrait {
+pub trait ProtocolTrait: sealed::Sealed {
     fn auth(&mut self, username: &str, password: &str) -> Result<(), MemcacheError>;
     fn version(&mut self) -> Result<String, MemcacheError>;
     fn flush(&mut self) -> Result<(), MemcacheError>;
     fn flush_with_delay(&mut self, delay: u32) -> Result<(), MemcacheError>;
     fn get<V: FromMemcacheValueExt>(&mut self, key: &str) -> Result<Option<V>, MemcacheError>;
+    /// Get several keys with their cas id, which is the `gets` command of the ASCII protocol.
     fn gets<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError>;
+    /// Get several keys without their cas id, which is the `get` command of the ASCII protocol. The default
+    /// implementation calls `gets`, for protocols which return the cas id anyway.
+    fn get_many<V: FromMemcacheValueExt>(&mut self, keys: &[&str]) -> Result<HashMap<String, V>, MemcacheError> {
+        self.gets(keys)
+    }
+    /// Get a key and update its expiration. The default implementation sends a `get` and a `touch` command, for
+    /// protocols without a command doing both.
+    fn get_and_touch<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+        expiration: u32,
+    ) -> Result<Option<V>, MemcacheError> {
+        let value = self.get(key)?;
+        if value.is_some() {
+            self.touch(key, expiration)?;
+        }
+        Ok(value)
+    }
+    fn get_with_metadata<V: FromMemcacheValueExt>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<ValueWithMetadata<V>>, MemcacheError>;
+    fn exists_batch(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError>;
     fn set<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>;
+    fn sets<K, V, I>(&mut self, entries: I) -> Result<(), MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>;
     fn cas<V: ToMemcacheValue<Stream>>(
         &mut self,
         key: &str,
@@ -34,6 +170,11 @@ pub trait ProtocolTrait {
         cas: u64,
     ) -> Result<bool, MemcacheError>;
     fn add<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V, expiration: u32) -> Result<(), MemcacheError>;
+    fn adds<K, V, I>(&mut self, entries: I) -> Result<Vec<AddResult>, MemcacheError>
+    where
+        K: AsRef<str>,
+        V: ToMemcacheValue<Stream>,
+        I: IntoIterator<Item = (K, V, u32)>;
     fn replace<V: ToMemcacheValue<Stream>>(
         &mut self,
         key: &str,
@@ -42,9 +183,17 @@ pub trait ProtocolTrait {
     ) -> Result<(), MemcacheError>;
     fn append<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError>;
     fn prepend<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError>;
+    fn append_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError>;
+    fn prepend_noreply<V: ToMemcacheValue<Stream>>(&mut self, key: &str, value: V) -> Result<(), MemcacheError>;
     fn delete(&mut self, key: &str) -> Result<bool, MemcacheError>;
+    fn deletes(&mut self, keys: &[&str]) -> Result<Vec<bool>, MemcacheError>;
+    fn deletes_noreply(&mut self, keys: &[&str]) -> Result<(), MemcacheError>;
+    fn compare_and_delete(&mut self, key: &str, cas: u64) -> Result<bool, MemcacheError>;
     fn increment(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
+    fn increment_noreply(&mut self, key: &str, amount: u64) -> Result<(), MemcacheError>;
     fn decrement(&mut self, key: &str, amount: u64) -> Result<u64, MemcacheError>;
     fn touch(&mut self, key: &str, expiration: u32) -> Result<bool, MemcacheError>;
+    fn touches(&mut self, entries: &[(&str, u32)]) -> Result<Vec<bool>, MemcacheError>;
     fn stats(&mut self) -> Result<Stats, MemcacheError>;
+    fn stats_with_arg(&mut self, arg: &str) -> Result<Stats, MemcacheError>;
 }
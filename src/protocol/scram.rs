@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+use crate::error::{ClientError, MemcacheError, ServerError};
+
+pub(crate) const MECHANISM: &str = "SCRAM-SHA-1";
+
+/// The client side of a SCRAM-SHA-1 exchange (RFC 5802), without channel binding.
+pub(crate) struct ScramClient<'a> {
+    password: &'a str,
+    client_first_bare: String,
+    nonce: String,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl<'a> ScramClient<'a> {
+    pub(crate) fn new(username: &str, password: &'a str) -> Self {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(24)
+            .map(char::from)
+            .collect();
+        Self::with_nonce(username, password, nonce)
+    }
+
+    fn with_nonce(username: &str, password: &'a str, nonce: String) -> Self {
+        let username = username.replace('=', "=3D").replace(',', "=2C");
+        ScramClient {
+            password,
+            client_first_bare: format!("n={},r={}", username, nonce),
+            nonce,
+            server_signature: None,
+        }
+    }
+
+    /// The first message, sent with the start auth request.
+    pub(crate) fn client_first(&self) -> String {
+        format!("n,,{}", self.client_first_bare)
+    }
+
+    /// Compute the final message, sent with the auth step request, from the server's first message.
+    pub(crate) fn client_final(&mut self, server_first: &str) -> Result<String, MemcacheError> {
+        let mut nonce = None;
+        let mut salt = None;
+        let mut iterations = None;
+        for attribute in server_first.split(',') {
+            match attribute.split_once('=') {
+                Some(("r", value)) => nonce = Some(value),
+                Some(("s", value)) => salt = Some(BASE64.decode(value).map_err(|_| bad_message(server_first))?),
+                Some(("i", value)) => iterations = Some(value.parse::<u32>().map_err(|_| bad_message(server_first))?),
+                _ => {}
+            }
+        }
+        let (nonce, salt, iterations) = match (nonce, salt, iterations) {
+            (Some(nonce), Some(salt), Some(iterations)) if iterations > 0 => (nonce, salt, iterations),
+            _ => return Err(bad_message(server_first)),
+        };
+        if !nonce.starts_with(&self.nonce) {
+            Err(ServerError::BadResponse(Cow::Borrowed(
+                "SCRAM server nonce doesn't extend the client nonce",
+            )))?
+        }
+
+        let mut salted_password = [0u8; 20];
+        pbkdf2::pbkdf2_hmac::<Sha1>(self.password.as_bytes(), &salt, iterations, &mut salted_password);
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = Sha1::digest(&client_key);
+        let client_final_without_proof = format!("c=biws,r={}", nonce);
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, server_first, client_final_without_proof
+        );
+        let client_signature = hmac(&stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key.iter().zip(client_signature).map(|(a, b)| a ^ b).collect();
+        let server_key = hmac(&salted_password, b"Server Key");
+        self.server_signature = Some(hmac(&server_key, auth_message.as_bytes()));
+
+        Ok(format!("{},p={}", client_final_without_proof, BASE64.encode(proof)))
+    }
+
+    /// Check the server's final message, which proves that the server knows the password too.
+    pub(crate) fn verify_server_final(&self, server_final: &str) -> Result<(), MemcacheError> {
+        if let Some(error) = server_final.strip_prefix("e=") {
+            Err(ClientError::Error(Cow::Owned(format!(
+                "SCRAM authentication failed: {}",
+                error
+            ))))?
+        }
+        let signature = server_final
+            .strip_prefix("v=")
+            .and_then(|signature| BASE64.decode(signature).ok())
+            .ok_or_else(|| bad_message(server_final))?;
+        if Some(signature) != self.server_signature {
+            Err(ServerError::BadResponse(Cow::Borrowed(
+                "SCRAM server signature doesn't match",
+            )))?
+        }
+        Ok(())
+    }
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn bad_message(message: &str) -> MemcacheError {
+    ServerError::BadResponse(Cow::Owned(format!("invalid SCRAM message '{}'", message))).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScramClient;
+
+    #[test]
+    fn rfc5802_example() {
+        let mut client = ScramClient::with_nonce("user", "pencil", "fyko+d2lbbFgONRv9qkxdawL".into());
+        assert_eq!(client.client_first(), "n,,n=user,r=fyko+d2lbbFgONRv9qkxdawL");
+
+        let client_final = client
+            .client_final("r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,s=QSXCR+Q6sek8bf92,i=4096")
+            .unwrap();
+        assert_eq!(
+            client_final,
+            "c=biws,r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,p=v0X8v3Bz2T0CJGbJQyF0X+HI4Ts="
+        );
+        assert!(client.verify_server_final("v=rmF9pqV8S7suAoZWja4dJRkFsKQ=").is_ok());
+        assert!(client.verify_server_final("v=AAAAAAAAAAAAAAAAAAAAAAAAAAA=").is_err());
+        assert!(client.verify_server_final("e=invalid-proof").is_err());
+    }
+
+    #[test]
+    fn invalid_server_first() {
+        let mut client = ScramClient::with_nonce("user", "pencil", "abc".into());
+        assert!(client.client_final("r=xyz,s=QSXCR+Q6sek8bf92,i=4096").is_err());
+        assert!(client.client_final("r=abcdef,s=QSXCR+Q6sek8bf92").is_err());
+        assert!(client.client_final("r=abcdef,s=QSXCR+Q6sek8bf92,i=many").is_err());
+    }
+
+    #[test]
+    fn escape_username() {
+        let client = ScramClient::with_nonce("a=b,c", "pencil", "abc".into());
+        assert_eq!(client.client_first(), "n,,n=a=3Db=2Cc,r=abc");
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+use crate::client::Client;
+use crate::value::WithFlags;
+
+/// The number of shadow writes waiting for the worker thread, beyond which the new ones are dropped.
+const SHADOW_QUEUE_SIZE: usize = 1024;
+
+struct ShadowWrite {
+    shadow: Client,
+    key: String,
+    value: Vec<u8>,
+    flags: u32,
+    expiration: u32,
+}
+
+/// The worker thread running the writes of `Client::shadow_write` on the shadow clients, shared by a client and its
+/// clones. It's started by their first shadow write, and stops once they are all dropped.
+#[derive(Default)]
+pub(crate) struct ShadowWriter {
+    sender: Mutex<Option<SyncSender<ShadowWrite>>>,
+}
+
+impl ShadowWriter {
+    /// Queue a write of `value`, serialized with `flags`, to `shadow`. Returns `false` if it was dropped because the
+    /// queue is full.
+    pub(crate) fn send(&self, shadow: &Client, key: &str, value: Vec<u8>, flags: u32, expiration: u32) -> bool {
+        let mut sender = self.sender.lock().unwrap_or_else(PoisonError::into_inner);
+        let write = ShadowWrite {
+            shadow: shadow.clone(),
+            key: key.to_string(),
+            value,
+            flags,
+            expiration,
+        };
+        sender.get_or_insert_with(spawn).try_send(write).is_ok()
+    }
+}
+
+fn spawn() -> SyncSender<ShadowWrite> {
+    let (sender, receiver) = mpsc::sync_channel::<ShadowWrite>(SHADOW_QUEUE_SIZE);
+    thread::spawn(move || {
+        for write in receiver {
+            let value: &[u8] = &write.value;
+            let value = WithFlags {
+                value: &value,
+                flags: write.flags,
+            };
+            // errors from the shadow client are ignored
+            let _ = write.shadow.set(&write.key, value, write.expiration);
+        }
+    });
+    sender
+}
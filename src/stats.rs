@@ -0,0 +1,588 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::client::Stats;
+use crate::error::{MemcacheError, ServerError};
+
+/// A connection to a memcached server, as reported by `stats conns`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionInfo {
+    /// The file descriptor of the connection on the server.
+    pub fd: u32,
+    /// The peer address, like `tcp:127.0.0.1:50000`, or the listening address for listening sockets.
+    pub addr: String,
+    /// The state of the connection's state machine, like `conn_waiting`.
+    pub state: String,
+    /// Seconds since the connection's last command.
+    pub secs_since_last_cmd: u64,
+}
+
+/// An item of a slab class, as listed by `stats cachedump`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CachedItem {
+    pub key: String,
+    /// The size of the value in bytes.
+    pub bytes: u32,
+    /// The expiration time as a unix timestamp. Items without expiration report the server's start time or 0,
+    /// depending on its version.
+    pub exptime: i64,
+}
+
+/// Properties of a memcached server, parsed from its version string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+    /// The URL of the server.
+    pub url: String,
+    /// The version, like `Version(1, 6, 12)`.
+    pub version: Version,
+    /// Anything following the version number in the version string, like a distribution's build suffix, or an
+    /// empty string.
+    pub platform: String,
+}
+
+/// A `major.minor.patch` memcached server version, which compares by its numbers, e.g.
+/// `version >= Version(1, 6, 0)`. With the `semver` feature, it converts into a `semver::Version`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(pub u32, pub u32, pub u32);
+
+impl Version {
+    /// Parse a version string like `1.6.12`. Anything following the version number, like a distribution's build
+    /// suffix in `1.6.12-ubuntu`, is ignored, and missing numbers are zero.
+    pub fn parse(version: &str) -> Result<Self, MemcacheError> {
+        let (version, _) = parse_version(version)?;
+        Ok(version)
+    }
+}
+
+impl From<(u32, u32, u32)> for Version {
+    fn from((major, minor, patch): (u32, u32, u32)) -> Self {
+        Version(major, minor, patch)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+#[cfg(feature = "semver")]
+impl From<Version> for semver::Version {
+    fn from(Version(major, minor, patch): Version) -> Self {
+        semver::Version::new(major.into(), minor.into(), patch.into())
+    }
+}
+
+/// The health of a client's servers, checked by `Client::healthcheck`. The unreachable servers are listed by URL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HealthStatus {
+    /// All the servers responded.
+    Healthy,
+    /// Some of the servers didn't respond.
+    Degraded(Vec<String>),
+    /// None of the servers responded.
+    Unhealthy(Vec<String>),
+}
+
+impl HealthStatus {
+    /// Whether all the servers responded.
+    pub fn is_ok(&self) -> bool {
+        *self == HealthStatus::Healthy
+    }
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HealthStatus::Healthy => write!(f, "healthy"),
+            HealthStatus::Degraded(servers) => write!(f, "degraded, unreachable: {}", servers.join(", ")),
+            HealthStatus::Unhealthy(servers) => write!(f, "unhealthy, unreachable: {}", servers.join(", ")),
+        }
+    }
+}
+
+/// An overview of a memcached server, built from its version and statistics by `Client::info`. The statistics which
+/// the server doesn't report are 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerSummary {
+    /// The URL of the server.
+    pub url: String,
+    pub version: String,
+    /// Seconds since the server started.
+    pub uptime: u64,
+    pub curr_items: u64,
+    /// The bytes used to store the items.
+    pub bytes_used: u64,
+    /// The memory limit for the items, in bytes.
+    pub max_bytes: u64,
+    /// `get_hits / (get_hits + get_misses)`, or 0 before any get.
+    pub hit_rate: f64,
+    pub evictions: u64,
+    /// The number of open connections.
+    pub connections: u64,
+}
+
+/// An overview of all the servers of a client, returned by `Client::info`. It's displayed as a table.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterInfo {
+    pub servers: Vec<ServerSummary>,
+}
+
+impl fmt::Display for ClusterInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .servers
+            .iter()
+            .map(|server| server.url.len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        write!(
+            f,
+            "{:<width$} {:>10} {:>10} {:>10} {:>12} {:>12} {:>8} {:>10} {:>11}",
+            "server",
+            "version",
+            "uptime",
+            "items",
+            "bytes",
+            "max bytes",
+            "hit rate",
+            "evictions",
+            "connections",
+            width = width
+        )?;
+        for server in self.servers.iter() {
+            write!(
+                f,
+                "\n{:<width$} {:>10} {:>10} {:>10} {:>12} {:>12} {:>7.1}% {:>10} {:>11}",
+                server.url,
+                server.version,
+                server.uptime,
+                server.curr_items,
+                server.bytes_used,
+                server.max_bytes,
+                server.hit_rate * 100.0,
+                server.evictions,
+                server.connections,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn summarize_server(url: String, version: String, stats: &Stats) -> ServerSummary {
+    let counter = |key: &str| stats.get(key).and_then(|value| value.parse::<u64>().ok()).unwrap_or(0);
+    let (hits, misses) = (counter("get_hits"), counter("get_misses"));
+    ServerSummary {
+        url,
+        version,
+        uptime: counter("uptime"),
+        curr_items: counter("curr_items"),
+        bytes_used: counter("bytes"),
+        max_bytes: counter("limit_maxbytes"),
+        hit_rate: if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 / (hits + misses) as f64
+        },
+        evictions: counter("evictions"),
+        connections: counter("curr_connections"),
+    }
+}
+
+/// The round trip latency to a memcached server, measured by `Client::benchmark_servers`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerLatency {
+    /// The URL of the server.
+    pub url: String,
+    pub min: Duration,
+    pub max: Duration,
+    pub avg: Duration,
+    pub p99: Duration,
+}
+
+/// Summarize the measured round trips of a server, `samples` must not be empty.
+pub(crate) fn summarize_latency(url: String, mut samples: Vec<Duration>) -> ServerLatency {
+    samples.sort();
+    let count = samples.len();
+    // the nearest rank percentile: the smallest sample which is greater than or equal to 99% of the samples
+    let p99_index = (count * 99 + 99) / 100 - 1;
+    ServerLatency {
+        url,
+        min: samples[0],
+        max: samples[count - 1],
+        avg: samples.iter().sum::<Duration>() / count as u32,
+        p99: samples[p99_index],
+    }
+}
+
+/// Parse a version string like `1.6.12` or `1.4.25-ubuntu` into the version and the trailing text.
+pub(crate) fn parse_version(version: &str) -> Result<(Version, String), MemcacheError> {
+    let end = version
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(version.len());
+    let (number, platform) = version.split_at(end);
+    let mut parts = number.split('.');
+    let mut next = || -> Result<u32, MemcacheError> {
+        match parts.next() {
+            Some(part) => Ok(part.parse()?),
+            None => Ok(0),
+        }
+    };
+    let version = Version(next()?, next()?, next()?);
+    Ok((version, platform.trim_start_matches(['-', ' ']).to_string()))
+}
+
+fn bad_stat(key: &str, value: &str) -> MemcacheError {
+    ServerError::BadResponse(Cow::Owned(format!("invalid stat '{} {}'", key, value))).into()
+}
+
+/// The statistics of the items of a slab class, as reported by `stats items`. A statistic which isn't reported by
+/// the server is `0`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ItemStats {
+    /// The number of items stored in the slab class.
+    pub number: u64,
+    /// The age of the oldest item in the LRU, in seconds.
+    pub age: u32,
+    pub evicted: u64,
+    pub evicted_nonzero: u64,
+    pub evicted_time: u32,
+    pub outofmemory: u64,
+    pub tailrepairs: u64,
+    pub reclaimed: u64,
+    pub expired_unfetched: u64,
+    pub evicted_unfetched: u64,
+    pub crawler_reclaimed: u64,
+    pub lrutail_reflocked: u64,
+    pub moves_to_cold: u64,
+    pub moves_to_warm: u64,
+    pub moves_within_lru: u64,
+    pub direct_reclaims: u64,
+    pub hits_to_hot: u64,
+    pub hits_to_warm: u64,
+    pub hits_to_cold: u64,
+}
+
+/// The item statistics of each slab class of a server, ordered by slab id.
+pub type SlabItemStats = Vec<(u32, ItemStats)>;
+
+/// Group the `items:<slab id>:<field>` stats returned by `stats items` by slab class, ordered by slab id.
+pub(crate) fn parse_item_stats(stats: Stats) -> Result<SlabItemStats, MemcacheError> {
+    let mut slabs: BTreeMap<u32, ItemStats> = BTreeMap::new();
+    for (key, value) in stats.iter() {
+        let (slab_id, field) = match key.strip_prefix("items:").and_then(|key| key.split_once(':')) {
+            Some((slab_id, field)) => (slab_id.parse().map_err(|_| bad_stat(key, value))?, field),
+            None => return Err(bad_stat(key, value)),
+        };
+        let item = slabs.entry(slab_id).or_default();
+        let counter = match field {
+            "age" => {
+                item.age = value.parse().map_err(|_| bad_stat(key, value))?;
+                continue;
+            }
+            "evicted_time" => {
+                item.evicted_time = value.parse().map_err(|_| bad_stat(key, value))?;
+                continue;
+            }
+            "number" => &mut item.number,
+            "evicted" => &mut item.evicted,
+            "evicted_nonzero" => &mut item.evicted_nonzero,
+            "outofmemory" => &mut item.outofmemory,
+            "tailrepairs" => &mut item.tailrepairs,
+            "reclaimed" => &mut item.reclaimed,
+            "expired_unfetched" => &mut item.expired_unfetched,
+            "evicted_unfetched" => &mut item.evicted_unfetched,
+            "crawler_reclaimed" => &mut item.crawler_reclaimed,
+            "lrutail_reflocked" => &mut item.lrutail_reflocked,
+            "moves_to_cold" => &mut item.moves_to_cold,
+            "moves_to_warm" => &mut item.moves_to_warm,
+            "moves_within_lru" => &mut item.moves_within_lru,
+            "direct_reclaims" => &mut item.direct_reclaims,
+            "hits_to_hot" => &mut item.hits_to_hot,
+            "hits_to_warm" => &mut item.hits_to_warm,
+            "hits_to_cold" => &mut item.hits_to_cold,
+            _ => continue,
+        };
+        *counter = value.parse().map_err(|_| bad_stat(key, value))?;
+    }
+    Ok(slabs.into_iter().collect())
+}
+
+// numeric stats describing a server process rather than counting something, which aren't summed
+const PROCESS_STATS: &[&str] = &["pid", "uptime", "time", "pointer_size"];
+
+/// Merge the statistics of several servers: integer counters are summed, other stats keep the first server's
+/// value. `cache_hit_rate` is added from the total `get_hits` and `get_misses`.
+pub(crate) fn aggregate_stats<I: IntoIterator<Item = Stats>>(servers: I) -> Stats {
+    let mut result = Stats::new();
+    for stats in servers {
+        for (key, value) in stats {
+            match result.get_mut(&key) {
+                None => {
+                    result.insert(key, value);
+                }
+                Some(total) if !PROCESS_STATS.contains(&key.as_str()) => {
+                    if let (Ok(a), Ok(b)) = (total.parse::<u64>(), value.parse::<u64>()) {
+                        *total = a.saturating_add(b).to_string();
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    let counter = |key: &str| result.get(key).and_then(|value| value.parse::<u64>().ok());
+    if let (Some(hits), Some(misses)) = (counter("get_hits"), counter("get_misses")) {
+        let lookups = hits + misses;
+        let rate = if lookups == 0 {
+            0.0
+        } else {
+            hits as f64 / lookups as f64
+        };
+        result.insert("cache_hit_rate".to_string(), rate.to_string());
+    }
+    result
+}
+
+/// Parse an `ITEM <key> [<bytes> b; <exptime> s]` line of a `stats cachedump` response, without its line ending.
+pub(crate) fn parse_cachedump_item(line: &str) -> Result<CachedItem, MemcacheError> {
+    let bad_item = || {
+        MemcacheError::from(ServerError::BadResponse(Cow::Owned(format!(
+            "invalid cachedump item '{}'",
+            line
+        ))))
+    };
+    let item = line.strip_prefix("ITEM ").ok_or_else(bad_item)?;
+    // the key ends at the last " [", since it's not quoted
+    let (key, rest) = item.rsplit_once(" [").ok_or_else(bad_item)?;
+    let (bytes, exptime) = rest
+        .strip_suffix(" s]")
+        .and_then(|rest| rest.split_once(" b; "))
+        .ok_or_else(bad_item)?;
+    Ok(CachedItem {
+        key: key.to_string(),
+        bytes: bytes.parse().map_err(|_| bad_item())?,
+        exptime: exptime.parse().map_err(|_| bad_item())?,
+    })
+}
+
+/// Group the `<fd>:<field>` stats returned by `stats conns` by connection, ordered by fd.
+pub(crate) fn parse_connection_stats(stats: Stats) -> Result<Vec<ConnectionInfo>, MemcacheError> {
+    let mut connections: BTreeMap<u32, ConnectionInfo> = BTreeMap::new();
+    for (key, value) in stats.iter() {
+        let (fd, field) = match key.split_once(':') {
+            Some((fd, field)) => (fd.parse().map_err(|_| bad_stat(key, value))?, field),
+            None => return Err(bad_stat(key, value)),
+        };
+        let connection = connections.entry(fd).or_insert_with(|| ConnectionInfo {
+            fd,
+            ..Default::default()
+        });
+        match field {
+            "addr" | "listen_addr" => connection.addr = value.clone(),
+            "state" => connection.state = value.clone(),
+            "secs_since_last_cmd" => {
+                connection.secs_since_last_cmd = value.parse().map_err(|_| bad_stat(key, value))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(connections.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_cachedump_item, parse_connection_stats, parse_item_stats, parse_version, summarize_latency,
+        summarize_server, CachedItem, ClusterInfo, ConnectionInfo, ItemStats, Version,
+    };
+    use crate::client::Stats;
+    use std::time::Duration;
+
+    #[test]
+    fn connection_stats() {
+        let stats: Stats = vec![
+            ("26:listen_addr", "tcp:0.0.0.0:11211"),
+            ("26:state", "conn_listening"),
+            ("28:addr", "tcp:127.0.0.1:50000"),
+            ("28:state", "conn_parse_cmd"),
+            ("28:secs_since_last_cmd", "12"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            parse_connection_stats(stats).unwrap(),
+            vec![
+                ConnectionInfo {
+                    fd: 26,
+                    addr: "tcp:0.0.0.0:11211".into(),
+                    state: "conn_listening".into(),
+                    secs_since_last_cmd: 0,
+                },
+                ConnectionInfo {
+                    fd: 28,
+                    addr: "tcp:127.0.0.1:50000".into(),
+                    state: "conn_parse_cmd".into(),
+                    secs_since_last_cmd: 12,
+                },
+            ]
+        );
+
+        let stats: Stats = vec![("pid".to_string(), "1".to_string())].into_iter().collect();
+        assert!(parse_connection_stats(stats).is_err());
+    }
+
+    #[test]
+    fn aggregate_stats() {
+        let server = |pid: &str, hits: &str, misses: &str| -> Stats {
+            vec![
+                ("pid", pid),
+                ("version", "1.6.21"),
+                ("get_hits", hits),
+                ("get_misses", misses),
+                ("rusage_user", "0.5"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+        };
+        let stats = super::aggregate_stats(vec![server("10", "3", "1"), server("20", "5", "3")]);
+        assert_eq!(stats["pid"], "10");
+        assert_eq!(stats["version"], "1.6.21");
+        assert_eq!(stats["get_hits"], "8");
+        assert_eq!(stats["get_misses"], "4");
+        assert_eq!(stats["rusage_user"], "0.5");
+        assert_eq!(stats["cache_hit_rate"].parse::<f64>().unwrap(), 8.0 / 12.0);
+
+        let stats = super::aggregate_stats(vec![server("10", "0", "0")]);
+        assert_eq!(stats["cache_hit_rate"], "0");
+        assert!(super::aggregate_stats(vec![]).is_empty());
+    }
+
+    #[test]
+    fn cachedump_item() {
+        assert_eq!(
+            parse_cachedump_item("ITEM foo [3 b; 1700000000 s]").unwrap(),
+            CachedItem {
+                key: "foo".to_string(),
+                bytes: 3,
+                exptime: 1700000000,
+            }
+        );
+        assert_eq!(
+            parse_cachedump_item("ITEM with [brackets] [0 b; 0 s]").unwrap().key,
+            "with [brackets]"
+        );
+        assert!(parse_cachedump_item("ITEM foo").is_err());
+        assert!(parse_cachedump_item("ITEM foo [3 b; never s]").is_err());
+        assert!(parse_cachedump_item("STAT foo [3 b; 0 s]").is_err());
+    }
+
+    #[test]
+    fn cluster_info() {
+        let stats: Stats = vec![
+            ("uptime", "100"),
+            ("curr_items", "3"),
+            ("bytes", "300"),
+            ("limit_maxbytes", "67108864"),
+            ("get_hits", "3"),
+            ("get_misses", "1"),
+            ("curr_connections", "2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let server = summarize_server("memcache://localhost:11211".to_string(), "1.6.21".to_string(), &stats);
+        assert_eq!(server.hit_rate, 0.75);
+        assert_eq!((server.uptime, server.evictions, server.connections), (100, 0, 2));
+
+        let info = ClusterInfo { servers: vec![server] };
+        let table = info.to_string();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("server                     "));
+        assert!(lines[1].starts_with("memcache://localhost:11211     1.6.21"));
+        assert!(lines[1].contains("75.0%"));
+    }
+
+    #[test]
+    fn item_stats() {
+        let stats: Stats = vec![
+            ("items:5:number", "3"),
+            ("items:5:age", "60"),
+            ("items:5:hits_to_warm", "12"),
+            ("items:5:mem_requested", "1024"),
+            ("items:1:number", "1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            parse_item_stats(stats).unwrap(),
+            vec![
+                (
+                    1,
+                    ItemStats {
+                        number: 1,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    5,
+                    ItemStats {
+                        number: 3,
+                        age: 60,
+                        hits_to_warm: 12,
+                        ..Default::default()
+                    }
+                ),
+            ]
+        );
+        let invalid: Stats = vec![("items:1:age".to_string(), "old".to_string())]
+            .into_iter()
+            .collect();
+        assert!(parse_item_stats(invalid).is_err());
+    }
+
+    #[test]
+    fn server_latency() {
+        let samples = (1..=200).rev().map(Duration::from_millis).collect();
+        let latency = summarize_latency("memcache://localhost:11211".into(), samples);
+        assert_eq!(latency.min, Duration::from_millis(1));
+        assert_eq!(latency.max, Duration::from_millis(200));
+        assert_eq!(latency.avg, Duration::from_micros(100_500));
+        assert_eq!(latency.p99, Duration::from_millis(198));
+
+        let latency = summarize_latency(String::new(), vec![Duration::from_millis(3)]);
+        assert_eq!(
+            (latency.min, latency.p99),
+            (Duration::from_millis(3), Duration::from_millis(3))
+        );
+    }
+
+    #[test]
+    fn version() {
+        assert_eq!(parse_version("1.6.12").unwrap(), (Version(1, 6, 12), String::new()));
+        assert_eq!(
+            parse_version("1.4.25-ubuntu").unwrap(),
+            (Version(1, 4, 25), "ubuntu".into())
+        );
+        assert_eq!(parse_version("1.6").unwrap(), (Version(1, 6, 0), String::new()));
+        assert!(parse_version("unknown").is_err());
+    }
+
+    #[test]
+    fn version_type() {
+        let version = Version::parse("1.6.12-ubuntu").unwrap();
+        assert_eq!(version, Version(1, 6, 12));
+        assert!(version >= Version(1, 6, 0));
+        assert!(version < Version(1, 10, 0));
+        assert_eq!(version.to_string(), "1.6.12");
+        #[cfg(feature = "semver")]
+        assert_eq!(semver::Version::from(version), semver::Version::new(1, 6, 12));
+    }
+}
@@ -1,7 +1,7 @@
 mod udp_stream;
 
 use std::io::{self, Read, Write};
-use std::net::TcpStream;
+use std::net::{SocketAddr, TcpStream};
 #[cfg(unix)]
 use std::os::unix::net::UnixStream;
 use std::time::Duration;
@@ -19,6 +19,10 @@ pub enum Stream {
     Unix(UnixStream),
     #[cfg(feature = "tls")]
     Tls(SslStream<TcpStream>),
+    /// An in-memory buffer of the written bytes, which reads nothing. Used by clients built with
+    /// `ClientBuilder::with_test_mode`.
+    #[cfg(feature = "test-mode")]
+    Memory(Vec<u8>),
 }
 
 impl Stream {
@@ -30,6 +34,8 @@ impl Stream {
             #[cfg(feature = "tls")]
             Stream::Tls(ref stream) => stream.get_ref().set_read_timeout(timeout)?,
             Stream::Udp(ref conn) => conn.set_read_timeout(timeout)?,
+            #[cfg(feature = "test-mode")]
+            Stream::Memory(_) => {}
         }
         Ok(())
     }
@@ -42,9 +48,31 @@ impl Stream {
             #[cfg(feature = "tls")]
             Stream::Tls(ref stream) => stream.get_ref().set_write_timeout(timeout)?,
             Stream::Udp(ref conn) => conn.set_write_timeout(timeout)?,
+            #[cfg(feature = "test-mode")]
+            Stream::Memory(_) => {}
         }
         Ok(())
     }
+
+    /// The address of the server for TCP and TLS connections, `None` for other connections.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Stream::Tcp(ref conn) => conn.peer_addr().ok(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(ref stream) => stream.get_ref().peer_addr().ok(),
+            _ => None,
+        }
+    }
+
+    /// The local address for TCP and TLS connections, `None` for other connections.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Stream::Tcp(ref conn) => conn.local_addr().ok(),
+            #[cfg(feature = "tls")]
+            Stream::Tls(ref stream) => stream.get_ref().local_addr().ok(),
+            _ => None,
+        }
+    }
 }
 
 impl Read for Stream {
@@ -56,6 +84,8 @@ impl Read for Stream {
             Stream::Unix(ref mut stream) => stream.read(buf),
             #[cfg(feature = "tls")]
             Stream::Tls(ref mut stream) => stream.read(buf),
+            #[cfg(feature = "test-mode")]
+            Stream::Memory(_) => Ok(0),
         }
     }
 }
@@ -69,6 +99,8 @@ impl Write for Stream {
             Stream::Unix(ref mut stream) => stream.write(buf),
             #[cfg(feature = "tls")]
             Stream::Tls(ref mut stream) => stream.write(buf),
+            #[cfg(feature = "test-mode")]
+            Stream::Memory(ref mut buffer) => buffer.write(buf),
         }
     }
 
@@ -80,6 +112,8 @@ impl Write for Stream {
             Stream::Unix(ref mut stream) => stream.flush(),
             #[cfg(feature = "tls")]
             Stream::Tls(ref mut stream) => stream.flush(),
+            #[cfg(feature = "test-mode")]
+            Stream::Memory(_) => Ok(()),
         }
     }
 }
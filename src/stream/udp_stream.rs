@@ -63,12 +63,15 @@ impl Write for UdpStream {
         udp_header.write_u16::<BigEndian>(1)?; // total datagrams in this request (requests can only be 1 datagram long)
         udp_header.write_u16::<BigEndian>(0)?; // reserved bytes
         self.write_buf.splice(0..0, udp_header.iter().cloned());
+        let request_id = self.request_id;
+        // move to the next request id even if this request fails, so a late response to it can't be taken as the
+        // response of the next one
+        self.request_id = (self.request_id % (u16::MAX)) + 1;
         self.socket.send(self.write_buf.as_slice())?;
         self.write_buf.clear(); // clear the buffer for the next command
 
         let mut response_datagrams: HashMap<u16, Vec<u8>> = HashMap::new();
-        let mut total_datagrams;
-        let mut remaining_datagrams = 0;
+        let mut total_datagrams = 0;
         self.read_buf.clear();
         loop {
             // for large values, response can span multiple datagrams, so gather them all
@@ -79,30 +82,30 @@ impl Write for UdpStream {
                 return Err(Error::new(ErrorKind::Other, "Invalid UDP header received"));
             }
 
-            let request_id = BigEndian::read_u16(&buf[0..]);
-            if self.request_id != request_id {
-                // ideally this shouldn't happen as we wait to read out response before sending another request
+            if BigEndian::read_u16(&buf[0..]) != request_id {
+                // a late response to a previous request, which has already failed
                 continue;
             }
             let sequence_no = BigEndian::read_u16(&buf[2..]);
-            total_datagrams = BigEndian::read_u16(&buf[4..]);
-            if remaining_datagrams == 0 {
-                remaining_datagrams = total_datagrams;
+            if response_datagrams.is_empty() {
+                total_datagrams = BigEndian::read_u16(&buf[4..]);
+            } else if total_datagrams != BigEndian::read_u16(&buf[4..]) {
+                return Err(Error::new(ErrorKind::Other, "Inconsistent UDP datagram count received"));
+            }
+            if sequence_no >= total_datagrams {
+                return Err(Error::new(ErrorKind::Other, "Invalid UDP sequence number received"));
             }
 
-            let mut v: Vec<u8> = Vec::new();
-            v.extend_from_slice(&buf[8..bytes_read]);
-            response_datagrams.insert(sequence_no, v);
-            remaining_datagrams -= 1;
-            if remaining_datagrams == 0 {
+            // datagrams can arrive out of order or more than once, so they're collected by sequence number
+            response_datagrams.insert(sequence_no, buf[8..bytes_read].to_vec());
+            if response_datagrams.len() == total_datagrams as usize {
                 break;
             }
         }
         for i in 0..total_datagrams {
-            self.read_buf.append(&mut (response_datagrams[&i].clone()));
+            self.read_buf.extend_from_slice(&response_datagrams[&i]);
         }
 
-        self.request_id = (self.request_id % (u16::MAX)) + 1;
         Ok(())
     }
 }
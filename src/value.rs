@@ -1,4 +1,6 @@
 use crate::error::MemcacheError;
+use crate::stream::Stream;
+use std::borrow::Cow;
 use std::io;
 use std::io::Write;
 use std::str;
@@ -6,9 +8,16 @@ use std::str::FromStr;
 
 pub enum Flags {
     Bytes = 0,
+    /// Set on values stored by `CompressedValue`. The low bits are left to the applications, which often use them to
+    /// mark their serialization format.
+    #[cfg(feature = "compression")]
+    Compressed = 1 << 30,
 }
 
 /// determine how the value is serialize to memcache
+///
+/// Values are written to a connection's `Stream`, and to a `Vec<u8>` when they're compressed or queued in a
+/// `Pipeline`, so implement it for any `W: Write`.
 pub trait ToMemcacheValue<W: Write> {
     fn get_flags(&self) -> u32;
     fn get_length(&self) -> usize;
@@ -80,6 +89,34 @@ impl<'a, W: Write> ToMemcacheValue<W> for &'a str {
     }
 }
 
+impl<'a, W: Write> ToMemcacheValue<W> for Cow<'a, [u8]> {
+    fn get_flags(&self) -> u32 {
+        ToMemcacheValue::<W>::get_flags(&self.as_ref())
+    }
+
+    fn get_length(&self) -> usize {
+        ToMemcacheValue::<W>::get_length(&self.as_ref())
+    }
+
+    fn write_to(&self, stream: &mut W) -> io::Result<()> {
+        ToMemcacheValue::<W>::write_to(&self.as_ref(), stream)
+    }
+}
+
+impl<'a, W: Write> ToMemcacheValue<W> for Cow<'a, str> {
+    fn get_flags(&self) -> u32 {
+        ToMemcacheValue::<W>::get_flags(&self.as_ref())
+    }
+
+    fn get_length(&self) -> usize {
+        ToMemcacheValue::<W>::get_length(&self.as_ref())
+    }
+
+    fn write_to(&self, stream: &mut W) -> io::Result<()> {
+        ToMemcacheValue::<W>::write_to(&self.as_ref(), stream)
+    }
+}
+
 macro_rules! impl_to_memcache_value_for_number {
     ($ty:ident) => {
         impl<W: Write> ToMemcacheValue<W> for $ty {
@@ -113,8 +150,151 @@ impl_to_memcache_value_for_number!(i64);
 impl_to_memcache_value_for_number!(f32);
 impl_to_memcache_value_for_number!(f64);
 
+/// A borrowed value which is stored with the given flags instead of the ones reported by its own type.
+pub(crate) struct WithFlags<'a, V> {
+    pub(crate) value: &'a V,
+    pub(crate) flags: u32,
+}
+
+impl<'a, W: Write, V: ToMemcacheValue<W>> ToMemcacheValue<W> for WithFlags<'a, V> {
+    fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    fn get_length(&self) -> usize {
+        self.value.get_length()
+    }
+
+    fn write_to(&self, stream: &mut W) -> io::Result<()> {
+        self.value.write_to(stream)
+    }
+}
+
+/// A value which is stored compressed with zlib, with the `Flags::Compressed` bit added to the flags of the inner
+/// value. Values read with the `compression` feature are decompressed when they have this bit, whatever their type,
+/// so they can be read like the inner value. Clients built with `ClientBuilder::with_compression_threshold` compress
+/// large values automatically.
+///
+/// Example:
+///
+/// ```rust
+/// let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+/// let value = "a".repeat(1000);
+/// client.set("compressed", memcache::CompressedValue::new(value.as_str()).unwrap(), 0).unwrap();
+/// let stored: Option<String> = client.get("compressed").unwrap();
+/// assert_eq!(stored, Some(value));
+/// # client.flush().unwrap();
+/// ```
+#[cfg(feature = "compression")]
+pub struct CompressedValue<V> {
+    value: V,
+    flags: u32,
+    compressed: Vec<u8>,
+}
+
+#[cfg(feature = "compression")]
+impl<V: ToMemcacheValue<Vec<u8>>> CompressedValue<V> {
+    /// Compress `value`.
+    pub fn new(value: V) -> io::Result<Self> {
+        let mut bytes = Vec::with_capacity(value.get_length());
+        value.write_to(&mut bytes)?;
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&bytes)?;
+        let compressed = encoder.finish()?;
+        let flags = value.get_flags() | Flags::Compressed as u32;
+        Ok(CompressedValue {
+            value,
+            flags,
+            compressed,
+        })
+    }
+
+    /// The value before compression.
+    pub fn into_inner(self) -> V {
+        self.value
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<W: Write, V> ToMemcacheValue<W> for CompressedValue<V> {
+    fn get_flags(&self) -> u32 {
+        self.flags
+    }
+
+    fn get_length(&self) -> usize {
+        self.compressed.len()
+    }
+
+    fn write_to(&self, stream: &mut W) -> io::Result<()> {
+        stream.write_all(&self.compressed)
+    }
+}
+
+/// A value which is compressed when it's larger than the compression threshold of the client.
+pub(crate) enum MaybeCompressed<V> {
+    Plain(V),
+    #[cfg(feature = "compression")]
+    Compressed(CompressedValue<V>),
+}
+
+impl<V: ToMemcacheValue<Stream>> ToMemcacheValue<Stream> for MaybeCompressed<V> {
+    fn get_flags(&self) -> u32 {
+        match self {
+            MaybeCompressed::Plain(value) => value.get_flags(),
+            #[cfg(feature = "compression")]
+            MaybeCompressed::Compressed(value) => ToMemcacheValue::<Stream>::get_flags(value),
+        }
+    }
+
+    fn get_length(&self) -> usize {
+        match self {
+            MaybeCompressed::Plain(value) => value.get_length(),
+            #[cfg(feature = "compression")]
+            MaybeCompressed::Compressed(value) => ToMemcacheValue::<Stream>::get_length(value),
+        }
+    }
+
+    fn write_to(&self, stream: &mut Stream) -> io::Result<()> {
+        match self {
+            MaybeCompressed::Plain(value) => value.write_to(stream),
+            #[cfg(feature = "compression")]
+            MaybeCompressed::Compressed(value) => value.write_to(stream),
+        }
+    }
+}
+
 type MemcacheValue<T> = Result<T, MemcacheError>;
 
+/// The maximum length of a decompressed value, so that a small corrupted or malicious value can't exhaust the memory.
+/// It's far above the 1MB item size limit of memcached by default.
+#[cfg(feature = "compression")]
+const MAX_DECOMPRESSED_LENGTH: u64 = 128 * 1024 * 1024;
+
+/// Decompress a value stored by `CompressedValue`, and remove the `Flags::Compressed` bit from its flags.
+#[cfg(feature = "compression")]
+fn decompress(value: Vec<u8>, flags: u32) -> MemcacheValue<(Vec<u8>, u32)> {
+    decompress_at_most(value, flags, MAX_DECOMPRESSED_LENGTH)
+}
+
+#[cfg(feature = "compression")]
+fn decompress_at_most(value: Vec<u8>, flags: u32, max_length: u64) -> MemcacheValue<(Vec<u8>, u32)> {
+    use std::io::Read;
+
+    if flags & Flags::Compressed as u32 == 0 {
+        return Ok((value, flags));
+    }
+    let mut decompressed = Vec::new();
+    flate2::read::ZlibDecoder::new(value.as_slice())
+        .take(max_length + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > max_length {
+        return Err(
+            crate::error::ClientError::from(format!("decompressed value is longer than {} bytes", max_length)).into(),
+        );
+    }
+    Ok((decompressed, flags & !(Flags::Compressed as u32)))
+}
+
 /// determine how the value is unserialize to memcache
 pub trait FromMemcacheValue: Sized {
     fn from_memcache_value(_: Vec<u8>, _: u32) -> MemcacheValue<Self>;
@@ -126,6 +306,8 @@ pub trait FromMemcacheValueExt: Sized {
 
 impl<V: FromMemcacheValue> FromMemcacheValueExt for V {
     fn from_memcache_value(value: Vec<u8>, flags: u32, _cas: Option<u64>) -> MemcacheValue<Self> {
+        #[cfg(feature = "compression")]
+        let (value, flags) = decompress(value, flags)?;
         FromMemcacheValue::from_memcache_value(value, flags)
     }
 }
@@ -154,6 +336,18 @@ impl FromMemcacheValue for String {
     }
 }
 
+impl FromMemcacheValue for Cow<'static, [u8]> {
+    fn from_memcache_value(value: Vec<u8>, _: u32) -> MemcacheValue<Self> {
+        return Ok(Cow::Owned(value));
+    }
+}
+
+impl FromMemcacheValue for Cow<'static, str> {
+    fn from_memcache_value(value: Vec<u8>, _: u32) -> MemcacheValue<Self> {
+        return Ok(Cow::Owned(String::from_utf8(value)?));
+    }
+}
+
 macro_rules! impl_from_memcache_value_for_number {
     ($ty:ident) => {
         impl FromMemcacheValue for $ty {
@@ -176,3 +370,87 @@ impl_from_memcache_value_for_number!(i32);
 impl_from_memcache_value_for_number!(i64);
 impl_from_memcache_value_for_number!(f32);
 impl_from_memcache_value_for_number!(f64);
+
+/// Stored as its hyphenated string, like `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+#[cfg(feature = "uuid")]
+impl<W: Write> ToMemcacheValue<W> for uuid::Uuid {
+    fn get_flags(&self) -> u32 {
+        return Flags::Bytes as u32;
+    }
+
+    fn get_length(&self) -> usize {
+        return uuid::fmt::Hyphenated::LENGTH;
+    }
+
+    fn write_to(&self, stream: &mut W) -> io::Result<()> {
+        stream.write_all(
+            self.hyphenated()
+                .encode_lower(&mut uuid::Uuid::encode_buffer())
+                .as_bytes(),
+        )
+    }
+}
+
+/// Read from its 16 raw bytes, or from its string representation.
+#[cfg(feature = "uuid")]
+impl FromMemcacheValue for uuid::Uuid {
+    fn from_memcache_value(value: Vec<u8>, _: u32) -> MemcacheValue<Self> {
+        if let Ok(uuid) = uuid::Uuid::from_slice(&value) {
+            return Ok(uuid);
+        }
+        let s = str::from_utf8(&value)?;
+        uuid::Uuid::parse_str(s)
+            .map_err(|e| crate::error::ClientError::from(format!("invalid uuid '{}': {}", s, e)).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_value() {
+        use super::{CompressedValue, FromMemcacheValueExt, ToMemcacheValue};
+        use crate::stream::Stream;
+
+        let value = "foo".repeat(1000);
+        let compressed = CompressedValue::new(value.as_str()).unwrap();
+        let flags = ToMemcacheValue::<Vec<u8>>::get_flags(&compressed);
+        assert_eq!(flags, super::Flags::Compressed as u32);
+        let mut bytes = Vec::new();
+        ToMemcacheValue::<Vec<u8>>::write_to(&compressed, &mut bytes).unwrap();
+        assert_eq!(ToMemcacheValue::<Vec<u8>>::get_length(&compressed), bytes.len());
+        assert!(bytes.len() < value.len());
+
+        let decompressed: String = FromMemcacheValueExt::from_memcache_value(bytes.clone(), flags, None).unwrap();
+        assert_eq!(decompressed, value);
+        let (decompressed, _) = super::decompress_at_most(bytes.clone(), flags, value.len() as u64).unwrap();
+        assert_eq!(decompressed, value.as_bytes());
+        assert!(super::decompress_at_most(bytes.clone(), flags, value.len() as u64 - 1).is_err());
+        let (raw, raw_flags, _): (Vec<u8>, u32, Option<u64>) =
+            FromMemcacheValueExt::from_memcache_value(bytes.clone(), flags, None).unwrap();
+        assert_eq!((raw, raw_flags), (bytes, flags));
+        assert!(<String as FromMemcacheValueExt>::from_memcache_value(b"foo".to_vec(), flags, None).is_err());
+        assert_eq!(
+            <CompressedValue<&str> as ToMemcacheValue<Stream>>::get_flags(&compressed),
+            flags
+        );
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        use super::{FromMemcacheValue, ToMemcacheValue};
+
+        let uuid = uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+        let mut bytes = Vec::new();
+        ToMemcacheValue::<Vec<u8>>::write_to(&uuid, &mut bytes).unwrap();
+        assert_eq!(bytes, b"67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(ToMemcacheValue::<Vec<u8>>::get_length(&uuid), bytes.len());
+
+        let parsed: uuid::Uuid = FromMemcacheValue::from_memcache_value(bytes, 0).unwrap();
+        assert_eq!(parsed, uuid);
+        let parsed: uuid::Uuid = FromMemcacheValue::from_memcache_value(uuid.as_bytes().to_vec(), 0).unwrap();
+        assert_eq!(parsed, uuid);
+        assert!(<uuid::Uuid as FromMemcacheValue>::from_memcache_value(b"not a uuid".to_vec(), 0).is_err());
+    }
+}
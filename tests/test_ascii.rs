@@ -45,5 +45,33 @@ fn test_ascii() {
     assert_eq!(client.increment("ascii_counter", 100).unwrap(), 103);
     assert_eq!(client.decrement("ascii_counter", 3).unwrap(), 100);
 
-    client.stats().unwrap();
+    let stats = client.stats().unwrap();
+    assert!(stats[0].1.contains_key("pid"));
+    assert!(!stats[0].1["pid"].contains(' '));
+
+    let connections = client.stats_connections().unwrap();
+    assert!(!connections[0].1.is_empty());
+}
+
+#[test]
+fn test_ascii_udp() {
+    // the udp stream only sends a request when it's flushed, so every command must flush before reading
+    let client = memcache::Client::connect("memcache+udp://localhost:22345?protocol=ascii").unwrap();
+
+    client.version().unwrap();
+    client.flush().unwrap();
+
+    client.set("ascii_udp_foo", "bar", 0).unwrap();
+    let value: Option<String> = client.get("ascii_udp_foo").unwrap();
+    assert_eq!(value, Some("bar".into()));
+    let values: HashMap<String, String> = client.gets(&["ascii_udp_foo", "not_exists_key"]).unwrap();
+    assert_eq!(values.len(), 1);
+
+    client.set("ascii_udp_counter", 3, 0).unwrap();
+    assert_eq!(client.increment("ascii_udp_counter", 100).unwrap(), 103);
+    assert_eq!(client.decrement("ascii_udp_counter", 3).unwrap(), 100);
+
+    assert!(client.delete("ascii_udp_foo").unwrap());
+    let value: Option<String> = client.get("ascii_udp_foo").unwrap();
+    assert_eq!(value, None);
 }
@@ -1,13 +1,22 @@
 extern crate memcache;
 extern crate rand;
+#[macro_use]
+extern crate static_assertions;
 
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::io::{Read, Write};
 use std::iter;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
 use std::time;
 
+// a client can be shared between threads, e.g. in an `Arc<Client>`
+assert_impl_all!(memcache::Client: Send, Sync);
+
 fn gen_random_key() -> String {
     let bs = iter::repeat(())
         .map(|()| thread_rng().sample(Alphanumeric))
@@ -16,6 +25,78 @@ fn gen_random_key() -> String {
     return String::from_utf8(bs).unwrap();
 }
 
+/// Start a binary protocol server which only answers noops, so that connections to it pass the pool's checks, and
+/// closes the connection on any other command. Returns its port.
+fn spawn_noop_server() -> u16 {
+    spawn_stoppable_noop_server().0
+}
+
+/// Like `spawn_noop_server`, but also returns a function stopping the server: its connections are closed, and the
+/// new ones are closed right away.
+fn spawn_stoppable_noop_server() -> (u16, impl FnOnce()) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let streams: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
+    let (server_stopped, server_streams) = (stopped.clone(), streams.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            if server_stopped.load(Ordering::SeqCst) {
+                continue;
+            }
+            server_streams.lock().unwrap().push(stream.try_clone().unwrap());
+            thread::spawn(move || {
+                let mut header = [0u8; 24];
+                while stream.read_exact(&mut header).is_ok() && header[1] == 0x0a {
+                    let mut response = [0u8; 24];
+                    response[0] = 0x81;
+                    response[1] = 0x0a;
+                    if stream.write_all(&response).is_err() {
+                        break;
+                    }
+                }
+                // also close the clone kept to stop the server
+                let _ = stream.shutdown(Shutdown::Both);
+            });
+        }
+    });
+    let stop = move || {
+        stopped.store(true, Ordering::SeqCst);
+        for stream in streams.lock().unwrap().iter() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    };
+    (port, stop)
+}
+
+/// Start a binary protocol server which answers noops, so that connections to it pass the pool's checks, but never
+/// answers any other command. Returns its port.
+fn spawn_silent_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut header = [0u8; 24];
+                while stream.read_exact(&mut header).is_ok() && header[1] == 0x0a {
+                    let mut response = [0u8; 24];
+                    response[0] = 0x81;
+                    response[1] = 0x0a;
+                    if stream.write_all(&response).is_err() {
+                        return;
+                    }
+                }
+                // keep the connection open without answering
+                let mut rest = vec![];
+                let _ = stream.read_to_end(&mut rest);
+            });
+        }
+    });
+    port
+}
+
 #[test]
 fn test() {
     let mut urls = vec![
@@ -146,9 +227,10 @@ fn udp_test() {
     assert_eq!(client.touch("foooo", 123).unwrap(), false);
     assert_eq!(client.touch("fooo", 12345).unwrap(), true);
 
-    // gets is not supported for udp
-    let value: Result<std::collections::HashMap<String, String>, _> = client.gets(&["foo", "fooo"]);
-    assert_eq!(value.is_ok(), false);
+    let value: std::collections::HashMap<String, String> = client.gets(&["foo", "fooo", "foooo"]).unwrap();
+    assert_eq!(value.len(), 2);
+    assert_eq!(value["foo"], "barbazbar");
+    assert_eq!(value["fooo"], "0");
 
     let mut keys: Vec<String> = Vec::new();
     for _ in 0..1000 {
@@ -250,3 +332,1110 @@ fn test_cas() {
         client.flush().unwrap();
     }
 }
+
+#[test]
+fn test_set_many() {
+    use memcache::Client;
+    use std::collections::HashMap;
+    let clients = vec![
+        Client::connect(vec![
+            "memcache://localhost:12346",
+            "memcache://localhost:12347",
+            "memcache://localhost:12348?protocol=ascii",
+        ])
+        .unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..1000).map(|_| gen_random_key()).collect();
+        client
+            .set_many(keys.iter().map(|key| (key.as_str(), key.as_str())), 0)
+            .unwrap();
+
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let values: HashMap<String, String> = client.gets(&key_refs).unwrap();
+        assert_eq!(values.len(), keys.len());
+        for key in keys.iter() {
+            assert_eq!(&values[key], key);
+        }
+    }
+}
+
+#[test]
+fn test_exists_batch() {
+    use memcache::Client;
+    let clients = vec![
+        Client::connect(vec![
+            "memcache://localhost:12346",
+            "memcache://localhost:12347",
+            "memcache://localhost:12348?protocol=ascii",
+        ])
+        .unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..50).map(|_| gen_random_key()).collect();
+        for key in keys.iter().step_by(2) {
+            client.set(key, "value", 0).unwrap();
+        }
+
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let exists = client.exists_batch(&key_refs).unwrap();
+        let expected: Vec<bool> = (0..keys.len()).map(|i| i % 2 == 0).collect();
+        assert_eq!(exists, expected);
+    }
+}
+
+#[test]
+fn test_delete_multi() {
+    use memcache::Client;
+    let clients = vec![
+        Client::connect(vec![
+            "memcache://localhost:12346",
+            "memcache://localhost:12347",
+            "memcache://localhost:12348?protocol=ascii",
+        ])
+        .unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..500).map(|_| gen_random_key()).collect();
+        for key in keys.iter().step_by(2) {
+            client.set(key, "value", 0).unwrap();
+        }
+
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let deleted = client.delete_multi(&key_refs).unwrap();
+        let expected: Vec<bool> = (0..keys.len()).map(|i| i % 2 == 0).collect();
+        assert_eq!(deleted, expected);
+        assert_eq!(client.exists_batch(&key_refs).unwrap(), vec![false; keys.len()]);
+    }
+}
+
+#[test]
+fn test_multi_touch() {
+    use memcache::Client;
+    let clients = vec![
+        Client::connect(vec![
+            "memcache://localhost:12346",
+            "memcache://localhost:12347",
+            "memcache://localhost:12348?protocol=ascii",
+        ])
+        .unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..20).map(|_| gen_random_key()).collect();
+        for key in keys.iter().step_by(2) {
+            client.set(key, "value", 0).unwrap();
+        }
+
+        let entries: Vec<(&str, u32)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), i as u32))
+            .collect();
+        let touched = client.multi_touch(&entries).unwrap();
+        let expected: Vec<bool> = (0..keys.len()).map(|i| i % 2 == 0).collect();
+        assert_eq!(touched, expected);
+    }
+}
+
+#[test]
+fn test_compare_and_delete() {
+    use std::collections::HashMap;
+    for url in [
+        "memcache://localhost:12346",
+        "memcache://localhost:12345?protocol=ascii",
+    ]
+    .iter()
+    {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        client.set(&key, "bar", 0).unwrap();
+        let values: HashMap<String, (Vec<u8>, u32, Option<u64>)> = client.gets(&[&key]).unwrap();
+        let cas = values[&key].2.unwrap();
+
+        assert_eq!(client.compare_and_delete(&key, cas + 1).unwrap(), false);
+        let value: Option<String> = client.get(&key).unwrap();
+        assert_eq!(value, Some(String::from("bar")));
+
+        assert_eq!(client.compare_and_delete(&key, cas).unwrap(), true);
+        let value: Option<String> = client.get(&key).unwrap();
+        assert_eq!(value, None);
+        assert_eq!(client.compare_and_delete(&key, cas).unwrap(), false);
+    }
+}
+
+#[test]
+fn test_set_many_with_flags() {
+    use memcache::Client;
+    let clients = vec![
+        Client::connect("memcache://localhost:12346").unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..10).map(|_| gen_random_key()).collect();
+        let entries: Vec<(&str, &str, u32, u32)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), key.as_str(), 0, i as u32))
+            .collect();
+        client.set_many_with_flags(&entries).unwrap();
+
+        for (i, key) in keys.iter().enumerate() {
+            let value: Option<(Vec<u8>, u32)> = client.get(key).unwrap();
+            assert_eq!(value, Some((key.as_bytes().to_vec(), i as u32)));
+        }
+    }
+}
+
+#[test]
+fn test_prepend_missing_key() {
+    let client = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    let key = gen_random_key();
+    assert!(client.prepend(key.as_str(), "bar").is_err());
+    let value: Option<String> = client.get(key.as_str()).unwrap();
+    assert_eq!(value, None);
+}
+
+#[cfg(feature = "key-hashing")]
+#[test]
+fn test_key_hashing() {
+    use std::sync::{Arc, Mutex};
+
+    let observed = Arc::new(Mutex::new(vec![]));
+    let recorder = observed.clone();
+    let client = memcache::Client::builder()
+        .add_server(vec![
+            "memcache://localhost:12346",
+            "memcache://localhost:12347?protocol=ascii",
+        ])
+        .unwrap()
+        .with_key_hashing(true)
+        .with_miss_observer(move |key, _hit| recorder.lock().unwrap().push(key.to_string()))
+        .build()
+        .unwrap();
+    let long_key = "k".repeat(300) + &gen_random_key();
+    let short_key = gen_random_key();
+
+    client.set(&long_key, "long", 0).unwrap();
+    client.set_many(vec![(short_key.as_str(), "short")], 0).unwrap();
+    let value: Option<String> = client.get(&long_key).unwrap();
+    assert_eq!(value, Some(String::from("long")));
+    // the miss observer is called with the key passed by the caller, not its hash
+    assert_eq!(*observed.lock().unwrap(), vec![long_key.clone()]);
+
+    let values: std::collections::HashMap<String, String> = client.gets(&[&long_key, &short_key]).unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[&long_key], "long");
+    assert_eq!(values[&short_key], "short");
+    assert_eq!(client.exists_batch(&[&long_key, "missing"]).unwrap(), vec![true, false]);
+
+    let hashed_key = client.hash_key(&long_key);
+    let value: Option<String> = client.get(&hashed_key).unwrap();
+    assert_eq!(value, Some(String::from("long")));
+    assert!(client.delete(&long_key).unwrap());
+
+    let client = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    assert!(client.set(&long_key, "long", 0).is_err());
+}
+
+#[test]
+fn test_get_or_set_many() {
+    use memcache::Client;
+    use std::collections::HashMap;
+    let clients = vec![
+        Client::connect("memcache://localhost:12346").unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..4).map(|_| gen_random_key()).collect();
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_str()).collect();
+        client.set(keys[0], 0u64, 0).unwrap();
+
+        let result: HashMap<String, u64> = client
+            .get_or_set_many(&keys, 0, |missing| {
+                assert_eq!(missing, &keys[1..]);
+                Ok(missing.iter().map(|key| (key.to_string(), key.len() as u64)).collect())
+            })
+            .unwrap();
+        assert_eq!(result.len(), 4);
+        assert_eq!(result[keys[0]], 0);
+        assert_eq!(result[keys[3]], keys[3].len() as u64);
+
+        let result: HashMap<String, u64> = client
+            .get_or_set_many(&keys, 0, |_| panic!("all keys should be cached"))
+            .unwrap();
+        assert_eq!(result.len(), 4);
+
+        assert!(client
+            .get_or_set_many::<u64, _>(&[gen_random_key().as_str()], 0, |_| Err(memcache::ClientError::Error(
+                "loader failed".into()
+            )
+            .into()))
+            .is_err());
+    }
+}
+
+#[test]
+fn test_cow_values() {
+    use std::borrow::Cow;
+    let client = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    let key = gen_random_key();
+
+    client.set(key.as_str(), Cow::Borrowed("foo"), 0).unwrap();
+    let value: Option<Cow<str>> = client.get(key.as_str()).unwrap();
+    assert_eq!(value, Some(Cow::Owned("foo".to_string())));
+
+    client
+        .set(key.as_str(), Cow::<[u8]>::Owned(b"bar".to_vec()), 0)
+        .unwrap();
+    client.append(key.as_str(), Cow::Borrowed(&b"baz"[..])).unwrap();
+    let value: Option<Cow<[u8]>> = client.get(key.as_str()).unwrap();
+    assert_eq!(value.as_deref(), Some(&b"barbaz"[..]));
+}
+
+#[test]
+fn test_get_raw() {
+    let client = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    let key = gen_random_key();
+    assert_eq!(client.get_raw(key.as_str()).unwrap(), None);
+
+    client.set_many_with_flags(&[(key.as_str(), "foo", 0, 7)]).unwrap();
+    let (value, flags, cas) = client.get_raw(key.as_str()).unwrap().unwrap();
+    assert_eq!((value.as_slice(), flags), (&b"foo"[..], 7));
+    assert!(client.cas(key.as_str(), "bar", 0, cas.unwrap()).unwrap());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compression_threshold() {
+    let value: String = (0..20_000).map(|i| format!("{:05}", i)).collect();
+    assert_eq!(value.len(), 100_000);
+    for url in &[
+        "memcache://localhost:12345?protocol=ascii",
+        "memcache://localhost:12346",
+    ] {
+        let client = memcache::Client::builder()
+            .add_server(*url)
+            .unwrap()
+            .with_compression_threshold(1024)
+            .build()
+            .unwrap();
+        let key = gen_random_key();
+        client.set(key.as_str(), value.as_str(), 0).unwrap();
+        let (raw, flags, _) = client.get_raw(key.as_str()).unwrap().unwrap();
+        assert!(raw.len() < value.len());
+        assert_ne!(flags, 0);
+        let stored: Option<String> = client.get(key.as_str()).unwrap();
+        assert_eq!(stored.as_ref(), Some(&value));
+
+        // small values are stored as is
+        client.set(key.as_str(), "small", 0).unwrap();
+        let (raw, flags, _) = client.get_raw(key.as_str()).unwrap().unwrap();
+        assert_eq!((raw.as_slice(), flags), (&b"small"[..], 0));
+
+        let other_key = gen_random_key();
+        client
+            .set_many_concurrent(&[(key.clone(), value.as_str(), 0), (other_key.clone(), "small", 0)])
+            .unwrap();
+        let (raw, _, _) = client.get_raw(key.as_str()).unwrap().unwrap();
+        assert!(raw.len() < value.len());
+        assert_eq!(client.get::<String>(&key).unwrap().as_ref(), Some(&value));
+        assert_eq!(client.get::<String>(&other_key).unwrap().as_deref(), Some("small"));
+
+        let added_key = gen_random_key();
+        client.add_many(&[(added_key.as_str(), value.as_str(), 0)]).unwrap();
+        let (raw, _, _) = client.get_raw(added_key.as_str()).unwrap().unwrap();
+        assert!(raw.len() < value.len());
+        assert_eq!(client.get::<String>(&added_key).unwrap().as_ref(), Some(&value));
+
+        if url.contains("ascii") {
+            client.set(added_key.as_str(), "small", 100).unwrap();
+            assert!(client
+                .replace_only_if_older_than(&added_key, value.as_str(), 100, time::Duration::from_secs(0))
+                .unwrap());
+            let (raw, _, _) = client.get_raw(added_key.as_str()).unwrap().unwrap();
+            assert!(raw.len() < value.len());
+            assert_eq!(client.get::<String>(&added_key).unwrap().as_ref(), Some(&value));
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_metrics() {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let key = gen_random_key();
+    metrics::with_local_recorder(&recorder, || {
+        let client = memcache::Client::connect("memcache://localhost:12346").unwrap();
+        let _: Option<String> = client.get(key.as_str()).unwrap();
+        client.set(key.as_str(), "foo", 0).unwrap();
+        let _: Option<String> = client.get(key.as_str()).unwrap();
+        let _: Option<String> = client.get(key.as_str()).unwrap();
+        assert!(client.add(key.as_str(), "bar", 0).is_err());
+        assert_eq!(client.exists_batch(&[key.as_str()]).unwrap(), vec![true]);
+        client.version().unwrap();
+        client
+            .with_connection_for_key(key.as_str(), |connection| {
+                use memcache::ProtocolTrait;
+                connection.version()
+            })
+            .unwrap();
+    });
+
+    let mut counters = vec![];
+    let mut durations = 0;
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+        let labels: Vec<(String, String)> = key
+            .key()
+            .labels()
+            .map(|label| (label.key().to_string(), label.value().to_string()))
+            .collect();
+        assert!(labels.contains(&("server".to_string(), "localhost:12346".to_string())));
+        match (key.kind(), value) {
+            (MetricKind::Counter, DebugValue::Counter(count)) => {
+                assert_eq!(key.key().name(), "memcache.operations.total");
+                let label = |name: &str| labels.iter().find(|(k, _)| k == name).unwrap().1.clone();
+                counters.push((label("operation"), label("status"), count));
+            }
+            (MetricKind::Histogram, DebugValue::Histogram(values)) => {
+                assert_eq!(key.key().name(), "memcache.operation.duration_seconds");
+                durations += values.len();
+            }
+            (kind, _) => panic!("unexpected {:?} metric", kind),
+        }
+    }
+    counters.sort();
+    let counters: Vec<(&str, &str, u64)> = counters.iter().map(|(o, s, c)| (o.as_str(), s.as_str(), *c)).collect();
+    assert_eq!(
+        counters,
+        vec![
+            ("add", "error", 1),
+            ("exists_batch", "ok", 1),
+            ("get", "hit", 2),
+            ("get", "miss", 1),
+            ("set", "ok", 1),
+            ("version", "ok", 1),
+            ("with_connection", "ok", 1)
+        ]
+    );
+    assert_eq!(durations, 8);
+}
+
+#[test]
+fn test_add_many() {
+    for url in &[
+        "memcache://localhost:12345?protocol=ascii",
+        "memcache://localhost:12346",
+    ] {
+        let client = memcache::Client::connect(vec![*url, "memcache://localhost:12347"]).unwrap();
+        let keys: Vec<String> = (0..10).map(|_| gen_random_key()).collect();
+        for key in keys.iter().step_by(2) {
+            client.set(key.as_str(), "old", 0).unwrap();
+        }
+
+        let entries: Vec<(&str, &str, u32)> = keys.iter().map(|key| (key.as_str(), "new", 0)).collect();
+        let added = client.add_many(&entries).unwrap();
+        assert_eq!(added.len(), keys.len());
+        for (index, (key, added)) in keys.iter().zip(added).enumerate() {
+            assert_eq!(added.unwrap(), index % 2 == 1);
+            let value: Option<String> = client.get(key.as_str()).unwrap();
+            assert_eq!(value.unwrap(), if index % 2 == 1 { "new" } else { "old" });
+        }
+        assert!(client.add_many::<&str>(&[]).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_get_and_touch() {
+    for url in &[
+        "memcache://localhost:12345?protocol=ascii",
+        "memcache://localhost:12346",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        assert_eq!(client.get_and_touch::<String>(&key, 100).unwrap(), None);
+        client.set(key.as_str(), "bar", 1).unwrap();
+        let value: Option<String> = client.get_and_touch(&key, 100).unwrap();
+        assert_eq!(value.unwrap(), "bar");
+        thread::sleep(time::Duration::from_secs(2));
+        let value: Option<String> = client.get(&key).unwrap();
+        assert_eq!(value.unwrap(), "bar");
+    }
+}
+
+#[test]
+fn test_connection_callbacks() {
+    let port = spawn_noop_server();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (on_connect, on_disconnect) = (events.clone(), events.clone());
+    let client = memcache::Client::builder()
+        .add_server(format!("memcache://127.0.0.1:{}", port))
+        .unwrap()
+        .with_connect_callback(move |server| on_connect.lock().unwrap().push(format!("connect {}", server)))
+        .with_disconnect_callback(move |server| on_disconnect.lock().unwrap().push(format!("disconnect {}", server)))
+        .build()
+        .unwrap();
+    let server = format!("127.0.0.1:{}", port);
+    assert_eq!(*events.lock().unwrap(), vec![format!("connect {}", server)]);
+
+    assert!(client.get::<String>("foo").is_err());
+    // the pool may already be opening a new connection to replace the broken one
+    assert_eq!(events.lock().unwrap().get(1), Some(&format!("disconnect {}", server)));
+}
+
+#[cfg(feature = "ketama")]
+#[test]
+fn test_read_failover() {
+    let port = spawn_noop_server();
+    let client = memcache::Client::builder()
+        .add_server("memcache://localhost:12345")
+        .unwrap()
+        .add_server(format!("memcache://127.0.0.1:{}", port))
+        .unwrap()
+        .add_server("memcache://localhost:12346")
+        .unwrap()
+        .with_ketama_hashing()
+        .with_read_failover(1)
+        .build()
+        .unwrap();
+    // without the unreachable server, its keys go to its successor on the ring
+    let successors = memcache::Client::builder()
+        .add_server("memcache://localhost:12345")
+        .unwrap()
+        .add_server("memcache://localhost:12346")
+        .unwrap()
+        .with_ketama_hashing()
+        .build()
+        .unwrap();
+    let first_server = memcache::Client::connect("memcache://localhost:12345").unwrap();
+
+    // a key of the unreachable server whose successor is the first server, which isn't the next one in order
+    let key = (0..)
+        .map(|_| gen_random_key())
+        .find(|key| {
+            successors.set(key.as_str(), "successor", 0).unwrap();
+            client.set(key.as_str(), "unreachable", 0).is_err()
+                && first_server.get::<String>(key.as_str()).unwrap().is_some()
+        })
+        .unwrap();
+    assert_eq!(
+        client.get::<String>(key.as_str()).unwrap(),
+        Some("successor".to_string())
+    );
+}
+
+#[test]
+fn test_set_many_concurrent() {
+    let client = memcache::Client::connect(vec![
+        "memcache://localhost:12345",
+        "memcache://localhost:12346?protocol=ascii",
+        "memcache://localhost:12347",
+    ])
+    .unwrap();
+    let entries: Vec<(String, u64, u32)> = (0..20).map(|i| (gen_random_key(), i, 0)).collect();
+    client.set_many_concurrent(&entries).unwrap();
+    for (key, value, _) in &entries {
+        let stored: Option<u64> = client.get(key).unwrap();
+        assert_eq!(stored, Some(*value));
+    }
+
+    assert!(client.set_many_concurrent(&[("a".repeat(251), 0, 0)]).is_err());
+}
+
+#[test]
+fn test_warm_from_iterator() {
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    let prefix = gen_random_key();
+    let long_key = "a".repeat(251);
+    // the batch with the too long key fails, without stopping the next ones
+    let entries = (0..30).map(|i| match i {
+        15 => (long_key.clone(), i),
+        _ => (format!("{}_{}", prefix, i), i),
+    });
+    let mut progress = vec![];
+    let set = client
+        .warm_from_iterator_with_progress(entries, 0, 10, |set, consumed| progress.push((set, consumed)))
+        .unwrap();
+    assert_eq!(set, 20);
+    assert_eq!(progress, vec![(10, 10), (10, 20), (20, 30)]);
+    let value: Option<u32> = client.get(&format!("{}_29", prefix)).unwrap();
+    assert_eq!(value, Some(29));
+}
+
+#[test]
+fn test_healthcheck() {
+    use memcache::HealthStatus;
+
+    let broken = format!("memcache://127.0.0.1:{}", spawn_noop_server());
+    let other_broken = format!("memcache://127.0.0.1:{}", spawn_noop_server());
+    let build = |servers: Vec<&str>| memcache::Client::connect(servers).unwrap();
+    assert_eq!(
+        build(vec!["memcache://localhost:12345"]).healthcheck(),
+        HealthStatus::Healthy
+    );
+    let degraded = build(vec!["memcache://localhost:12345", &broken]).healthcheck();
+    assert_eq!(degraded, HealthStatus::Degraded(vec![broken.clone()]));
+    assert!(!degraded.is_ok());
+    assert_eq!(degraded.to_string(), format!("degraded, unreachable: {}", broken));
+    assert_eq!(
+        build(vec![&broken, &other_broken]).healthcheck(),
+        HealthStatus::Unhealthy(vec![broken.clone(), other_broken.clone()])
+    );
+}
+
+#[test]
+fn test_try_get() {
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    let key = gen_random_key();
+    client.set(key.as_str(), "bar", 0).unwrap();
+    assert_eq!(client.try_get::<String>(&key).unwrap(), Some("bar".to_string()));
+
+    // the pool has a single connection, which is taken
+    client
+        .try_with_connection_for_key(&key, |_| {
+            match client.try_with_connection_for_key(&key, |_| Ok(())) {
+                Err(memcache::MemcacheError::PoolExhausted) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+            match client.try_get::<String>(&key) {
+                Err(memcache::MemcacheError::PoolExhausted) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(client.try_get::<String>(&key).unwrap(), Some("bar".to_string()));
+}
+
+#[test]
+fn test_increment_quiet() {
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        client.set(key.as_str(), 10, 0).unwrap();
+        for _ in 0..3 {
+            client.increment_quiet(&key, 2).unwrap();
+        }
+        assert_eq!(client.get::<u64>(&key).unwrap(), Some(16));
+    }
+}
+
+#[test]
+fn test_increment_quiet_non_numeric() {
+    use memcache::ProtocolTrait;
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let disconnects = Arc::new(Mutex::new(0));
+        let on_disconnect = disconnects.clone();
+        let client = memcache::Client::builder()
+            .add_server(*url)
+            .unwrap()
+            .with_max_pool_size(1)
+            .with_disconnect_callback(move |_| *on_disconnect.lock().unwrap() += 1)
+            .build()
+            .unwrap();
+        let key = gen_random_key();
+        client.set(key.as_str(), "bar", 0).unwrap();
+        client.increment_quiet(&key, 1).unwrap();
+        // the error response of the failed increment mustn't be read as the response of the next command
+        assert_eq!(client.get::<String>(&key).unwrap(), Some("bar".to_string()));
+        assert_eq!(*disconnects.lock().unwrap(), 0);
+        // without a checkout in between, the connection isn't validated by the pool
+        let value: Option<String> = client
+            .with_connection_for_key(&key, |connection| {
+                connection.increment_noreply(&key, 1)?;
+                connection.get(&key)
+            })
+            .unwrap();
+        assert_eq!(value, Some("bar".to_string()));
+    }
+}
+
+#[test]
+fn test_pipeline_for_key() {
+    use memcache::PipelineReply;
+
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        let other = gen_random_key();
+        client.set(other.as_str(), "old", 0).unwrap();
+        let error = client
+            .pipeline_for_key(&key, |p| {
+                p.set(&key, "value", 0)?;
+                p.delete(&other)?;
+                p.set(&"a".repeat(300), "too long", 0)
+            })
+            .unwrap_err();
+        // the key of the last set is too long, so nothing was sent
+        assert_eq!(client.get::<String>(&other).unwrap(), Some("old".to_string()));
+        assert!(matches!(
+            error,
+            memcache::MemcacheError::ClientError(memcache::ClientError::KeyTooLong)
+        ));
+
+        let results = client
+            .pipeline_for_key(&key, |p| {
+                p.set(&key, "value", 0)?;
+                p.get(&key)?;
+                p.get("pipeline_missing_key")?;
+                p.delete(&other)?;
+                p.delete(&other)
+            })
+            .unwrap();
+        let replies: Vec<PipelineReply> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(replies[0], PipelineReply::Stored);
+        match &replies[1] {
+            PipelineReply::Value(Some((value, _, _))) => assert_eq!(value, b"value"),
+            reply => panic!("unexpected reply {:?}", reply),
+        }
+        assert_eq!(replies[2], PipelineReply::Value(None));
+        assert_eq!(replies[3], PipelineReply::Deleted(true));
+        assert_eq!(replies[4], PipelineReply::Deleted(false));
+        assert_eq!(client.pipeline_for_key(&key, |_| Ok(())).unwrap().len(), 0);
+    }
+}
+
+#[test]
+fn test_pipe_set() {
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let keys: Vec<String> = (0..5).map(|_| gen_random_key()).collect();
+        let entries: Vec<(&str, u32, u32)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.as_str(), i as u32, 10))
+            .collect();
+        client.pipe_set(&entries).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(client.get::<u32>(key).unwrap(), Some(i as u32));
+        }
+        // the connection is still in sync
+        assert!(client.version().is_ok());
+    }
+}
+
+#[test]
+fn test_get_batch_by_server() {
+    let client = memcache::Client::connect(vec!["memcache://localhost:12345", "memcache://localhost:12346"]).unwrap();
+    let keys: Vec<String> = (0..10).map(|_| gen_random_key()).collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    for key in &keys[..9] {
+        client.set(key, *key, 10).unwrap();
+    }
+    let result = client.get_batch_by_server::<String>(&keys).unwrap();
+    assert!(result
+        .keys()
+        .all(|url| url == "memcache://localhost:12345" || url == "memcache://localhost:12346"));
+    assert_eq!(result.values().map(std::collections::HashMap::len).sum::<usize>(), 9);
+    for values in result.values() {
+        for (key, value) in values {
+            assert_eq!(key, value);
+        }
+    }
+}
+
+#[test]
+fn test_get_with_cas() {
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        assert_eq!(client.get_with_cas::<String>(&key).unwrap(), None);
+        client.set(key.as_str(), "value", 0).unwrap();
+        let (value, cas) = client.get_with_cas::<String>(&key).unwrap().unwrap();
+        assert_eq!(value, "value");
+        assert!(!client.cas(key.as_str(), "new value", 0, cas + 1).unwrap());
+        assert!(client.cas(key.as_str(), "new value", 0, cas).unwrap());
+    }
+}
+
+#[test]
+fn test_get_and_delete() {
+    for url in &[
+        "memcache://localhost:12345",
+        "memcache://localhost:12345?protocol=ascii",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        client.set(key.as_str(), 42u32, 0).unwrap();
+        assert_eq!(client.get_and_delete::<u32>(&key).unwrap(), Some(42));
+        assert_eq!(client.get::<u32>(&key).unwrap(), None);
+        assert_eq!(client.get_and_delete::<u32>(&key).unwrap(), None);
+    }
+}
+
+#[test]
+fn test_replace_only_if_older_than() {
+    use std::time::Duration;
+
+    let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    let key = gen_random_key();
+    assert!(!client
+        .replace_only_if_older_than(&key, "value", 100, Duration::from_secs(0))
+        .unwrap());
+    client.set(key.as_str(), "value", 100).unwrap();
+    assert!(!client
+        .replace_only_if_older_than(&key, "new value", 100, Duration::from_secs(60))
+        .unwrap());
+    assert!(client
+        .replace_only_if_older_than(&key, "new value", 100, Duration::from_secs(0))
+        .unwrap());
+    assert_eq!(client.get::<String>(&key).unwrap(), Some(String::from("new value")));
+    assert!(client
+        .replace_only_if_older_than(&key, "value", 0, Duration::from_secs(0))
+        .is_err());
+
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    assert!(client
+        .replace_only_if_older_than(&key, "value", 100, Duration::from_secs(0))
+        .is_err());
+}
+
+#[test]
+fn test_set_flags() {
+    let client = memcache::Client::connect("memcache://localhost:12345?protocol=ascii").unwrap();
+    let key = gen_random_key();
+    client.set(key.as_str(), "value", 100).unwrap();
+    assert!(client.set_flags(&key, 7).unwrap());
+    let (value, flags, _) = client.get_raw(&key).unwrap().unwrap();
+    assert_eq!((value.as_slice(), flags), (&b"value"[..], 7));
+    let ttl = client.get_with_metadata::<String>(&key).unwrap().unwrap().3;
+    assert!(matches!(ttl, Some(ttl) if ttl > 0 && ttl <= 100));
+    assert!(!client.set_flags(&gen_random_key(), 7).unwrap());
+
+    // the binary protocol can't keep the time to live
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    assert!(client.set_flags(&key, 8).is_err());
+    let (_, flags, _) = client.get_raw(&key).unwrap().unwrap();
+    assert_eq!(flags, 7);
+}
+
+#[test]
+fn test_prune_stale_connections() {
+    let client = memcache::Client::builder()
+        .add_server("memcache://localhost:12345")
+        .unwrap()
+        .with_max_pool_size(3)
+        .build()
+        .unwrap();
+    assert_eq!(client.prune_stale_connections(), 0);
+    let (_, state) = &client.pool_metrics()[0];
+    assert_eq!(state.idle_connections, 3);
+    // the connections in use aren't checked, nor counted
+    let pruned = client
+        .with_connection_for_key("prune_stale_connections", |_| Ok(client.prune_stale_connections()))
+        .unwrap();
+    assert_eq!(pruned, 0);
+    client.set("prune_stale_connections", "value", 10).unwrap();
+
+    let (port, stop) = spawn_stoppable_noop_server();
+    let client = memcache::Client::builder()
+        .add_server(format!("memcache://127.0.0.1:{}", port))
+        .unwrap()
+        .with_max_pool_size(3)
+        .build()
+        .unwrap();
+    assert_eq!(client.prune_stale_connections(), 0);
+    // the idle connections to a server which went away fail the check
+    stop();
+    assert_eq!(client.prune_stale_connections(), 3);
+}
+
+#[test]
+fn test_flush_many() {
+    let client = memcache::Client::connect(vec![
+        "memcache://localhost:12345",
+        "memcache://localhost:12346?tcp_nodelay=true",
+    ])
+    .unwrap();
+    let key = gen_random_key();
+    client.set(key.as_str(), "value", 0).unwrap();
+
+    let flushed = client.flush_many(&["memcache://localhost:12346"]).unwrap();
+    assert_eq!(
+        flushed,
+        vec![
+            ("memcache://localhost:12345".to_string(), false),
+            ("memcache://localhost:12346?tcp_nodelay=true".to_string(), false),
+        ]
+    );
+    assert!(client.flush_many(&["not a url"]).is_err());
+
+    let flushed = client.flush_by_host("localhost").unwrap();
+    assert!(flushed.iter().all(|(_, flushed)| *flushed));
+    assert_eq!(client.get::<String>(&key).unwrap(), None);
+}
+
+#[test]
+fn test_pool_metrics() {
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    let metrics = client.pool_metrics();
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].0, "memcache://localhost:12345");
+    assert_eq!(metrics[0].1.connections, 1);
+    assert_eq!(client.total_pool_utilization(), 0.0);
+
+    client
+        .with_connection_for_key("foo", |_| {
+            assert_eq!(client.pool_metrics()[0].1.idle_connections, 0);
+            assert_eq!(client.total_pool_utilization(), 1.0);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(client.total_pool_utilization(), 0.0);
+}
+
+#[cfg(feature = "test-mode")]
+#[test]
+fn test_test_mode() {
+    use memcache::{InMemoryBackend, MockCall};
+
+    let mock = InMemoryBackend::new();
+    let client = memcache::Client::builder()
+        .add_server("memcache://localhost:1")
+        .unwrap()
+        .with_test_mode(mock.clone())
+        .build()
+        .unwrap();
+    client.set("foo", "bar", 60).unwrap();
+    client.set("counter", 40, 0).unwrap();
+    assert_eq!(client.increment("counter", 2).unwrap(), 42);
+    let values: std::collections::HashMap<String, String> = client.gets(&["foo", "counter", "missing"]).unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["counter"], "42");
+
+    let added = client.add_many(&[("foo", "baz", 0), ("new", "baz", 0)]).unwrap();
+    assert!(matches!(added[..], [Ok(false), Ok(true)]));
+    assert!(client.delete("new").unwrap());
+    assert!(client.replace("new", "baz", 0).is_err());
+
+    mock.assert_set_called_with("foo", "bar", 60);
+    assert_eq!(
+        mock.calls().last(),
+        Some(&MockCall {
+            command: "replace",
+            key: "new".to_string(),
+            value: Some(b"baz".to_vec()),
+            expiration: Some(0),
+        })
+    );
+    client.flush().unwrap();
+    let value: Option<String> = client.get("foo").unwrap();
+    assert_eq!(value, None);
+}
+
+#[test]
+fn test_get_with_metadata() {
+    for url in &[
+        "memcache://localhost:12345?protocol=ascii",
+        "memcache://localhost:12346",
+    ] {
+        let client = memcache::Client::connect(*url).unwrap();
+        let key = gen_random_key();
+        assert_eq!(client.get_with_metadata::<String>(key.as_str()).unwrap(), None);
+
+        client.set_many_with_flags(&[(key.as_str(), "foo", 100, 7)]).unwrap();
+        let (value, flags, cas, ttl) = client.get_with_metadata::<String>(key.as_str()).unwrap().unwrap();
+        assert_eq!((value.as_str(), flags), ("foo", 7));
+        let values: std::collections::HashMap<String, (Vec<u8>, u32, Option<u64>)> =
+            client.gets(&[key.as_str()]).unwrap();
+        assert_eq!(values[&key].2, Some(cas));
+        if url.contains("ascii") {
+            let ttl = ttl.unwrap();
+            assert!(ttl > 0 && ttl <= 100);
+        } else {
+            assert_eq!(ttl, None);
+        }
+
+        client.set(key.as_str(), "bar", 0).unwrap();
+        let (_, _, _, ttl) = client.get_with_metadata::<String>(key.as_str()).unwrap().unwrap();
+        assert!(ttl.is_none() || ttl == Some(-1));
+    }
+}
+
+#[test]
+fn test_client_builder_from_env() {
+    use memcache::ClientBuilder;
+    std::env::set_var(
+        "MEMCACHE_TEST_SERVERS",
+        " memcache://localhost:12345?protocol=ascii&timeout=10 ,memcache://localhost:12346?tcp_nodelay=true&timeout=1.5,\
+         memcache+tcp://localhost:12347, memcache:///tmp/memcached.sock ,",
+    );
+    let client = ClientBuilder::from_env("MEMCACHE_TEST_SERVERS")
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(client.version().unwrap().len(), 4);
+    let keys: Vec<String> = (0..10).map(|_| gen_random_key()).collect();
+    for key in keys.iter() {
+        client.set(key.as_str(), key.as_str(), 0).unwrap();
+    }
+    for key in keys.iter() {
+        let value: Option<String> = client.get(key.as_str()).unwrap();
+        assert_eq!(value.as_ref(), Some(key));
+    }
+
+    std::env::set_var(
+        "MEMCACHE_TEST_BAD_SERVERS",
+        "memcache://localhost:12345,redis://localhost:6379",
+    );
+    assert!(ClientBuilder::from_env("MEMCACHE_TEST_BAD_SERVERS").is_err());
+    std::env::set_var("MEMCACHE_TEST_EMPTY_SERVERS", " , ");
+    assert!(ClientBuilder::from_env("MEMCACHE_TEST_EMPTY_SERVERS").is_err());
+    assert!(ClientBuilder::from_env("MEMCACHE_TEST_MISSING_SERVERS").is_err());
+}
+
+#[test]
+fn test_append_noreply() {
+    use memcache::Client;
+    let clients = vec![
+        Client::connect("memcache://localhost:12346").unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+    ];
+    for client in clients {
+        let key = gen_random_key();
+        client.set(key.as_str(), "b", 0).unwrap();
+        for _ in 0..10 {
+            client.append_noreply(key.as_str(), "c").unwrap();
+        }
+        client.prepend_noreply(key.as_str(), "a").unwrap();
+        let value: Option<String> = client.get(key.as_str()).unwrap();
+        assert_eq!(value, Some(format!("ab{}", "c".repeat(10))));
+
+        // the failure isn't reported, and doesn't break the following commands
+        let missing_key = gen_random_key();
+        client.append_noreply(missing_key.as_str(), "c").unwrap();
+        let value: Option<String> = client.get(missing_key.as_str()).unwrap();
+        assert_eq!(value, None);
+
+        // nor the next command on the same connection
+        let value: Option<String> = client
+            .with_connection_for_key(missing_key.as_str(), |connection| {
+                use memcache::ProtocolTrait;
+                connection.prepend_noreply(missing_key.as_str(), "a")?;
+                connection.get(missing_key.as_str())
+            })
+            .unwrap();
+        assert_eq!(value, None);
+    }
+}
+
+#[test]
+fn test_shadow_write() {
+    let client = memcache::Client::connect("memcache://localhost:12345").unwrap();
+    let shadow = memcache::Client::connect("memcache://localhost:12346").unwrap();
+    let key = gen_random_key();
+    client.shadow_write(&shadow, &key, "bar", 10).unwrap();
+    assert_eq!(client.get::<String>(&key).unwrap().as_deref(), Some("bar"));
+    // the shadow write is run by a background thread
+    let mut value = None;
+    for _ in 0..100 {
+        value = shadow.get::<String>(&key).unwrap();
+        if value.is_some() {
+            break;
+        }
+        thread::sleep(time::Duration::from_millis(10));
+    }
+    assert_eq!(value.as_deref(), Some("bar"));
+
+    // a shadow server which doesn't answer doesn't slow down the writes
+    let silent =
+        memcache::Client::connect(format!("memcache://127.0.0.1:{}?timeout=2", spawn_silent_server())).unwrap();
+    let started = time::Instant::now();
+    for _ in 0..10 {
+        client.shadow_write(&silent, &key, "baz", 10).unwrap();
+    }
+    assert!(started.elapsed() < time::Duration::from_secs(1));
+    assert_eq!(client.get::<String>(&key).unwrap().as_deref(), Some("baz"));
+}
+
+#[test]
+fn test_with_connection_for_key_io_error() {
+    let disconnects = Arc::new(Mutex::new(0));
+    let on_disconnect = disconnects.clone();
+    let client = memcache::Client::builder()
+        .add_server("memcache://localhost:12345")
+        .unwrap()
+        .with_max_pool_size(1)
+        .with_disconnect_callback(move |_| *on_disconnect.lock().unwrap() += 1)
+        .build()
+        .unwrap();
+    let result: Result<(), memcache::MemcacheError> = client.with_connection_for_key("foo", |_| {
+        Err(std::io::Error::new(std::io::ErrorKind::Other, "unread response"))?
+    });
+    assert!(result.is_err());
+    // the connection is discarded instead of going back to the pool
+    assert_eq!(*disconnects.lock().unwrap(), 1);
+    client.version().unwrap();
+}
+
+#[test]
+fn test_delete_many_noreply() {
+    use memcache::{Client, ProtocolTrait};
+    let clients = vec![
+        Client::connect("memcache://localhost:12346").unwrap(),
+        Client::connect("memcache://localhost:12345?protocol=ascii").unwrap(),
+        Client::connect(vec!["memcache://localhost:12347", "memcache://localhost:12348"]).unwrap(),
+    ];
+    for client in clients {
+        let keys: Vec<String> = (0..10).map(|_| gen_random_key()).collect();
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_str()).collect();
+        for key in keys[..5].iter() {
+            client.set(key, "foo", 0).unwrap();
+        }
+        // the last keys are missing, which isn't reported
+        client.delete_many_noreply(&keys).unwrap();
+        assert_eq!(client.exists_batch(&keys).unwrap(), vec![false; 10]);
+
+        // the connections are still usable
+        client.set(keys[0], "bar", 0).unwrap();
+        let value: Option<String> = client.get(keys[0]).unwrap();
+        assert_eq!(value, Some("bar".into()));
+
+        // without a checkout in between, the connection isn't validated by the pool
+        let value: Option<String> = client
+            .with_connection_for_key(keys[0], |connection| {
+                connection.deletes_noreply(&[keys[9]])?;
+                connection.get(keys[0])
+            })
+            .unwrap();
+        assert_eq!(value, Some("bar".into()));
+    }
+}
+
+#[test]
+fn test_benchmark_servers() {
+    let client = memcache::Client::connect(vec![
+        "memcache://localhost:12346",
+        "memcache://localhost:12345?protocol=ascii",
+    ])
+    .unwrap();
+    let latencies = client.benchmark_servers(20).unwrap();
+    assert_eq!(latencies.len(), 2);
+    for latency in latencies {
+        assert!(latency.min <= latency.avg && latency.avg <= latency.max);
+        assert!(latency.p99 <= latency.max);
+    }
+    assert!(client.benchmark_servers(0).is_err());
+}